@@ -1,25 +1,135 @@
 // file src/app.rs
 
-use anyhow::{Error, Result};
+use crate::commands;
+use crate::config::{Modified, Owner, PrettyPrint};
+use crate::disk_index::DiskIndex;
+use crate::format::{self, FormatHint, ValueKind};
+use crate::modified;
+use crate::owner;
+use crate::protobuf::Descriptors;
+use crate::renderer::{RenderContext, Rendered, Renderers};
+use crate::task::Progress;
+use anyhow::{bail, Error, Result};
+use base64::Engine;
+use encoding_rs::Encoding;
+use sha2::{Digest, Sha256};
 use sled::Db;
-use std::collections::BTreeMap;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::rc::Rc;
 
 
 pub struct App {
     pub db: Option<Db>,
-    pub sled_trees: Vec<String>,
+    pub sled_trees: Vec<(Vec<u8>, String)>, // raw name, lossy display name
+    pub hidden_trees: Vec<String>, // globs of tree names to leave out of sled_trees
+    pub show_hidden_trees: bool,
+    pub tree_filter: Option<String>, // only list trees whose names contain this, ignoring case
+    pub tree_limit: Option<usize>, // most trees kept in sled_trees (--max-trees), raised by load more
+    pub sort_trees: bool,
+    pub matching_trees: usize, // trees that pass the filters, loaded or not
     pub current_tree: Option<sled::Tree>,
     pub current_path: Vec<String>, // current path within cached_key_tree
     pub delimiter: Option<String>,
+    pub detect_delimiter: bool, // pick each tree's delimiter from a sample of its keys
+    pub delimiter_detected: bool, // the current delimiter was picked by detection, not by hand
+    pub max_depth: Option<usize>, // deepest hierarchy level; the rest of a key stays unsplit
+    pub format_hints: BTreeMap<String, FormatHint>, // declared value formats, by tree name
+    pub format_hint: Option<FormatHint>, // the selected tree's declared format
+    pub protobuf: Option<Descriptors>,
+    pub protobuf_messages: BTreeMap<String, String>, // protobuf message types, by tree name
+    pub protobuf_message: Option<String>, // the selected tree's protobuf message type
+    pub encodings: BTreeMap<String, &'static Encoding>, // text encodings of values, by tree name
+    pub encoding: Option<&'static Encoding>, // the selected tree's
+    pub value_encodings: BTreeMap<String, &'static Encoding>, // overrides, by full key, in the selected tree
+    renderers: Renderers,
+    // The whole hierarchy, only built for the views that need all of it at once (the flat
+    // path view and R). Level by level navigation reads each level from the tree instead.
     cached_key_tree: Option<KeyTree>,
+    level: RefCell<Option<Level>>,
+    // The last value get_value read, by full key, so redrawing doesn't read it again.
+    // Anything that may change the tree drops it with invalidate_value.
+    value_cache: Option<(String, Option<Vec<u8>>)>,
+    // What the fields [modified] and [owner] name hold for each row of current_key_range,
+    // read when the window is loaded and again whenever values may have changed, so
+    // drawing doesn't read and parse every visible value
+    pub modified: Modified,
+    pub owner: Owner,
+    pub row_notes: Vec<RowNote>,
+    // --disk-index: the hierarchy lives on disk instead of in cached_key_tree. The index is
+    // built on a worker thread, so select_tree only marks it as needed.
+    pub use_disk_index: bool,
+    pub disk_index: Option<DiskIndex>,
+    pub disk_index_needed: bool,
     // current_key_range represents the keys to display in the left panel.
     // If no delimiter, offset and range are within set of all keys in the sled tree
     // if delimiter, offset and range are within the branch of cached_key_tree that is 
     // identified by current_path
     pub current_key_range: KeyRange, // (offset, visible_keys)  
     pub total_keys: usize, 
+    // When set, the left panel shows only these full keys (flat, even in delimiter mode)
+    pub key_filter: Option<KeyFilter>,
+    // While a / search is typed, the left panel shows only the entries of the current level
+    // containing this, ignoring case
+    pub key_search: Option<String>,
+    // Delimiter mode only: list every key of the tree at once, indented by shared prefix,
+    // instead of one level at a time
+    pub flat_paths: bool,
+    // List keys largest first. The flat path view keeps each branch above its children and
+    // reverses the order of siblings instead.
+    pub descending: bool,
+    flat_rows: Vec<FlatRow>,
+    // Keys of the tree that don't survive being split on the delimiter and joined back,
+    // with the first of them: not UTF-8, or holding the delimiter as data (empty segments)
+    pub unsplittable_keys: Option<(usize, String)>,
+    // --verify-writes: read every key back after a write or delete and fail if it differs
+    pub verify_writes: bool,
+    // --read-only: every write is refused before it reaches the database
+    pub read_only: bool,
 }
 
+// Delimiters tried by detection, and the ones the example database is built with
+pub const DELIMITERS: [&str; 8] = ["/", "\\", ":", "::", ",", ".", "-", "_"];
+
+// Keys sampled from the start of a tree to detect its delimiter
+const DELIMITER_SAMPLE: usize = 1000;
+
+
+// The delimiter that best splits a tree's keys into a hierarchy, if one clearly does: it
+// must appear in most keys, mostly not doubled (":" in "a::b" is part of a longer delimiter),
+// and group them under shared first segments. Ties go to the longer delimiter.
+pub fn detect_delimiter(tree: &sled::Tree) -> Result<Option<String>> {
+    let keys: Vec<String> = tree.iter()
+        .keys()
+        .take(DELIMITER_SAMPLE)
+        .map(|key| Ok(String::from_utf8_lossy(&key?).to_string()))
+        .collect::<Result<_>>()?;
+    let best = DELIMITERS.iter()
+        .filter_map(|&delimiter| {
+            let containing: Vec<&String> = keys.iter().filter(|key| key.contains(delimiter)).collect();
+            if containing.len() < 2 || containing.len() * 2 < keys.len() {
+                return None;
+            }
+            let doubled = delimiter.repeat(2);
+            if containing.iter().filter(|key| key.contains(&doubled)).count() * 2 > containing.len() {
+                return None;
+            }
+            let first_segments: std::collections::BTreeSet<&str> = containing.iter()
+                .filter_map(|key| key.split(delimiter).next())
+                .collect();
+            if first_segments.len() * 2 > containing.len() {
+                return None;
+            }
+            Some((containing.len(), delimiter.len(), delimiter))
+        })
+        .max();
+    Ok(best.map(|(_, _, delimiter)| delimiter.to_string()))
+}
+
+
 struct KeyTree {
     keys: BTreeMap<String, KeyNode>,
 }
@@ -28,10 +138,38 @@ struct KeyNode {
     children: BTreeMap<String, KeyNode>,
 }
 
+// The annotations of one row of the key list
+#[derive(Default)]
+pub struct RowNote {
+    pub modified_at: Option<i64>, // seconds since the Unix epoch
+    pub owner: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct KeyEntry {
     pub key: String,
     pub has_children: bool,
+    // entries directly under a branch, up to MAX_CHILD_COUNT + 1; 0 when not counted
+    pub child_count: usize,
+}
+
+// Most children counted under a branch when its level is read from the tree, so a huge
+// branch can't stall the list; more are shown as "1000+"
+pub const MAX_CHILD_COUNT: usize = 1000;
+
+// The entries of the level at a path, read the first time they are asked for
+struct Level {
+    path: Vec<String>,
+    entries: Rc<Vec<KeyEntry>>,
+    child_counts: HashMap<String, usize>, // of the branches that have been on screen
+}
+
+// One row of the flat path view
+struct FlatRow {
+    depth: usize,
+    name: String, // last segment
+    full_key: String,
+    has_children: bool,
 }
 
 pub struct  KeyRange {
@@ -39,92 +177,352 @@ pub struct  KeyRange {
     pub keys: Vec<KeyEntry>,
 }
 
+// The values of every key under a branch joined together, in key order
+pub struct Concatenated {
+    pub bytes: Vec<u8>,
+    pub keys: usize,
+    pub truncated: bool, // stopped at the size limit
+}
+
+// A fixed list of full keys to show instead of the normal key list
+pub struct KeyFilter {
+    pub label: String, // shown in the list title, e.g. "json"
+    pub keys: Vec<String>,
+    pub search: Option<String>, // the needle, when the filter is a key search
+}
+
+// Key -> hash of its value for every key in a tree, to compare the tree against later
+pub type Baseline = BTreeMap<Vec<u8>, u64>;
+
+// How a key differs from the baseline
+#[derive(Clone, Copy, PartialEq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+// Output layouts for exporting the key hierarchy without values
+#[derive(Clone, Copy)]
+pub enum KeyTreeFormat {
+    Text, // indented outline, one segment per line
+    Json, // nested objects of segment names, leaves are null
+}
+
+// How the value pane shows a value: as text (pretty-printed, as a table and so on, as the
+// other toggles say), or its raw bytes in one of three forms. c steps through them all,
+// x toggles straight between text and hex.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ValueView {
+    Text,
+    Escaped, // UTF-8 with invalid bytes as \xNN
+    Base64,
+    Hex,
+}
+
+impl ValueView {
+    pub fn next(self) -> Self {
+        match self {
+            ValueView::Text => ValueView::Escaped,
+            ValueView::Escaped => ValueView::Base64,
+            ValueView::Base64 => ValueView::Hex,
+            ValueView::Hex => ValueView::Text,
+        }
+    }
+
+    // shown beside the wrap mode in the value title
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            ValueView::Text => None,
+            ValueView::Escaped => Some("ESCAPED"),
+            ValueView::Base64 => Some("BASE64"),
+            ValueView::Hex => Some("HEX"),
+        }
+    }
+}
+
 impl App {
     pub fn new() -> Self {
         Self {
             db: None,
             sled_trees: vec![],
+            hidden_trees: vec![],
+            show_hidden_trees: false,
+            tree_filter: None,
+            tree_limit: None,
+            sort_trees: true,
+            matching_trees: 0,
             current_tree: None,
             current_path: vec![],
             delimiter: None,
+            detect_delimiter: false,
+            delimiter_detected: false,
+            max_depth: None,
+            format_hints: BTreeMap::new(),
+            format_hint: None,
+            protobuf: None,
+            renderers: Renderers::builtin(),
+            protobuf_messages: BTreeMap::new(),
+            protobuf_message: None,
+            encodings: BTreeMap::new(),
+            encoding: None,
+            value_encodings: BTreeMap::new(),
             cached_key_tree: None,
+            level: RefCell::new(None),
+            value_cache: None,
+            modified: Modified::default(),
+            owner: Owner::default(),
+            row_notes: vec![],
+            use_disk_index: false,
+            disk_index: None,
+            disk_index_needed: false,
             current_key_range: KeyRange{ offset: 0, keys: vec![] },
             total_keys: 0,
+            key_filter: None,
+            key_search: None,
+            flat_paths: false,
+            descending: false,
+            flat_rows: vec![],
+            unsplittable_keys: None,
+            verify_writes: false,
+            read_only: false,
         }
     }
 
     fn build_key_tree(&mut self) -> Result<()> {
-        if let Some(tree) = &self.current_tree {
-            // If we have a delimiter, build the hierarchical tree
-            if let Some(delimiter) = &self.delimiter {
-                let mut key_tree = KeyTree {
-                    keys: BTreeMap::new(),
-                };
+        if self.use_disk_index {
+            self.disk_index = None;
+            self.disk_index_needed = self.current_tree.is_some() && self.delimiter.is_some();
+            self.total_keys = 0;
+            return Ok(());
+        }
+        self.cached_key_tree = None;
+        self.level.replace(None);
+        if let (Some(tree), Some(delimiter)) = (&self.current_tree, &self.delimiter) {
+            if self.flat_paths {
+                let index = index_keys(tree, delimiter, self.max_depth, None)?;
+                self.install_key_index(index);
+            } else {
+                // levels are read as they are opened, so only a sample is checked up front
+                self.unsplittable_keys = unsplittable_sample(tree, delimiter, self.max_depth)?;
+            }
+        }
+        Ok(())
+    }
 
-                for result in tree.iter() {
-                    let (key, _) = result?;
-                    let key_str = String::from_utf8_lossy(&key).to_string();
-                    let parts: Vec<&str> = key_str.split(delimiter).collect();
-                    
-                    let mut current = &mut key_tree.keys;
-                    for part in parts.iter() {
-                        let entry = current.entry(part.to_string()).or_insert_with(|| KeyNode {
-                            children: BTreeMap::new(),
-                        });
-                        current = &mut entry.children;
+
+    // Swap in a freshly built key hierarchy. Returns false when current_path no longer
+    // exists in it and was cut back to the nearest level that does.
+    pub fn install_key_index(&mut self, index: KeyIndex) -> bool {
+        self.cached_key_tree = Some(index.key_tree);
+        self.level.replace(None);
+        self.invalidate_value();
+        self.unsplittable_keys = index.unsplittable_keys;
+        self.build_flat_rows();
+        let kept = self.trim_current_path();
+        self.total_keys = self.total_keys();
+        kept
+    }
+
+
+    // Cut current_path back to its deepest level that still has keys under it, after the
+    // tree changed underneath. Returns false if anything was cut.
+    pub fn trim_current_path(&mut self) -> bool {
+        let depth = (1..=self.current_path.len()).rev()
+            .find(|&depth| self.path_exists(&self.current_path[..depth]))
+            .unwrap_or(0);
+        let kept = depth == self.current_path.len();
+        self.current_path.truncate(depth);
+        kept
+    }
+
+    fn path_exists(&self, path: &[String]) -> bool {
+        if let Some(index) = &self.disk_index {
+            return index.child_count(path).is_ok_and(|count| count > 0);
+        }
+        if let Some(key_tree) = &self.cached_key_tree {
+            let mut level = &key_tree.keys;
+            for segment in path {
+                match level.get(segment) {
+                    Some(node) => level = &node.children,
+                    None => return false,
+                }
+            }
+            return !level.is_empty();
+        }
+        self.read_level(path, 1).is_ok_and(|entries| !entries.is_empty())
+    }
+
+
+    // Up to `limit` entries of the level at `path`, read from the tree
+    fn read_level(&self, path: &[String], limit: usize) -> Result<Vec<KeyEntry>> {
+        let (Some(tree), Some(delimiter)) = (&self.current_tree, &self.delimiter) else { return Ok(vec![]) };
+        let prefix = match path.is_empty() {
+            true => String::new(),
+            false => format!("{}{}", path.join(delimiter), delimiter),
+        };
+        let split = self.max_depth.is_none_or(|depth| path.len() + 1 < depth.max(1));
+        scan_level(tree, prefix.as_bytes(), delimiter, split, limit)
+    }
+
+    // The entries of the current level: the whole hierarchy's when it is built, else read
+    // from the tree once and kept until the path or the tree changes
+    fn level_entries(&self) -> Result<Rc<Vec<KeyEntry>>> {
+        if let Some(key_tree) = &self.cached_key_tree {
+            let mut level = &key_tree.keys;
+            for segment in &self.current_path {
+                match level.get(segment) {
+                    Some(node) => level = &node.children,
+                    None => return Ok(Rc::new(vec![])),
+                }
+            }
+            let entries = level.iter()
+                .map(|(name, node)| KeyEntry { key: name.clone(), has_children: !node.children.is_empty(), child_count: node.children.len() })
+                .collect();
+            return Ok(Rc::new(entries));
+        }
+        if let Some(level) = &*self.level.borrow() {
+            if level.path == self.current_path {
+                return Ok(level.entries.clone());
+            }
+        }
+        let entries = Rc::new(self.read_level(&self.current_path, usize::MAX)?);
+        self.level.replace(Some(Level { path: self.current_path.clone(), entries: entries.clone(), child_counts: HashMap::new() }));
+        Ok(entries)
+    }
+
+    // Count the entries directly under each branch among `entries` of the current level that
+    // hasn't been counted, reading only those branches from the tree, and remember the counts
+    // with the level
+    fn count_children(&self, entries: &mut [KeyEntry]) -> Result<()> {
+        for entry in entries.iter_mut().filter(|entry| entry.has_children && entry.child_count == 0) {
+            let known = self.level.borrow().as_ref()
+                .filter(|level| level.path == self.current_path)
+                .and_then(|level| level.child_counts.get(&entry.key).copied());
+            entry.child_count = match known {
+                Some(count) => count,
+                None => {
+                    let mut path = self.current_path.clone();
+                    path.push(entry.key.clone());
+                    let count = self.read_level(&path, MAX_CHILD_COUNT + 1)?.len();
+                    if let Some(level) = self.level.borrow_mut().as_mut().filter(|level| level.path == self.current_path) {
+                        level.child_counts.insert(entry.key.clone(), count);
                     }
+                    count
                 }
-                self.cached_key_tree = Some(key_tree);
+            };
+        }
+        Ok(())
+    }
+
+
+    // Walk the cached key tree into the rows of the flat path view
+    fn build_flat_rows(&mut self) {
+        fn walk(nodes: &BTreeMap<String, KeyNode>, depth: usize, prefix: &str, delimiter: &str, descending: bool, rows: &mut Vec<FlatRow>) {
+            let nodes: Box<dyn Iterator<Item = (&String, &KeyNode)>> = match descending {
+                true => Box::new(nodes.iter().rev()),
+                false => Box::new(nodes.iter()),
+            };
+            for (name, node) in nodes {
+                let full_key = if depth == 0 { name.clone() } else { format!("{}{}{}", prefix, delimiter, name) };
+                rows.push(FlatRow { depth, name: name.clone(), full_key: full_key.clone(), has_children: !node.children.is_empty() });
+                walk(&node.children, depth + 1, &full_key, delimiter, descending, rows);
             }
         }
+        self.flat_rows.clear();
+        if let (true, Some(tree), Some(delimiter)) = (self.flat_paths, &self.cached_key_tree, &self.delimiter) {
+            walk(&tree.keys, 0, "", delimiter, self.descending, &mut self.flat_rows);
+        }
+    }
+
+
+    // Switch between the flat path view and level by level navigation, starting from the top
+    // Switching it on reads the whole hierarchy, which the view lists at once
+    pub fn set_flat_paths(&mut self, flat_paths: bool) -> Result<()> {
+        self.flat_paths = flat_paths;
+        self.current_path.clear();
+        if self.disk_index.is_none() {
+            self.build_key_tree()?;
+        }
+        self.build_flat_rows();
+        self.total_keys = self.total_keys();
         Ok(())
     }
 
-    // Get a range of keys, either from the cached_key_tree (if delimiter) or the DB (if not),
+    // List keys largest first or smallest first, from the top again
+    pub fn set_descending(&mut self, descending: bool) {
+        self.descending = descending;
+        self.build_flat_rows();
+    }
+
+    // Get a range of keys, either from the current level (if delimiter) or the DB (if not),
     // and cache it in current_key_range so it can be used to render and to reference keys by index. 
+    // offset counts from the last key instead when descending.
     pub fn set_key_range(&mut self, offset: usize, count: usize) -> Result<()> {
-        if self.delimiter.is_none() {
+        if let Some(filter) = &self.key_filter {
+            let keys = window(filter.keys.iter(), offset, count, self.descending)
+                .into_iter()
+                .map(|key| KeyEntry { key: key.clone(), has_children: false, child_count: 0 })
+                .collect();
+            self.current_key_range = KeyRange{offset, keys};
+        } else if let Some(needle) = self.key_search.clone() {
+            self.set_filtered_key_range(offset, count, &needle)?;
+        } else if self.flat_paths && self.delimiter.is_some() {
+            // branches end with the delimiter; leaves show their whole key. Neither can be
+            // drilled into, the rows already cover every level.
+            let delimiter = self.delimiter.as_deref().unwrap_or_default();
+            let keys = self.flat_rows
+                .iter()
+                .skip(offset)
+                .take(count)
+                .map(|row| {
+                    let indent = "  ".repeat(row.depth);
+                    let key = if row.has_children {
+                        format!("{}{}{}", indent, row.name, delimiter)
+                    } else {
+                        format!("{}{}", indent, row.full_key)
+                    };
+                    KeyEntry { key, has_children: false, child_count: 0 }
+                })
+                .collect();
+            self.current_key_range = KeyRange{offset, keys};
+        } else if self.delimiter.is_none() {
             // Use sled's range functionality for flat key list
             if let Some(tree) = &self.current_tree {
                 let mut keys = Vec::with_capacity(count);
-                for result in tree.iter().skip(offset).take(count) {
+                for result in window(tree.iter(), offset, count, self.descending) {
                     let (key, _) = result?;
                     keys.push(KeyEntry {
                         key: String::from_utf8_lossy(&key).to_string(),
                         has_children: false,
+                        child_count: 0,
                     });
                 }
                 self.current_key_range = KeyRange{offset, keys};
             } else {
                 self.current_key_range = KeyRange{offset: 0, keys: vec![]};
             }
+        } else if let Some(index) = &self.disk_index {
+            let keys = if self.descending {
+                // the same window counted from the other end, read in order and turned round
+                let end = index.child_count(&self.current_path)?.saturating_sub(offset);
+                let start = end.saturating_sub(count);
+                let mut keys = index.children(&self.current_path, start, end - start)?;
+                keys.reverse();
+                keys
+            } else {
+                index.children(&self.current_path, offset, count)?
+            };
+            self.current_key_range = KeyRange{offset, keys};
+        } else if self.use_disk_index {
+            // the index is still being built
+            self.current_key_range = KeyRange{offset: 0, keys: vec![]};
         } else {
-            // Use cached key tree for hierarchical keys
-            // the key tree is cached when the sled tree is first selected
-            if let Some(tree) = &self.cached_key_tree {
-                let mut current = &tree.keys;
-                for path_segment in &self.current_path {
-                    if let Some(node) = current.get(path_segment) {
-                        current = &node.children;
-                    } else {
-                        self.current_key_range = KeyRange{offset: 0, keys: vec![]};
-                    }
-                }
-
-                let keys: Vec<KeyEntry> = current
-                    .iter()
-                    .skip(offset)
-                    .take(count)
-                    .map(|(k, v)| KeyEntry {
-                        key: k.clone(),
-                        has_children: !v.children.is_empty(),
-                    })
-                    .collect();
-                    self.current_key_range = KeyRange{offset, keys};
-                } else {
-                self.current_key_range = KeyRange{offset: 0, keys: vec![]};
-            }
+            let mut keys: Vec<KeyEntry> = window(self.level_entries()?.iter(), offset, count, self.descending).into_iter().cloned().collect();
+            self.count_children(&mut keys)?;
+            self.current_key_range = KeyRange{offset, keys};
         }
+        self.read_row_notes();
         Ok(())
     }
 
@@ -132,48 +530,120 @@ impl App {
     // Total number of keys that can be scrolled in the left pane
     fn total_keys(&self) -> usize {
         if self.current_tree.is_none() { return 0 }
+        if let Some(filter) = &self.key_filter { return filter.keys.len() }
+        if let Some(needle) = &self.key_search { return self.search_level(needle).map_or(0, |entries| entries.len()) }
         if self.delimiter.is_none() { return (self.current_tree.as_ref().expect("This is a bug. There should be a guard clause immediately before this.")).len() }
-        if self.cached_key_tree.is_none() { return 0 }
-        let mut current = &self.cached_key_tree.as_ref().expect("This is a bug. There should be a guard clause immediately before this.").keys;
-        for path_segment in &self.current_path {
-            if let Some(node) = current.get(path_segment) {
-                current = &node.children;
-            } else {
-                return 0;
-            }
-        }
-        current.iter().count()
+        if self.flat_paths { return self.flat_rows.len() }
+        if let Some(index) = &self.disk_index { return index.child_count(&self.current_path).unwrap_or(0) }
+        if self.use_disk_index { return 0 }
+        self.level_entries().map_or(0, |entries| entries.len())
     }        
 
 
+    // True when the current level is the deepest one max_depth allows
+    pub fn at_max_depth(&self) -> bool {
+        self.delimiter.is_some() && self.max_depth.is_some_and(|depth| self.current_path.len() + 1 >= depth)
+    }
+
+
+    // Recount the keys that can be scrolled, e.g. after the tree was changed by another writer
+    pub fn refresh_total_keys(&mut self) {
+        self.total_keys = self.total_keys();
+    }
+
+
+    // Re-read the selected tree after it may have been changed by another writer
+    pub fn reload_tree(&mut self) -> Result<()> {
+        self.invalidate_value();
+        if self.delimiter.is_some() {
+            self.build_key_tree()?;
+        }
+        self.refresh_total_keys();
+        Ok(())
+    }
+
+
     // Refresh the list of sled trees that are available for selection in this DB
     pub fn refresh_trees(&mut self) -> Result<()> {
         if let Some(db) = &self.db {
-            let mut trees: Vec<String> = db.tree_names()
+            // binary names can decode to the same display name, so keep the raw bytes to open by
+            let mut trees = db.tree_names()
                     .into_iter()
-                    .map(|name| String::from_utf8_lossy(&name).to_string())
-                    .collect();
-                trees.sort();
-            self.sled_trees = trees;
+                    .map(|name| (name.to_vec(), String::from_utf8_lossy(&name).to_string()))
+                    .filter(|(_, name)| self.show_hidden_trees || !self.hidden_trees.iter().any(|pattern| glob_match(pattern, name)))
+                    .filter(|(_, name)| self.tree_filter.as_ref().is_none_or(|filter| name.to_lowercase().contains(&filter.to_lowercase())));
+            let limit = self.tree_limit.unwrap_or(usize::MAX);
+            if self.sort_trees {
+                // sorting needs every name, but only the first `limit` are kept
+                let mut all: Vec<(Vec<u8>, String)> = trees.collect();
+                all.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+                self.matching_trees = all.len();
+                all.truncate(limit);
+                self.sled_trees = all;
+            } else {
+                // in sled's order, so only the first `limit` are kept and the rest just counted
+                self.sled_trees = trees.by_ref().take(limit).collect();
+                self.matching_trees = self.sled_trees.len() + trees.count();
+            }
         }
         Ok(())
     }
 
 
+    // A database with nothing in it yet: just the default tree, and that empty
+    pub fn is_empty_database(&self) -> bool {
+        self.db.as_ref().is_some_and(|db| db.tree_names().len() == 1 && db.is_empty())
+    }
+
+
     // Select a particular sled tree and cache a tree of it's hierarchical keys if a delimiter is set
     pub fn select_tree(&mut self, index: usize) -> Result<()> {
         if let Some(db) = &self.db {
-            self.current_tree = Some(db.open_tree(&self.sled_trees[index])?);
-            self.current_path.clear();
-            if self.delimiter.is_some() {
-                self.build_key_tree()?;
+            self.current_tree = Some(db.open_tree(&self.sled_trees[index].0)?);
+            self.format_hint = self.format_hints.get(&self.sled_trees[index].1).copied();
+            self.protobuf_message = self.protobuf_messages.get(&self.sled_trees[index].1).cloned();
+            self.encoding = self.encodings.get(&self.sled_trees[index].1).copied();
+            self.value_encodings.clear();
+            if self.detect_delimiter {
+                let tree = self.current_tree.as_ref().expect("This is a bug. The tree was opened just above.");
+                self.delimiter = detect_delimiter(tree)?;
+                self.delimiter_detected = true;
             }
-            self.total_keys = self.total_keys();
+            self.set_delimiter(self.delimiter.clone())?;
         }
         Ok(())
     }
 
 
+    // The delimiter settings: one fixed delimiter for every tree ("" for none), or else
+    // detection when it's on
+    pub fn configure_delimiter(&mut self, detect: bool, fixed: Option<&str>) {
+        match fixed {
+            Some(delimiter) => {
+                self.detect_delimiter = false;
+                self.delimiter = (!delimiter.is_empty()).then(|| delimiter.to_string());
+            }
+            None => self.detect_delimiter = detect,
+        }
+    }
+
+
+    // Split the current tree's keys on another delimiter (None for a flat list), starting
+    // again from the top
+    pub fn set_delimiter(&mut self, delimiter: Option<String>) -> Result<()> {
+        self.delimiter = delimiter;
+        self.invalidate_value();
+        self.current_path.clear();
+        self.key_filter = None;
+        self.cached_key_tree = None;
+        self.unsplittable_keys = None;
+        self.flat_rows.clear();
+        self.build_key_tree()?;
+        self.total_keys = self.total_keys();
+        Ok(())
+    }
+
+
     // Navigate down the key hierachy - should only be used if a delimiter is set
     pub fn select_key(&mut self, index: usize) -> Result<()> {
         if self.current_tree.is_some() && self.delimiter.is_some() {
@@ -184,31 +654,1055 @@ impl App {
     }    
 
 
-    // get the value associated with a particular current key
+    // The first keys of a tree, without selecting it
+    pub fn peek_tree(&self, index: usize, count: usize) -> Result<Vec<String>> {
+        let (Some(db), Some(name)) = (&self.db, self.sled_trees.get(index)) else {
+            return Ok(vec![]);
+        };
+        let tree = db.open_tree(&name.0)?;
+        tree.iter()
+            .keys()
+            .take(count)
+            .map(|key| Ok(String::from_utf8_lossy(&key?).to_string()))
+            .collect()
+    }
+
+
+    // The immediate children of a branch in current_key_range, without navigating into it.
+    // None for leaves and when there is no hierarchy.
+    pub fn peek_children(&self, index: usize, count: usize) -> Option<Vec<String>> {
+        let entry = self.current_key_range.keys.get(index)?;
+        if !entry.has_children || self.key_filter.is_some() {
+            return None;
+        }
+        if let Some(index) = &self.disk_index {
+            let mut path = self.current_path.clone();
+            path.push(entry.key.clone());
+            return index.children(&path, 0, count).ok().map(|entries| entries.into_iter().map(|entry| entry.key).collect());
+        }
+        let mut path = self.current_path.clone();
+        path.push(entry.key.clone());
+        self.read_level(&path, count).ok().map(|entries| entries.into_iter().map(|entry| entry.key).collect())
+    }
+
+
+    // Show only the given keys in the left panel, or the normal key list again with None
+    pub fn set_key_filter(&mut self, filter: Option<KeyFilter>) {
+        self.key_filter = filter;
+        self.total_keys = self.total_keys();
+    }
+
+    pub fn set_key_search(&mut self, needle: Option<String>) {
+        self.key_search = needle.filter(|needle| !needle.is_empty());
+        self.total_keys = self.total_keys();
+    }
+
+    // Like set_key_range, but only the entries of the current level containing `needle`.
+    // total_keys becomes the number of matches.
+    pub fn set_filtered_key_range(&mut self, offset: usize, count: usize, needle: &str) -> Result<()> {
+        let entries = self.search_level(needle)?;
+        self.total_keys = entries.len();
+        // the flat path view's rows are already in the order they are shown
+        let descending = self.descending && !(self.flat_paths && self.delimiter.is_some());
+        let mut keys = window(entries.into_iter(), offset, count, descending);
+        if self.delimiter.is_some() && !self.flat_paths && self.disk_index.is_none() {
+            self.count_children(&mut keys)?;
+        }
+        self.current_key_range = KeyRange{offset, keys};
+        Ok(())
+    }
+
+    // Every entry of the current level containing `needle`, ignoring case: keys of the tree
+    // without a delimiter, names in the current branch with one. The flat path view matches
+    // on whole keys and lists them whole.
+    fn search_level(&self, needle: &str) -> Result<Vec<KeyEntry>> {
+        let needle = needle.to_lowercase();
+        let matches = |key: &str| key.to_lowercase().contains(&needle);
+        let Some(tree) = &self.current_tree else { return Ok(vec![]) };
+        if self.delimiter.is_none() {
+            let mut entries = vec![];
+            for result in tree.iter().keys() {
+                let key = String::from_utf8_lossy(&result?).to_string();
+                if matches(&key) {
+                    entries.push(KeyEntry { key, has_children: false, child_count: 0 });
+                }
+            }
+            return Ok(entries);
+        }
+        if self.flat_paths {
+            return Ok(self.flat_rows.iter()
+                .filter(|row| matches(&row.full_key))
+                .map(|row| KeyEntry { key: row.full_key.clone(), has_children: false, child_count: 0 })
+                .collect());
+        }
+        if let Some(index) = &self.disk_index {
+            let mut entries = index.children(&self.current_path, 0, usize::MAX)?;
+            entries.retain(|entry| matches(&entry.key));
+            return Ok(entries);
+        }
+        Ok(self.level_entries()?.iter().filter(|entry| matches(&entry.key)).cloned().collect())
+    }
+
+
+    // The full sled key of an entry in current_key_range
+    pub fn full_key(&self, index: usize) -> Option<String> {
+        let entry = self.current_key_range.keys.get(index)?;
+        if self.key_filter.is_some() {
+            return Some(entry.key.clone());
+        }
+        if self.flat_paths && self.delimiter.is_some() && self.key_search.is_some() {
+            return Some(entry.key.clone());
+        }
+        if self.flat_paths && self.delimiter.is_some() {
+            return self.flat_rows.get(self.current_key_range.offset + index).map(|row| row.full_key.clone());
+        }
+        let mut new_path = self.current_path.clone();
+        new_path.push(entry.key.clone());
+        Some(new_path.join(self.delimiter.as_deref().unwrap_or_default()))
+    }
+
+
+    // Step by step account of how an entry's full key is built and looked up, for debugging
+    pub fn explain_key(&self, index: usize) -> Vec<String> {
+        let Some(entry) = self.current_key_range.keys.get(index) else {
+            return vec!["No entry selected".to_string()];
+        };
+        let mut lines = vec![
+            format!("entry:      {:?}", entry.key),
+            format!("path:       {:?}", self.current_path),
+            format!("delimiter:  {:?}", self.delimiter),
+            format!("filtered:   {}", self.key_filter.is_some()),
+        ];
+        let Some(full_key) = self.full_key(index) else {
+            return lines;
+        };
+        let hex: Vec<String> = full_key.as_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        lines.push(format!("full key:   {:?}", full_key));
+        lines.push(format!("key bytes:  {}", hex.join(" ")));
+        let lookup = match &self.current_tree {
+            Some(tree) => match tree.get(full_key.as_bytes()) {
+                Ok(Some(value)) => format!("found, {} bytes", value.len()),
+                Ok(None) => "not found".to_string(),
+                Err(e) => format!("error: {}", e),
+            },
+            None => "no tree selected".to_string(),
+        };
+        lines.push(format!("tree.get:   {}", lookup));
+        lines
+    }
+
+
+    // The stored value of a full key in the selected tree
+    pub fn value_of(&self, full_key: &str) -> Result<Option<Vec<u8>>> {
+        let Some(tree) = &self.current_tree else { return Ok(None) };
+        Ok(tree.get(full_key.as_bytes())?.map(|value| value.to_vec()))
+    }
+
+
+    // The encoding a key's value is shown in: its own override, else its tree's. None is
+    // UTF-8, with invalid bytes shown as U+FFFD.
+    pub fn encoding_of(&self, full_key: &str) -> Option<&'static Encoding> {
+        self.value_encodings.get(full_key).copied().or(self.encoding)
+    }
+
+
+    pub fn writable(&self) -> Result<()> {
+        if self.read_only {
+            bail!("read-only mode: write blocked");
+        }
+        Ok(())
+    }
+
+
+    // Store a new value only if the key still holds `expected`, so a concurrent change
+    // isn't overwritten. Returns false, writing nothing, when the value has moved on.
+    pub fn save_value(&mut self, full_key: &str, expected: &[u8], value: &[u8]) -> Result<bool> {
+        self.writable()?;
+        let Some(tree) = self.current_tree.clone() else { bail!("No tree selected") };
+        let swapped = tree.compare_and_swap(full_key.as_bytes(), Some(expected), Some(value))?;
+        self.invalidate_value();
+        if swapped.is_err() {
+            return Ok(false);
+        }
+        if let Some(db) = &self.db {
+            db.flush()?;
+        }
+        if self.verify_writes {
+            verify_key(&tree, full_key.as_bytes(), Some(value))?;
+        }
+        Ok(true)
+    }
+
+
+    // A value of the selected tree as the first registered renderer that takes it shows it
+    pub fn render_value(&self, value: &[u8], pretty: bool, settings: &PrettyPrint) -> Rendered {
+        let context = RenderContext {
+            format_hint: self.format_hint,
+            protobuf_message: self.protobuf_message.as_deref(),
+            descriptors: self.protobuf.as_ref(),
+            pretty,
+            settings,
+        };
+        self.renderers.render(value, &context)
+    }
+
+    // A value as the value pane shows it in the given view
+    pub fn view_value(&self, value: &[u8], view: ValueView, pretty: bool, settings: &PrettyPrint) -> Rendered {
+        match raw_view(value, view) {
+            Some(text) => Rendered { label: None, text, records: vec![] },
+            None => self.render_value(value, pretty, settings),
+        }
+    }
+
+
+    // Join the values of all keys under a branch, for data chunked across many keys.
+    // None when the entry isn't a branch.
+    pub fn concat_branch(&self, index: usize, limit: usize) -> Result<Option<Concatenated>> {
+        let (Some(tree), Some(delimiter)) = (&self.current_tree, &self.delimiter) else { return Ok(None) };
+        let is_branch = self.current_key_range.keys.get(index).is_some_and(|entry| entry.has_children);
+        let Some(full_key) = self.full_key(index).filter(|_| is_branch) else { return Ok(None) };
+        let mut concatenated = Concatenated { bytes: vec![], keys: 0, truncated: false };
+        for result in tree.scan_prefix(format!("{}{}", full_key, delimiter)) {
+            let (_, value) = result?;
+            if concatenated.bytes.len() + value.len() > limit {
+                concatenated.truncated = true;
+                break;
+            }
+            concatenated.bytes.extend_from_slice(&value);
+            concatenated.keys += 1;
+        }
+        Ok(Some(concatenated))
+    }
+
+
+    // get the value associated with a particular current key, read from the tree only when
+    // the key differs from the last one asked for
     pub fn get_value(&mut self, index: usize) -> Result<Option<Vec<u8>>, Error> {
-        if let Some(tree) = &self.current_tree {
-            if self.current_key_range.keys.len() > index {
-                let key = self.current_key_range.keys[index].to_owned();
-                let mut new_path = self.current_path.clone();
-                new_path.push(key.key);
-                let full_key = new_path.join("/");
-                let value = tree.get(full_key.as_bytes())?;
-                if let Some(value) = value {
-                    return Ok(Some(value.to_vec()));
+        let (Some(tree), Some(full_key)) = (&self.current_tree, self.full_key(index)) else {
+            return Ok(None);
+        };
+        if let Some((cached_key, value)) = &self.value_cache {
+            if *cached_key == full_key {
+                return Ok(value.clone());
+            }
+        }
+        let value = tree.get(full_key.as_bytes())?.map(|value| value.to_vec());
+        self.value_cache = Some((full_key, value.clone()));
+        Ok(value)
+    }
+
+
+    // Forget the cached value, so the next get_value reads the tree again
+    pub fn invalidate_value(&mut self) {
+        self.value_cache = None;
+        self.read_row_notes();
+    }
+
+    // The modified time and owner badge of each row in current_key_range, when either is
+    // configured
+    fn read_row_notes(&mut self) {
+        self.row_notes.clear();
+        if self.modified.field.is_none() && self.owner.field.is_none() {
+            return;
+        }
+        self.row_notes = (0..self.current_key_range.keys.len())
+            .map(|index| {
+                // parsed once for both fields
+                let parsed = self.full_key(index)
+                    .and_then(|key| self.value_of(&key).ok().flatten())
+                    .and_then(|value| format::to_json(&value));
+                match parsed {
+                    Some((_, parsed)) => RowNote {
+                        modified_at: modified::modified_at(&parsed, &self.modified),
+                        owner: owner::badge(&parsed, &self.owner),
+                    },
+                    None => RowNote::default(),
                 }
+            })
+            .collect();
+    }
+
+
+    // Write the cached key hierarchy (names only, no values) and return the number of nodes written
+    pub fn write_key_tree<W: Write>(&self, out: &mut W, format: KeyTreeFormat) -> Result<usize> {
+        if self.use_disk_index {
+            bail!("Exporting the key hierarchy isn't available with --disk-index");
+        }
+        let (Some(tree), Some(delimiter)) = (&self.current_tree, &self.delimiter) else {
+            bail!("No key hierarchy to export - a delimiter must be set");
+        };
+        match &self.cached_key_tree {
+            Some(key_tree) => write_key_nodes(out, &key_tree.keys, format),
+            None => write_key_tree(tree, delimiter, self.max_depth, out, format),
+        }
+    }
+
+
+    // Remove an entry's key from the tree and flush, returning false if the key held no value
+    pub fn delete_key(&mut self, index: usize) -> Result<bool> {
+        self.writable()?;
+        let (Some(tree), Some(full_key)) = (&self.current_tree, self.full_key(index)) else {
+            return Ok(false);
+        };
+        let removed = tree.remove(full_key.as_bytes())?.is_some();
+        self.value_cache = None;
+        if let Some(db) = &self.db {
+            db.flush()?;
+        }
+        if self.verify_writes {
+            verify_key(tree, full_key.as_bytes(), None)?;
+        }
+        if let Some(filter) = &mut self.key_filter {
+            filter.keys.retain(|key| *key != full_key);
+        }
+        if self.delimiter.is_some() {
+            self.build_key_tree()?;
+        }
+        self.total_keys = self.total_keys();
+        Ok(removed)
+    }
+
+
+    // Remove every key of the selected tree, keeping the tree itself. Returns how many went.
+    pub fn clear_tree(&mut self) -> Result<usize> {
+        self.writable()?;
+        let Some(tree) = &self.current_tree else { return Ok(0) };
+        let count = tree.len();
+        tree.clear()?;
+        self.value_cache = None;
+        if let Some(db) = &self.db {
+            db.flush()?;
+        }
+        if self.verify_writes && !tree.is_empty() {
+            bail!("Verify failed: the tree still holds {} keys after clearing", tree.len());
+        }
+        self.current_path.clear();
+        self.key_filter = None;
+        if self.delimiter.is_some() {
+            self.build_key_tree()?;
+        }
+        self.total_keys = self.total_keys();
+        Ok(count)
+    }
+
+
+    // Create an empty tree (sled creates a tree the first time it is opened) and list it
+    pub fn create_tree(&mut self, name: &str) -> Result<()> {
+        self.writable()?;
+        let Some(db) = &self.db else { bail!("No database open") };
+        if db.tree_names().iter().any(|existing| existing.as_ref() == name.as_bytes()) {
+            bail!("A tree named {} already exists", name);
+        }
+        db.open_tree(name)?;
+        db.flush()?;
+        if self.verify_writes && !db.tree_names().iter().any(|existing| existing.as_ref() == name.as_bytes()) {
+            bail!("Verify failed: tree {} isn't there after creating it", name);
+        }
+        self.refresh_trees()
+    }
+
+
+    // Drop a tree and every key in it. sled's default tree can't be dropped.
+    pub fn drop_tree(&mut self, raw_name: &[u8]) -> Result<()> {
+        self.writable()?;
+        let Some(db) = &self.db else { bail!("No database open") };
+        let name = String::from_utf8_lossy(raw_name);
+        if raw_name == commands::DEFAULT_TREE {
+            bail!("sled's default tree can't be deleted");
+        }
+        if !db.drop_tree(raw_name)? {
+            bail!("Tree {} no longer exists", name);
+        }
+        db.flush()?;
+        if self.verify_writes && db.tree_names().iter().any(|existing| existing.as_ref() == raw_name) {
+            bail!("Verify failed: tree {} is still there after deleting it", name);
+        }
+        if self.current_tree.as_ref().is_some_and(|tree| tree.name() == raw_name) {
+            self.current_tree = None;
+            self.invalidate_value();
+        }
+        self.refresh_trees()
+    }
+
+
+    // Clear any filter and navigate to where a full key lives: its parent branch in
+    // delimiter mode, the whole tree otherwise. Returns the key's position in that list,
+    // None when the key is gone.
+    pub fn reveal_key(&mut self, full_key: &str) -> Result<Option<usize>> {
+        let Some(tree) = &self.current_tree else { return Ok(None) };
+        let position = match &self.delimiter {
+            None => {
+                if !tree.contains_key(full_key.as_bytes())? {
+                    return Ok(None);
+                }
+                tree.range(..full_key.as_bytes()).count()
             }
+            Some(_) if self.flat_paths => {
+                let Some(position) = self.flat_rows.iter().position(|row| row.full_key == full_key) else {
+                    return Ok(None);
+                };
+                position
+            }
+            Some(delimiter) => {
+                let mut path: Vec<String> = match self.max_depth {
+                    Some(depth) => full_key.splitn(depth.max(1), delimiter.as_str()).map(String::from).collect(),
+                    None => full_key.split(delimiter.as_str()).map(String::from).collect(),
+                };
+                let leaf = path.pop().unwrap_or_default();
+                let position = match &self.disk_index {
+                    Some(index) => index.position(&path, &leaf)?,
+                    None if self.use_disk_index => None,
+                    None => self.read_level(&path, usize::MAX)?.iter().position(|entry| entry.key == leaf),
+                };
+                let Some(position) = position else { return Ok(None) };
+                self.current_path = path;
+                position
+            }
+        };
+        self.key_filter = None;
+        self.total_keys = self.total_keys();
+        // counted from the other end when listing largest first, except in the flat path
+        // view, whose rows are in the order they are shown
+        if self.descending && !(self.flat_paths && self.delimiter.is_some()) {
+            return Ok(Some(self.total_keys.saturating_sub(1 + position)));
         }
-        Ok(None)
+        Ok(Some(position))
     }
 
 
     // Remove elements from the current path to navigate back up the key hierachy
     pub fn go_back_in_path(&mut self) -> Result<()> {
-        if !self.current_path.is_empty() && self.current_path.len() > 1 {
+        if !self.current_path.is_empty() {
             self.current_path.pop();
             self.total_keys = self.total_keys();
         } 
         Ok(())
     }
 
+
+    // Jump straight back to the top level of the key hierarchy
+    pub fn go_to_root(&mut self) {
+        self.current_path.clear();
+        self.total_keys = self.total_keys();
+    }
+
+}
+
+
+// Match a whole name against a pattern where * is any run of characters and ? any one
+fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+
+// Index a tree's key hierarchy and write it (names only, no values), for o/O and --outline.
+// Returns the number of nodes written.
+pub fn write_key_tree<W: Write>(tree: &sled::Tree, delimiter: &str, max_depth: Option<usize>, out: &mut W, format: KeyTreeFormat) -> Result<usize> {
+    write_key_nodes(out, &index_keys(tree, delimiter, max_depth, None)?.key_tree.keys, format)
+}
+
+
+fn write_key_nodes<W: Write>(out: &mut W, nodes: &BTreeMap<String, KeyNode>, format: KeyTreeFormat) -> Result<usize> {
+    let mut count = 0;
+    match format {
+        KeyTreeFormat::Text => write_outline(out, nodes, 0, &mut count)?,
+        KeyTreeFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, &outline_json(nodes, &mut count))?;
+            writeln!(out)?;
+        }
+    }
+    Ok(count)
+}
+
+
+fn write_outline<W: Write>(out: &mut W, nodes: &BTreeMap<String, KeyNode>, depth: usize, count: &mut usize) -> Result<()> {
+    for (name, node) in nodes {
+        writeln!(out, "{}{}", "  ".repeat(depth), name)?;
+        *count += 1;
+        write_outline(out, &node.children, depth + 1, count)?;
+    }
+    Ok(())
+}
+
+
+fn outline_json(nodes: &BTreeMap<String, KeyNode>, count: &mut usize) -> serde_json::Value {
+    let mut map = serde_json::Map::new();
+    for (name, node) in nodes {
+        *count += 1;
+        let children = if node.children.is_empty() {
+            serde_json::Value::Null
+        } else {
+            outline_json(&node.children, count)
+        };
+        map.insert(name.clone(), children);
+    }
+    serde_json::Value::Object(map)
+}
+
+
+// A value's bytes in one of the raw views, or None for the text view, which is rendered
+pub fn raw_view(bytes: &[u8], view: ValueView) -> Option<String> {
+    match view {
+        ValueView::Text => None,
+        ValueView::Escaped => Some(format::escaped_utf8(bytes)),
+        ValueView::Base64 => Some(base64::engine::general_purpose::STANDARD.encode(bytes)),
+        ValueView::Hex => Some(format::hex_dump(bytes)),
+    }
+}
+
+
+// Classify every value in a tree, grouping the keys by content type. Runs on a worker thread.
+pub fn scan_value_types(tree: &sled::Tree, progress: &Progress) -> Result<BTreeMap<ValueKind, Vec<String>>> {
+    let mut kinds: BTreeMap<ValueKind, Vec<String>> = BTreeMap::new();
+    for result in tree.iter() {
+        progress.check()?;
+        let (key, value) = result?;
+        kinds.entry(format::classify(&value))
+            .or_default()
+            .push(String::from_utf8_lossy(&key).to_string());
+        progress.inc();
+    }
+    Ok(kinds)
+}
+
+
+// Full keys containing `needle`, ignoring case
+pub fn search_keys(tree: &sled::Tree, needle: &str, progress: &Progress) -> Result<Vec<String>> {
+    let needle = needle.to_lowercase();
+    let mut matches = vec![];
+    for result in tree.iter().keys() {
+        progress.check()?;
+        let key = String::from_utf8_lossy(&result?).to_string();
+        if key.to_lowercase().contains(&needle) {
+            matches.push(key);
+        }
+        progress.inc();
+    }
+    Ok(matches)
+}
+
+
+fn hash_value(value: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+
+// Keys that hold the same value, and a glimpse of it
+pub struct SharedValue {
+    pub size: usize,
+    pub preview: String,
+    pub keys: Vec<String>,
+}
+
+// Read a key back after it was written (None: deleted) and fail if it doesn't hold what
+// was written, for --verify-writes. Run after the flush, so the write has reached disk.
+pub fn verify_key(tree: &sled::Tree, key: &[u8], expected: Option<&[u8]>) -> Result<()> {
+    let found = tree.get(key)?;
+    if found.as_deref() == expected {
+        return Ok(());
+    }
+    let key = String::from_utf8_lossy(key);
+    match (found, expected) {
+        (Some(_), None) => bail!("Verify failed: {} still exists after deleting it", key),
+        (None, _) => bail!("Verify failed: {} is missing after writing it", key),
+        (Some(found), Some(expected)) => {
+            bail!("Verify failed: {} reads back as {} bytes that differ from the {} written", key, found.len(), expected.len())
+        }
+    }
+}
+
+
+// Write keys and their values as --dump lines: the given keys (a search or filter's), or
+// else every key under the prefix. Keys deleted since the list was made are skipped.
+// Runs on a worker thread.
+pub fn write_dump(tree: &sled::Tree, keys: Option<&[String]>, prefix: &str, writer: &mut impl Write, progress: &Progress) -> Result<usize> {
+    let name = tree.name();
+    let mut written = 0;
+    let mut write = |key: &[u8], value: &[u8]| -> Result<()> {
+        progress.check()?;
+        writeln!(writer, "{}", commands::dump_record(&name, key, value))?;
+        progress.inc();
+        written += 1;
+        Ok(())
+    };
+    match keys {
+        Some(keys) => {
+            for key in keys {
+                if let Some(value) = tree.get(key.as_bytes())? {
+                    write(key.as_bytes(), &value)?;
+                }
+            }
+        }
+        None => {
+            for result in tree.scan_prefix(prefix) {
+                let (key, value) = result?;
+                write(&key, &value)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+
+// Group keys by identical value. Only each value's hash and length are kept during the scan,
+// not the values; the previews are read back at the end. Groups of two or more, largest
+// first. Runs on a worker thread.
+pub fn find_shared_values(tree: &sled::Tree, progress: &Progress) -> Result<Vec<SharedValue>> {
+    let mut groups: HashMap<(u64, usize), Vec<String>> = HashMap::new();
+    for result in tree.iter() {
+        progress.check()?;
+        let (key, value) = result?;
+        groups.entry((hash_value(&value), value.len()))
+            .or_default()
+            .push(String::from_utf8_lossy(&key).to_string());
+        progress.inc();
+    }
+    let mut shared = Vec::new();
+    for ((_, size), keys) in groups.into_iter().filter(|(_, keys)| keys.len() > 1) {
+        let value = tree.get(keys[0].as_bytes())?.unwrap_or_default();
+        let preview = String::from_utf8_lossy(&value).chars().take(SHARED_PREVIEW_CHARS).map(|c| if c.is_control() { ' ' } else { c }).collect();
+        shared.push(SharedValue { size, preview, keys });
+    }
+    shared.sort_by(|a, b| b.keys.len().cmp(&a.keys.len()).then(b.size.cmp(&a.size)));
+    Ok(shared)
+}
+
+const SHARED_PREVIEW_CHARS: usize = 40;
+
+
+// Record a hash of every value in a tree. Runs on a worker thread.
+pub fn snapshot_tree(tree: &sled::Tree, progress: &Progress) -> Result<Baseline> {
+    let mut baseline = Baseline::new();
+    for result in tree.iter() {
+        progress.check()?;
+        let (key, value) = result?;
+        baseline.insert(key.to_vec(), hash_value(&value));
+        progress.inc();
+    }
+    Ok(baseline)
+}
+
+
+// A SHA-256 over a tree's keys and values in key order, each prefixed with its length so
+// moving bytes between a key and its value changes it. Equal fingerprints mean identical
+// contents, wherever the trees are.
+pub struct Fingerprint {
+    pub keys: usize,
+    pub bytes: usize,
+    pub hash: String,
+}
+
+pub fn fingerprint_tree(tree: &sled::Tree, progress: &Progress) -> Result<Fingerprint> {
+    let mut hasher = Sha256::new();
+    let (keys, bytes) = hash_tree(tree, &mut hasher, progress)?;
+    Ok(Fingerprint { keys, bytes, hash: hex_digest(hasher) })
+}
+
+// Every tree in name order, each name hashed ahead of its contents. Runs on a worker thread.
+pub fn fingerprint_database(db: &Db, progress: &Progress) -> Result<Fingerprint> {
+    let mut hasher = Sha256::new();
+    let (mut keys, mut bytes) = (0, 0);
+    let mut names = db.tree_names();
+    names.sort();
+    for name in names {
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(&name);
+        let (tree_keys, tree_bytes) = hash_tree(&db.open_tree(&name)?, &mut hasher, progress)?;
+        keys += tree_keys;
+        bytes += tree_bytes;
+    }
+    Ok(Fingerprint { keys, bytes, hash: hex_digest(hasher) })
+}
+
+fn hash_tree(tree: &sled::Tree, hasher: &mut Sha256, progress: &Progress) -> Result<(usize, usize)> {
+    let (mut keys, mut bytes) = (0, 0);
+    for result in tree.iter() {
+        progress.check()?;
+        let (key, value) = result?;
+        for part in [&key, &value] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        keys += 1;
+        bytes += key.len() + value.len();
+        progress.inc();
+    }
+    hasher.update((keys as u64).to_le_bytes());
+    Ok((keys, bytes))
+}
+
+fn hex_digest(hasher: Sha256) -> String {
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+
+// Compare a tree with an earlier snapshot. Both are sorted by key bytes, so a single
+// merge walk finds added, removed and changed keys. Runs on a worker thread.
+pub fn diff_baseline(tree: &sled::Tree, baseline: &Baseline, progress: &Progress) -> Result<BTreeMap<String, Change>> {
+    let mut changes = BTreeMap::new();
+    let mut old = baseline.iter().peekable();
+    for result in tree.iter() {
+        progress.check()?;
+        let (key, value) = result?;
+        while let Some((old_key, _)) = old.next_if(|(old_key, _)| old_key.as_slice() < key.as_ref()) {
+            changes.insert(String::from_utf8_lossy(old_key).to_string(), Change::Removed);
+        }
+        match old.next_if(|(old_key, _)| old_key.as_slice() == key.as_ref()) {
+            Some((_, old_hash)) if *old_hash == hash_value(&value) => {}
+            Some(_) => { changes.insert(String::from_utf8_lossy(&key).to_string(), Change::Changed); }
+            None => { changes.insert(String::from_utf8_lossy(&key).to_string(), Change::Added); }
+        }
+        progress.inc();
+    }
+    for (old_key, _) in old {
+        changes.insert(String::from_utf8_lossy(old_key).to_string(), Change::Removed);
+    }
+    Ok(changes)
+}
+
+
+// A tree's keys split into the delimiter hierarchy, built apart from the App so a rebuild
+// can run on a worker thread
+pub struct KeyIndex {
+    key_tree: KeyTree,
+    unsplittable_keys: Option<(usize, String)>,
+}
+
+// Progress is only passed for the R rebuild, which runs in the background
+pub fn index_keys(tree: &sled::Tree, delimiter: &str, max_depth: Option<usize>, progress: Option<&Progress>) -> Result<KeyIndex> {
+    let mut key_tree = KeyTree {
+        keys: BTreeMap::new(),
+    };
+    let mut unsplittable_keys: Option<(usize, String)> = None;
+
+    for result in tree.iter() {
+        if let Some(progress) = progress {
+            progress.check()?;
+            progress.inc();
+        }
+        let (key, _) = result?;
+        let key_str = String::from_utf8_lossy(&key).to_string();
+        let parts = split_key(&key_str, delimiter, max_depth);
+        if !splits_cleanly(&key, &parts, delimiter) {
+            let (count, _) = unsplittable_keys.get_or_insert_with(|| (0, key_str.clone()));
+            *count += 1;
+        }
+
+        let mut current = &mut key_tree.keys;
+        for part in parts.iter() {
+            let entry = current.entry(part.to_string()).or_insert_with(|| KeyNode {
+                children: BTreeMap::new(),
+            });
+            current = &mut entry.children;
+        }
+    }
+    Ok(KeyIndex { key_tree, unsplittable_keys })
+}
+
+
+// `count` items from `offset`, counting from the last item when descending
+fn window<I: DoubleEndedIterator>(items: I, offset: usize, count: usize, descending: bool) -> Vec<I::Item> {
+    match descending {
+        true => items.rev().skip(offset).take(count).collect(),
+        false => items.skip(offset).take(count).collect(),
+    }
+}
+
+
+fn split_key<'a>(key: &'a str, delimiter: &str, max_depth: Option<usize>) -> Vec<&'a str> {
+    match max_depth {
+        Some(depth) => key.splitn(depth.max(1), delimiter).collect(),
+        None => key.split(delimiter).collect(),
+    }
+}
+
+// The full key is rebuilt by joining segments, so a key that doesn't survive that won't
+// open as itself, and one with empty segments shows spurious empty levels
+fn splits_cleanly(key: &[u8], parts: &[&str], delimiter: &str) -> bool {
+    parts.join(delimiter).as_bytes() == key && parts.iter().all(|part| !part.is_empty())
+}
+
+// The keys that don't split cleanly among the first DELIMITER_SAMPLE, for the path bar's
+// warning when the whole tree isn't read
+fn unsplittable_sample(tree: &sled::Tree, delimiter: &str, max_depth: Option<usize>) -> Result<Option<(usize, String)>> {
+    let mut unsplittable_keys: Option<(usize, String)> = None;
+    for result in tree.iter().keys().take(DELIMITER_SAMPLE) {
+        let key = result?;
+        let key_str = String::from_utf8_lossy(&key).to_string();
+        if !splits_cleanly(&key, &split_key(&key_str, delimiter, max_depth), delimiter) {
+            let (count, _) = unsplittable_keys.get_or_insert_with(|| (0, key_str.clone()));
+            *count += 1;
+        }
+    }
+    Ok(unsplittable_keys)
+}
+
+
+// One level of the hierarchy read straight from the tree: the keys under `prefix`, cut at
+// the next delimiter unless `split` is off (at max_depth). Each branch is stepped over with
+// a single seek past everything under it, so a level costs about as much as its own
+// entries however many keys lie below them. Stops after `limit` entries.
+fn scan_level(tree: &sled::Tree, prefix: &[u8], delimiter: &str, split: bool, limit: usize) -> Result<Vec<KeyEntry>> {
+    let delimiter = delimiter.as_bytes();
+    let mut entries: BTreeMap<String, bool> = BTreeMap::new(); // name -> has children
+    let mut keys = tree.range(prefix..);
+    while entries.len() < limit {
+        let Some(key) = keys.next() else { break };
+        let (key, _) = key?;
+        let Some(rest) = key.strip_prefix(prefix) else { break };
+        let branch = match split {
+            true => rest.windows(delimiter.len()).position(|window| window == delimiter),
+            false => None,
+        };
+        match branch {
+            Some(end) => {
+                entries.insert(String::from_utf8_lossy(&rest[..end]).to_string(), true);
+                // skip every key under the branch
+                let mut under = key[..prefix.len() + end].to_vec();
+                under.extend_from_slice(delimiter);
+                match successor(&under) {
+                    Some(next) => keys = tree.range(next..),
+                    None => break,
+                }
+            }
+            None => {
+                entries.entry(String::from_utf8_lossy(rest).to_string()).or_insert(false);
+            }
+        }
+    }
+    Ok(entries.into_iter().map(|(key, has_children)| KeyEntry { key, has_children, child_count: 0 }).collect())
+}
+
+// The first byte string after every string starting with `prefix`, None when there is none
+fn successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut next = prefix.to_vec();
+    while let Some(last) = next.pop() {
+        if last < u8::MAX {
+            next.push(last + 1);
+            return Some(next);
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TempDb;
+
+    // An App on the test database with `tree` selected, split on `delimiter`
+    fn open_app(db: &TempDb, tree: &str, delimiter: Option<&str>) -> App {
+        let mut app = App::new();
+        db.wait_for_lock();
+        app.db = Some(sled::open(&db.0).expect("test database opens"));
+        app.configure_delimiter(false, Some(delimiter.unwrap_or_default()));
+        app.refresh_trees().expect("trees are listed");
+        let index = app.sled_trees.iter().position(|(_, name)| name == tree).expect("test tree exists");
+        app.select_tree(index).expect("test tree opens");
+        app
+    }
+
+    fn listed(app: &App) -> Vec<&str> {
+        app.current_key_range.keys.iter().map(|entry| entry.key.as_str()).collect()
+    }
+
+    #[test]
+    fn full_keys_join_segments_with_the_delimiter() {
+        let db = TempDb::with_keys("app-full-key", "data", &[("a::b::c", "deep"), ("a::x", "shallow"), ("top", "level")]);
+        let mut app = open_app(&db, "data", Some("::"));
+        app.set_key_range(0, 10).unwrap();
+        assert_eq!(listed(&app), ["a", "top"]);
+        app.select_key(0).unwrap();
+        app.set_key_range(0, 10).unwrap();
+        assert_eq!(listed(&app), ["b", "x"]);
+        assert_eq!(app.full_key(1).as_deref(), Some("a::x"));
+        assert_eq!(app.get_value(1).unwrap().as_deref(), Some(&b"shallow"[..]));
+        app.select_key(0).unwrap();
+        app.set_key_range(0, 10).unwrap();
+        assert_eq!(app.full_key(0).as_deref(), Some("a::b::c"));
+        assert_eq!(app.get_value(0).unwrap().as_deref(), Some(&b"deep"[..]));
+    }
+
+    fn entries(app: &App) -> Vec<(String, bool, usize)> {
+        app.current_key_range.keys.iter().map(|entry| (entry.key.clone(), entry.has_children, entry.child_count)).collect()
+    }
+
+    #[test]
+    fn levels_read_from_the_tree_match_a_full_scan() {
+        let mut keys: Vec<(String, String)> = (0..100_000)
+            .map(|i| (format!("r{}/s{:02}/t{:05}", i % 10, i / 10 % 100, i), i.to_string()))
+            .collect();
+        // a branch with more children than are counted
+        keys.extend((0..1500).map(|i| (format!("big/{:04}", i), String::new())));
+        // leaves that are branches too, and keys ending in the delimiter (an empty last segment)
+        for key in ["r3", "r0/s00", "r1/s05/", "end/", "flat"] {
+            keys.push((key.to_string(), String::new()));
+        }
+        let pairs: Vec<(&str, &str)> = keys.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect();
+        let db = TempDb::with_keys("app-levels", "data", &pairs);
+        let mut app = open_app(&db, "data", Some("/"));
+        let scan = index_keys(app.current_tree.as_ref().unwrap(), "/", None, None).unwrap();
+
+        let mut levels = 0;
+        let mut pending = vec![(vec![], &scan.key_tree.keys)];
+        while let Some((path, nodes)) = pending.pop() {
+            app.current_path = path.clone();
+            app.refresh_total_keys();
+            assert_eq!(app.total_keys, nodes.len(), "at {:?}", path);
+            let expected: Vec<(String, bool, usize)> = nodes.iter()
+                .map(|(name, node)| (name.clone(), !node.children.is_empty(), node.children.len().min(MAX_CHILD_COUNT + 1)))
+                .collect();
+            app.set_key_range(0, usize::MAX).unwrap();
+            assert_eq!(entries(&app), expected, "at {:?}", path);
+            app.set_key_range(3, 5).unwrap();
+            assert_eq!(entries(&app), expected.iter().skip(3).take(5).cloned().collect::<Vec<_>>(), "at {:?}", path);
+            for (name, node) in nodes.iter().filter(|(_, node)| !node.children.is_empty()) {
+                let mut child = path.clone();
+                child.push(name.clone());
+                pending.push((child, &node.children));
+            }
+            levels += 1;
+        }
+        assert_eq!(levels, 1 + 10 + 1000 + 2); // the root, r*, r*/s*, big and end
+    }
+
+    #[test]
+    fn window_counts_from_either_end() {
+        let window = |offset, count, descending| window(0..10usize, offset, count, descending);
+        assert_eq!(window(0, 3, true), [9, 8, 7]);
+        assert_eq!(window(4, 3, true), [5, 4, 3]);
+        assert_eq!(window(8, 3, true), [1, 0]); // the last page, partly filled
+        assert_eq!(window(10, 3, true), Vec::<usize>::new());
+        assert_eq!(window(15, 3, true), Vec::<usize>::new());
+        assert_eq!(window(4, 3, false), [4, 5, 6]);
+        assert_eq!(window(8, 3, false), [8, 9]);
+        assert_eq!(window(15, 3, false), Vec::<usize>::new());
+    }
+
+    fn numbered_keys(name: &str) -> TempDb {
+        let keys: Vec<String> = (0..10).map(|i| format!("k/{:02}", i)).chain(["a".to_string(), "z/1".to_string()]).collect();
+        let pairs: Vec<(&str, &str)> = keys.iter().map(|key| (key.as_str(), "")).collect();
+        TempDb::with_keys(name, "data", &pairs)
+    }
+
+    // The App splitting on / with its hierarchy in a disk index, built here rather than on
+    // a worker thread
+    fn with_disk_index(mut app: App) -> App {
+        app.use_disk_index = true;
+        app.set_delimiter(Some("/".to_string())).unwrap();
+        let tree = app.current_tree.as_ref().unwrap();
+        app.disk_index = Some(DiskIndex::build(tree, "/", None, &Progress::unwatched()).unwrap());
+        app.refresh_total_keys();
+        app
+    }
+
+    #[test]
+    fn disk_index_windows_count_from_the_end_when_descending() {
+        let db = numbered_keys("app-disk-descending");
+        let mut app = with_disk_index(open_app(&db, "data", Some("/")));
+        app.set_descending(true);
+        app.set_key_range(0, 10).unwrap();
+        assert_eq!(listed(&app), ["z", "k", "a"]);
+        app.select_key(1).unwrap();
+        assert_eq!(app.total_keys, 10);
+        app.set_key_range(0, 3).unwrap();
+        assert_eq!(listed(&app), ["09", "08", "07"]);
+        app.set_key_range(4, 3).unwrap();
+        assert_eq!(listed(&app), ["05", "04", "03"]);
+        app.set_key_range(8, 3).unwrap();
+        assert_eq!(listed(&app), ["01", "00"]);
+        app.set_key_range(10, 3).unwrap();
+        assert_eq!(listed(&app), Vec::<&str>::new());
+        app.set_key_range(15, 3).unwrap();
+        assert_eq!(listed(&app), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn reveal_key_positions_match_the_listing_order() {
+        let db = numbered_keys("app-reveal");
+        // the position reveal_key gives, and the full key listed there
+        let reveal = |app: &mut App, key: &str| {
+            let position = app.reveal_key(key).unwrap().expect("key is found");
+            app.set_key_range(position, 1).unwrap();
+            (position, app.full_key(0).unwrap())
+        };
+        for descending in [false, true] {
+            let mirrored = |position: usize, total: usize| if descending { total - 1 - position } else { position };
+
+            // one App at a time, as each holds the database's lock
+            {
+                let mut app = open_app(&db, "data", None);
+                app.set_descending(descending);
+                assert_eq!(reveal(&mut app, "k/07"), (mirrored(8, 12), "k/07".to_string()));
+            }
+            {
+                let mut app = open_app(&db, "data", Some("/"));
+                app.set_descending(descending);
+                assert_eq!(reveal(&mut app, "k/07"), (mirrored(7, 10), "k/07".to_string()));
+                assert_eq!(app.current_path, ["k"]);
+                assert_eq!(reveal(&mut app, "a"), (mirrored(0, 3), "a".to_string()));
+            }
+            {
+                let mut app = with_disk_index(open_app(&db, "data", Some("/")));
+                app.set_descending(descending);
+                assert_eq!(reveal(&mut app, "k/07"), (mirrored(7, 10), "k/07".to_string()));
+            }
+            {
+                // the flat path view's rows are already in the order they are shown
+                let mut app = open_app(&db, "data", Some("/"));
+                app.set_descending(descending);
+                app.set_flat_paths(true).unwrap();
+                let row = if descending { 5 } else { 9 }; // z, 1, k, 09, 08, 07 or a, k, 00 .. 07
+                assert_eq!(reveal(&mut app, "k/07"), (row, "k/07".to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn row_notes_are_read_with_the_window_and_after_a_save() {
+        let db = TempDb::with_keys("app-row-notes", "data", &[
+            ("a", r#"{"updated": 1700000000, "owner": "ops"}"#),
+            ("b", "plain text"),
+        ]);
+        let mut app = open_app(&db, "data", None);
+        app.modified.field = Some("updated".to_string());
+        app.owner.field = Some("owner".to_string());
+        app.set_key_range(0, 10).unwrap();
+        let notes = |app: &App| app.row_notes.iter().map(|note| (note.modified_at, note.owner.clone())).collect::<Vec<_>>();
+        assert_eq!(notes(&app), [(Some(1_700_000_000), Some("ops".to_string())), (None, None)]);
+        let old = app.value_of("a").unwrap().unwrap();
+        assert!(app.save_value("a", &old, br#"{"updated": 1700000100, "owner": "dev"}"#).unwrap());
+        assert_eq!(notes(&app)[0], (Some(1_700_000_100), Some("dev".to_string())));
+    }
+
+    #[test]
+    fn value_views_show_text_escapes_base64_and_hex() {
+        let app = App::new();
+        let view = |value: &[u8], view| app.view_value(value, view, false, &PrettyPrint::default()).text;
+        assert_eq!(view(b"plain text", ValueView::Text), "plain text");
+        assert_eq!(view(b"a\\b", ValueView::Escaped), "a\\\\b");
+        assert_eq!(view(b"ok", ValueView::Base64), "b2s=");
+        assert_eq!(view(b"ok", ValueView::Hex), format!("00000000  {:<47}  |ok|", "6f 6b"));
+
+        // not UTF-8: escaped keeps every byte, the other raw views don't care
+        let bytes = b"caf\xe9 \\x41\xff";
+        assert_eq!(raw_view(bytes, ValueView::Text), None);
+        assert_eq!(view(bytes, ValueView::Escaped), "caf\\xe9 \\\\x41\\xff");
+        assert_eq!(view(bytes, ValueView::Base64), "Y2Fm6SBceDQx/w==");
+        assert_eq!(view(bytes, ValueView::Hex), format!("00000000  {:<47}  |caf. \\x41.|", "63 61 66 e9 20 5c 78 34 31 ff"));
+    }
 }
@@ -0,0 +1,57 @@
+// file src/clipboard.rs
+use anyhow::{Context, Result};
+use base64::Engine;
+
+// The system clipboard, opened on first use. Kept open for the life of the app because
+// on X11 the copied text is only served while the owning clipboard handle exists.
+// Headless and SSH sessions have no clipboard, which is reported as an error, not a crash.
+#[derive(Default)]
+pub struct Clipboard {
+    inner: Option<arboard::Clipboard>,
+    #[cfg(test)]
+    pub copied: Option<Vec<String>>, // a stub's copies, kept instead of reaching the system clipboard
+}
+
+impl Clipboard {
+    // A clipboard that only records what is copied, for tests
+    #[cfg(test)]
+    pub fn stub() -> Self {
+        Self { inner: None, copied: Some(vec![]) }
+    }
+
+    pub fn copy(&mut self, text: &str) -> Result<()> {
+        #[cfg(test)]
+        if let Some(copied) = &mut self.copied {
+            copied.push(text.to_owned());
+            return Ok(());
+        }
+        if self.inner.is_none() {
+            self.inner = Some(arboard::Clipboard::new().context("No clipboard available")?);
+        }
+        let clipboard = self.inner.as_mut().expect("This is a bug. The clipboard was opened just above.");
+        clipboard.set_text(text.to_owned()).context("Could not copy to clipboard")?;
+        Ok(())
+    }
+}
+
+
+// How a value was turned into a shell argument
+pub enum ShellQuoting {
+    SingleQuoted,
+    Base64,
+}
+
+// Quote a value so it can be pasted as a single POSIX shell argument. Single-line text is
+// single-quoted; multi-line or binary values become a base64 command substitution that
+// decodes back to the exact bytes (apart from trailing newlines, which $(...) strips).
+pub fn shell_quote(bytes: &[u8]) -> (String, ShellQuoting) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(char::is_control) => {
+            (format!("'{}'", text.replace('\'', r"'\''")), ShellQuoting::SingleQuoted)
+        }
+        _ => {
+            let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+            (format!("\"$(printf %s '{}' | base64 -d)\"", encoded), ShellQuoting::Base64)
+        }
+    }
+}
@@ -0,0 +1,335 @@
+// file src/commands.rs
+// Non-interactive commands that run instead of the TUI
+use crate::app;
+use crate::db;
+use crate::format;
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+pub const DEFAULT_TREE: &[u8] = b"__sled__default";
+
+// Characters of a value shown by --inspect
+const PREVIEW_CHARS: usize = 80;
+
+
+// Open a tree that must already exist - sled's open_tree would silently create it
+fn open_existing_tree(db: &sled::Db, name: &[u8]) -> Result<sled::Tree> {
+    if !db.tree_names().iter().any(|tree_name| tree_name.as_ref() == name) {
+        bail!("Tree {} does not exist", String::from_utf8_lossy(name));
+    }
+    Ok(db.open_tree(name)?)
+}
+
+
+// Write a value's raw bytes to stdout
+pub fn get_value(db_path: &Path, tree_name: Option<&[u8]>, key: &str) -> Result<()> {
+    let db = db::open(db_path)?;
+    let tree = open_existing_tree(&db, tree_name.unwrap_or(DEFAULT_TREE))?;
+    let Some(value) = tree.get(key.as_bytes())? else {
+        bail!("Key {} not found", key);
+    };
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&value)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+
+// Store stdin's bytes, unchanged, as the value of a key
+pub fn set_value(db_path: &Path, tree_name: Option<&[u8]>, key: &str, verify: bool) -> Result<()> {
+    let mut value = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut value).context("Could not read value from stdin")?;
+    let db = db::open(db_path)?;
+    let tree = db.open_tree(tree_name.unwrap_or(DEFAULT_TREE))?;
+    tree.insert(key.as_bytes(), value.as_slice())?;
+    db.flush()?;
+    if verify {
+        app::verify_key(&tree, key.as_bytes(), Some(&value))?;
+    }
+    Ok(())
+}
+
+
+// Summarise a value: size, detected format, UTF-8 validity and the start of its text
+pub fn inspect_value(db_path: &Path, tree_name: Option<&[u8]>, key: &str, json: bool) -> Result<()> {
+    let db = db::open(db_path)?;
+    let tree = open_existing_tree(&db, tree_name.unwrap_or(DEFAULT_TREE))?;
+    let Some(value) = tree.get(key.as_bytes())? else {
+        bail!("Key {} not found", key);
+    };
+    let kind = format::classify(&value);
+    let utf8 = std::str::from_utf8(&value).is_ok();
+    let preview: String = String::from_utf8_lossy(&value).chars().take(PREVIEW_CHARS).collect();
+    if json {
+        let summary = serde_json::json!({
+            "tree": String::from_utf8_lossy(tree_name.unwrap_or(DEFAULT_TREE)),
+            "key": key,
+            "bytes": value.len(),
+            "format": kind.name(),
+            "utf8": utf8,
+            "preview": preview,
+        });
+        println!("{}", summary);
+    } else {
+        println!("bytes:   {}", value.len());
+        println!("format:  {}", kind.name());
+        println!("utf8:    {}", if utf8 { "yes" } else { "no" });
+        // keep the summary one line per field
+        let preview: String = preview.chars()
+            .map(|c| if c.is_control() { c.escape_default().to_string() } else { c.to_string() })
+            .collect();
+        println!("preview: {}", preview);
+    }
+    Ok(())
+}
+
+
+// Write every key of a tree, optionally only those under a prefix and containing a search
+// string (case-insensitive, like the TUI's search), as JSON lines. Each line names its tree,
+// so --restore can rebuild a database from a dump of all of them. Names, keys and values
+// that aren't UTF-8 are written base64-encoded, under tree_base64 / key_base64 / value_base64.
+pub fn dump_tree(db_path: &Path, tree_name: Option<&[u8]>, all_trees: bool, prefix: Option<&str>, search: Option<&str>) -> Result<()> {
+    let db = db::open(db_path)?;
+    let trees = if all_trees {
+        let mut names: Vec<Vec<u8>> = db.tree_names().into_iter().map(|name| name.to_vec()).collect();
+        names.sort();
+        names
+    } else {
+        vec![tree_name.unwrap_or(DEFAULT_TREE).to_vec()]
+    };
+    let search = search.map(str::to_lowercase);
+    let mut stdout = std::io::stdout().lock();
+    for name in &trees {
+        let tree = open_existing_tree(&db, name)?;
+        for result in tree.scan_prefix(prefix.unwrap_or_default()) {
+            let (key, value) = result?;
+            if let Some(search) = &search {
+                if !String::from_utf8_lossy(&key).to_lowercase().contains(search) {
+                    continue;
+                }
+            }
+            writeln!(stdout, "{}", dump_record(name, &key, &value))?;
+        }
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+
+// Write a tree's key hierarchy (names only) to stdout, as o/O export it in the TUI. The
+// delimiter is detected from the keys when not given.
+pub fn outline_tree(db_path: &Path, tree_name: Option<&[u8]>, delimiter: Option<&str>, json: bool) -> Result<()> {
+    let db = db::open(db_path)?;
+    let tree = open_existing_tree(&db, tree_name.unwrap_or(DEFAULT_TREE))?;
+    let delimiter = match delimiter {
+        Some("") => bail!("An outline needs a delimiter to split the keys on"),
+        Some(delimiter) => delimiter.to_string(),
+        None => match app::detect_delimiter(&tree)? {
+            Some(delimiter) => delimiter,
+            None => bail!("No delimiter found in the keys of {} - give one with --delimiter", String::from_utf8_lossy(tree_name.unwrap_or(DEFAULT_TREE))),
+        },
+    };
+    let format = if json { app::KeyTreeFormat::Json } else { app::KeyTreeFormat::Text };
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    app::write_key_tree(&tree, &delimiter, None, &mut stdout, format)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+
+// One line of a --dump, also written by W in the TUI
+pub fn dump_record(tree: &[u8], key: &[u8], value: &[u8]) -> serde_json::Value {
+    let mut line = serde_json::Map::new();
+    for (field, bytes) in [("tree", tree), ("key", key), ("value", value)] {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => line.insert(field.to_string(), text.into()),
+            Err(_) => line.insert(format!("{}_base64", field), base64::engine::general_purpose::STANDARD.encode(bytes).into()),
+        };
+    }
+    serde_json::Value::Object(line)
+}
+
+
+// Create a database from a --dump: JSON lines, or one JSON array of the same records.
+// Records without a tree go to the default tree, as dumps made before trees were named
+// did. Refuses a path that already holds anything unless forced, since a restore on top
+// of other data would mix the two.
+pub fn restore(dump_path: &Path, db_path: &Path, force: bool, running: Arc<AtomicBool>) -> Result<()> {
+    if db::holds_files(db_path) && !force {
+        bail!("{} already exists and isn't empty - give --force to restore into it anyway", db_path.display());
+    }
+    let records = read_records(dump_path)?;
+
+    let db = db::open(db_path)?;
+    let progress = ProgressBar::new_spinner();
+    progress.set_style(ProgressStyle::default_spinner().template("{spinner:.green} [{elapsed_precise}] {msg}").unwrap());
+    let mut counts: BTreeMap<Vec<u8>, usize> = BTreeMap::new();
+    let mut current: Option<(Vec<u8>, sled::Tree)> = None;
+    for (index, (at, record)) in records.enumerate() {
+        if !running.load(Ordering::SeqCst) {
+            db.flush()?;
+            bail!("Restore cancelled after {} keys - {} holds a partial restore", index, db_path.display());
+        }
+        let DumpRecord { tree: tree_name, key, value } = parse_record(&record?, &at)?;
+        let tree_name = tree_name.unwrap_or_else(|| DEFAULT_TREE.to_vec());
+        if current.as_ref().is_none_or(|(name, _)| *name != tree_name) {
+            if let Some((name, _)) = &current {
+                progress.suspend(|| eprintln!("{}: {} keys", String::from_utf8_lossy(name), counts[name]));
+            }
+            current = Some((tree_name.clone(), db.open_tree(&tree_name)?));
+        }
+        if let Some((_, tree)) = &current {
+            tree.insert(key, value)?;
+        }
+        let count = counts.entry(tree_name.clone()).or_default();
+        *count += 1;
+        progress.set_message(format!("{}: {} keys", String::from_utf8_lossy(&tree_name), count));
+        progress.tick();
+    }
+    if let Some((name, _)) = &current {
+        progress.suspend(|| eprintln!("{}: {} keys", String::from_utf8_lossy(name), counts[name]));
+    }
+    db.flush()?;
+    progress.finish_and_clear();
+    eprintln!("Restored {} keys in {} trees to {}", counts.values().sum::<usize>(), counts.len(), db_path.display());
+    Ok(())
+}
+
+
+// Add the records of a --dump file to a database that may already hold data, overwriting
+// keys it already has. Every record goes to `tree_name` when given, or else to the tree it
+// names. A dry run only reads and checks the file and counts what would be written, without
+// opening the database.
+pub fn import(dump_path: &Path, db_path: &Path, tree_name: Option<&[u8]>, dry_run: bool, running: Arc<AtomicBool>) -> Result<()> {
+    let records = read_records(dump_path)?;
+    let db = if dry_run { None } else { Some(db::open(db_path)?) };
+    let progress = ProgressBar::new_spinner();
+    progress.set_style(ProgressStyle::default_spinner().template("{spinner:.green} [{elapsed_precise}] {msg}").unwrap());
+    let mut trees: BTreeMap<Vec<u8>, (Option<sled::Tree>, usize)> = BTreeMap::new();
+    for (index, (at, record)) in records.enumerate() {
+        if !running.load(Ordering::SeqCst) {
+            progress.finish_and_clear();
+            match &db {
+                Some(db) => {
+                    db.flush()?;
+                    bail!("Import cancelled after {} keys - those stay written to {}", index, db_path.display());
+                }
+                None => bail!("Dry run cancelled after {} records", index),
+            }
+        }
+        let DumpRecord { tree: record_tree, key, value } = match record.and_then(|record| parse_record(&record, &at)) {
+            Ok(record) => record,
+            Err(e) => {
+                progress.finish_and_clear();
+                // the records before a bad one are already written, so say how many
+                if let (Some(db), 1..) = (&db, index) {
+                    db.flush()?;
+                    return Err(e.context(format!("Import stopped after {} keys - those stay written to {} (--dry-run checks a whole file first)", index, db_path.display())));
+                }
+                return Err(e);
+            }
+        };
+        let name = match tree_name {
+            Some(name) => name.to_vec(),
+            None => record_tree.unwrap_or_else(|| DEFAULT_TREE.to_vec()),
+        };
+        if !trees.contains_key(&name) {
+            let tree = db.as_ref().map(|db| db.open_tree(&name)).transpose()?;
+            trees.insert(name.clone(), (tree, 0));
+        }
+        let (tree, count) = trees.get_mut(&name).expect("opened above");
+        if let Some(tree) = tree {
+            tree.insert(key, value)?;
+        }
+        *count += 1;
+        progress.set_message(format!("{}: {} keys", String::from_utf8_lossy(&name), count));
+        progress.tick();
+    }
+    if let Some(db) = &db {
+        db.flush()?;
+    }
+    progress.finish_and_clear();
+    for (name, (_, count)) in &trees {
+        eprintln!("{}: {} keys", String::from_utf8_lossy(name), count);
+    }
+    let total: usize = trees.values().map(|(_, count)| count).sum();
+    match dry_run {
+        true => eprintln!("Dry run: {} valid records for {} trees, nothing written", total, trees.len()),
+        false => eprintln!("Imported {} keys into {} trees of {}", total, trees.len(), db_path.display()),
+    }
+    Ok(())
+}
+
+
+// The records of a --dump file, JSON lines or one JSON array of the same records, each with
+// where it is in the file ("Line 3", "Record 3") for error messages. A JSON array is read
+// whole; JSON lines are streamed, so a dump may be larger than memory.
+fn read_records(dump_path: &Path) -> Result<Box<dyn Iterator<Item = (String, Result<serde_json::Value>)>>> {
+    let file = std::fs::File::open(dump_path).with_context(|| format!("Could not open {}", dump_path.display()))?;
+    let mut reader = BufReader::new(file);
+    let array = reader.fill_buf()?.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'[');
+    if array {
+        let records: Vec<serde_json::Value> = serde_json::from_reader(reader)
+            .with_context(|| format!("{} isn't a JSON array of records", dump_path.display()))?;
+        return Ok(Box::new(records.into_iter().enumerate().map(|(index, record)| (format!("Record {}", index + 1), Ok(record)))));
+    }
+    Ok(Box::new(reader.lines().enumerate()
+        .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+        .map(|(index, line)| {
+            let at = format!("Line {}", index + 1);
+            let record = line.context("Could not read the dump")
+                .and_then(|line| serde_json::from_str(&line).with_context(|| format!("{} isn't JSON", at)));
+            (at, record)
+        })))
+}
+
+
+// One key and value of a dump, and the tree it names if any
+struct DumpRecord {
+    tree: Option<Vec<u8>>,
+    key: Vec<u8>,
+    value: Vec<u8>,
+}
+
+fn parse_record(record: &serde_json::Value, at: &str) -> Result<DumpRecord> {
+    let field = |name: &str| record_bytes(record, name).with_context(|| format!("{} is invalid", at));
+    let tree = field("tree")?;
+    let (Some(key), Some(value)) = (field("key")?, field("value")?) else {
+        bail!("{} needs a key and a value", at);
+    };
+    Ok(DumpRecord { tree, key, value })
+}
+
+
+// A tree, key or value from a dump record: the plain field as UTF-8, or the _base64 one
+fn record_bytes(record: &serde_json::Value, field: &str) -> Result<Option<Vec<u8>>> {
+    if let Some(text) = record.get(field) {
+        let text = text.as_str().with_context(|| format!("{} isn't a string", field))?;
+        return Ok(Some(text.as_bytes().to_vec()));
+    }
+    let Some(encoded) = record.get(format!("{}_base64", field)) else {
+        return Ok(None);
+    };
+    let encoded = encoded.as_str().with_context(|| format!("{}_base64 isn't a string", field))?;
+    let bytes = base64::engine::general_purpose::STANDARD.decode(encoded)
+        .with_context(|| format!("{}_base64 isn't base64", field))?;
+    Ok(Some(bytes))
+}
+
+
+// Bytes from a hex string, for --tree-hex
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        bail!("{} is not hex - it needs two digits per byte", hex);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).with_context(|| format!("{} is not hex", hex)))
+        .collect()
+}
@@ -0,0 +1,137 @@
+// file src/compare.rs
+// A second, read-only browser drawn beside the main one, for comparing two trees of this
+// database or two databases side by side. It has an App of its own, so it navigates
+// independently of the main view, and nothing in it writes.
+use crate::app::App;
+use crate::config::Config;
+use crate::tui_app::ViewMode;
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use ratatui::widgets::ListState;
+
+pub struct ComparePane {
+    pub app: App,
+    pub source: String, // "this database", or the other database's path
+    pub view_mode: ViewMode,
+    pub list_state: ListState, // selection within the loaded window, as in the main list
+    pub list_offset: usize,
+    pub list_height: usize, // rows the list shows, kept in step with the main list
+    pub scroll: u16,        // of the value
+}
+
+impl ComparePane {
+    pub fn open(db: sled::Db, source: String, config: &Config, list_height: usize) -> Result<Self> {
+        let mut app = App::new();
+        app.max_depth = config.max_depth;
+        app.configure_delimiter(config.detect_delimiter, config.delimiter.as_deref());
+        app.format_hints = config.formats.clone();
+        app.hidden_trees = config.hidden_trees.clone();
+        app.db = Some(db);
+        app.refresh_trees()?;
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+        Ok(Self { app, source, view_mode: ViewMode::Trees, list_state, list_offset: 0, list_height, scroll: 0 })
+    }
+
+    // Returns false when the pane should close, on Esc from its tree list
+    pub fn handle_key(&mut self, code: KeyCode) -> Result<bool> {
+        let page = self.list_height.max(1) as u16;
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1)?,
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1)?,
+            KeyCode::Home => self.select(0)?,
+            KeyCode::End => self.select(self.total().saturating_sub(1))?,
+            // the list moves by rows, so the pages scroll the value
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(page),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(page),
+            KeyCode::Enter => self.enter()?,
+            KeyCode::Backspace if !self.app.current_path.is_empty() => {
+                self.app.go_back_in_path()?;
+                self.list_offset = 0;
+                self.update_list()?;
+                self.select(0)?;
+            }
+            KeyCode::Backspace | KeyCode::Esc | KeyCode::Char('t') if matches!(self.view_mode, ViewMode::Keys) => {
+                self.view_mode = ViewMode::Trees;
+                self.app.total_keys = 0;
+                self.app.current_tree = None;
+                self.app.current_path.clear();
+                self.list_offset = 0;
+                self.select(0)?;
+            }
+            KeyCode::Esc => return Ok(false),
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    // Follow the main list's height, which changes with the terminal
+    pub fn set_list_height(&mut self, list_height: usize) -> Result<()> {
+        if list_height != self.list_height {
+            self.list_height = list_height;
+            if matches!(self.view_mode, ViewMode::Keys) {
+                self.update_list()?;
+            }
+        }
+        Ok(())
+    }
+
+    // The selected key's value, None in the tree list or on a branch without one
+    pub fn selected_value(&self) -> Option<Vec<u8>> {
+        if !matches!(self.view_mode, ViewMode::Keys) {
+            return None;
+        }
+        let full_key = self.app.full_key(self.list_state.selected().unwrap_or(0))?;
+        self.app.value_of(&full_key).ok().flatten()
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        let selected = self.list_state.selected().unwrap_or(0);
+        match self.view_mode {
+            ViewMode::Trees if self.list_offset + selected < self.app.sled_trees.len() => {
+                self.view_mode = ViewMode::Keys;
+                self.app.select_tree(self.list_offset + selected)?;
+            }
+            ViewMode::Keys if self.app.delimiter.is_some()
+                && self.app.current_key_range.keys.get(selected).is_some_and(|entry| entry.has_children) => {
+                self.app.select_key(selected)?;
+            }
+            _ => return Ok(()),
+        }
+        self.list_offset = 0;
+        self.update_list()?;
+        self.select(0)
+    }
+
+    fn total(&self) -> usize {
+        match self.view_mode {
+            ViewMode::Trees => self.app.sled_trees.len(),
+            ViewMode::Keys => self.app.total_keys,
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) -> Result<()> {
+        let current = self.list_offset + self.list_state.selected().unwrap_or(0);
+        self.select(current.saturating_add_signed(delta).min(self.total().saturating_sub(1)))
+    }
+
+    // Select an absolute index, shifting the window only as far as needed to show it
+    fn select(&mut self, absolute: usize) -> Result<()> {
+        let height = self.list_height.max(1);
+        if absolute < self.list_offset {
+            self.list_offset = absolute;
+        } else if absolute >= self.list_offset + height {
+            self.list_offset = absolute + 1 - height;
+        }
+        if matches!(self.view_mode, ViewMode::Keys) && self.app.current_key_range.offset != self.list_offset {
+            self.update_list()?;
+        }
+        self.list_state.select(Some(absolute - self.list_offset));
+        self.scroll = 0;
+        Ok(())
+    }
+
+    fn update_list(&mut self) -> Result<()> {
+        self.app.set_key_range(self.list_offset, self.list_height)
+    }
+}
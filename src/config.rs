@@ -0,0 +1,288 @@
+// file src/config.rs
+use crate::encoding;
+use crate::format::FormatHint;
+use crate::keymap::Keymap;
+use anyhow::{bail, Context, Result};
+use ratatui::style::{Color, Style, Stylize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// User settings read from ~/.config/sledit/config.toml. Every field has a default,
+// so a missing file or a file that only sets a few options is fine.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Backspace at the root of a tree returns to the tree list. When false, only
+    // Esc / t leave the tree.
+    pub backspace_leaves_tree: bool,
+    // Deepest level of the delimiter hierarchy; keys are not split any further below it
+    pub max_depth: Option<usize>,
+    // How long after pressing d a second d still deletes the key
+    pub delete_confirm_timeout_ms: u64,
+    // Re-read the current view every this many seconds, for leaving sledit open as a monitor
+    pub auto_refresh_secs: Option<u64>,
+    // Warn that the view may be stale after this many seconds without a key press, for a
+    // monitor that isn't auto-refreshing
+    pub stale_after_secs: Option<u64>,
+    // Tree names left out of the tree list, as globs (* and ?), e.g. "__internal*"
+    pub hidden_trees: Vec<String>,
+    // Show the selected key's position in the key list title, e.g. "1234 / 50000"
+    pub show_position: bool,
+    // Lines kept in view across a PageUp / PageDown, in both panes
+    pub page_overlap: usize,
+    // Guess each tree's key delimiter when it is opened
+    pub detect_delimiter: bool,
+    // Split every tree's keys on this instead of detecting each tree's; "" lists whole keys.
+    // --delimiter sets it too.
+    pub delimiter: Option<String>,
+    // Ctrl-S in the editor shows the changes and asks before saving
+    pub review_edits: bool,
+    // What Enter does on a key with no children: focus_value, edit or nothing
+    pub enter_on_leaf: EnterOnLeaf,
+    // What Tab does on a branch with no value of its own: hint, open or focus
+    pub tab_on_branch: TabOnBranch,
+    // Where sledit starts: list (the tree list), first_named or first_non_empty. --tree
+    // overrides it.
+    pub startup_tree: StartupTree,
+    pub pretty: PrettyPrint,
+    pub highlight: Highlight,
+    // [formats] - tree name to the format of all its values: json, toml, yaml, ron, hex, text, csv, tsv or ndjson
+    pub formats: BTreeMap<String, FormatHint>,
+    // [encodings] - tree name to the text encoding of its values, e.g. "windows-1252", "utf-16le"
+    pub encodings: BTreeMap<String, String>,
+    pub protobuf: Protobuf,
+    pub modified: Modified,
+    pub owner: Owner,
+    // from keys.toml rather than this file
+    #[serde(skip)]
+    pub keymap: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            backspace_leaves_tree: true,
+            max_depth: None,
+            delete_confirm_timeout_ms: 2000,
+            auto_refresh_secs: None,
+            stale_after_secs: None,
+            hidden_trees: vec![],
+            show_position: true,
+            page_overlap: 1,
+            detect_delimiter: true,
+            delimiter: None,
+            review_edits: false,
+            enter_on_leaf: EnterOnLeaf::default(),
+            tab_on_branch: TabOnBranch::default(),
+            startup_tree: StartupTree::default(),
+            pretty: PrettyPrint::default(),
+            highlight: Highlight::default(),
+            formats: BTreeMap::new(),
+            encodings: BTreeMap::new(),
+            protobuf: Protobuf::default(),
+            modified: Modified::default(),
+            owner: Owner::default(),
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnterOnLeaf {
+    #[default]
+    FocusValue, // move to the Value pane, to scroll it
+    Edit,       // open the value in the editor
+    Nothing,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TabOnBranch {
+    #[default]
+    Hint,  // stay in the list and say there's nothing to show
+    Open,  // go into the branch, as Enter does
+    Focus, // move to the empty Value pane anyway
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupTree {
+    #[default]
+    List,
+    FirstNamed,    // the first tree in the list other than sled's default tree
+    FirstNonEmpty, // the first tree in the list with any keys
+}
+
+// [pretty] - how values are re-serialized when pretty-printing. YAML and TOML
+// use their serializers' fixed layouts.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrettyPrint {
+    // values start out pretty-printed and coloured; p shows them as stored
+    pub on_start: bool,
+    pub json_indent: usize,
+    pub json_sort_keys: bool,
+    pub ron_indent: usize,
+}
+
+impl Default for PrettyPrint {
+    fn default() -> Self {
+        Self {
+            on_start: true,
+            json_indent: 2,
+            json_sort_keys: false,
+            ron_indent: 2,
+        }
+    }
+}
+
+// [highlight] - how the selected row of a list is drawn. The default is reverse video,
+// which some terminal themes make unreadable.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Highlight {
+    pub reversed: bool,
+    pub bold: bool,
+    // colour names ("yellow"), indexes ("208") or hex ("#ffaa00")
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    // drawn before the selected row, e.g. "▶ ", so it shows even without reverse video
+    pub symbol: String,
+}
+
+impl Default for Highlight {
+    fn default() -> Self {
+        Self {
+            reversed: true,
+            bold: false,
+            fg: None,
+            bg: None,
+            symbol: String::new(),
+        }
+    }
+}
+
+impl Highlight {
+    pub fn style(&self) -> Style {
+        let mut style = Style::default();
+        if self.reversed {
+            style = style.reversed();
+        }
+        if self.bold {
+            style = style.bold();
+        }
+        // colours were checked when the config was loaded
+        if let Some(fg) = self.fg.as_deref().and_then(|fg| fg.parse::<Color>().ok()) {
+            style = style.fg(fg);
+        }
+        if let Some(bg) = self.bg.as_deref().and_then(|bg| bg.parse::<Color>().ok()) {
+            style = style.bg(bg);
+        }
+        style
+    }
+
+    fn validate(&self) -> Result<()> {
+        for colour in [&self.fg, &self.bg].into_iter().flatten() {
+            if colour.parse::<Color>().is_err() {
+                bail!("Unknown highlight colour {}", colour);
+            }
+        }
+        Ok(())
+    }
+}
+
+// [protobuf] - decode the values of some trees as protobuf messages
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Protobuf {
+    // FileDescriptorSet describing the messages, from protoc --descriptor_set_out
+    pub descriptor_set: Option<PathBuf>,
+    // tree name to fully qualified message name, e.g. users = "app.v1.User"
+    pub messages: BTreeMap<String, String>,
+}
+
+// [modified] - annotate keys with how long ago their value was modified, read from a
+// timestamp field the application keeps inside its values
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Modified {
+    // dotted path to the field, e.g. "meta.updated_at"; unset turns the annotation off
+    pub field: Option<String>,
+    pub format: TimestampFormat,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    #[default]
+    Unix,   // seconds since the epoch
+    UnixMs, // milliseconds since the epoch
+    Rfc3339,
+}
+
+// [owner] - show who a value belongs to, from an owner or ACL field the application keeps
+// inside its values, as a badge on its key and in the value pane title
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Owner {
+    // dotted path to the field, e.g. "acl.owner"; unset turns the badge off
+    pub field: Option<String>,
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_dir().map(|dir| dir.join("config.toml")) else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read config file {}", path.display()))?;
+        let config: Self = toml::from_str(&text)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.highlight.validate()
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        for label in config.encodings.values() {
+            encoding::by_label(label).with_context(|| format!("Invalid config file {}", path.display()))?;
+        }
+        Ok(config)
+    }
+}
+
+// Choices made with a key in the TUI that should outlast the session, kept in state.toml
+// beside the config. sledit writes this file itself, so it stays out of config.toml.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UiState {
+    // # shows sizes as exact byte counts rather than KiB / MiB
+    pub exact_sizes: bool,
+}
+
+impl UiState {
+    // A missing or unreadable file is the defaults; this is a convenience, not a setting
+    pub fn load() -> Self {
+        config_dir()
+            .and_then(|dir| std::fs::read_to_string(dir.join("state.toml")).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = config_dir().context("No config directory - set HOME or XDG_CONFIG_HOME")?;
+        std::fs::create_dir_all(&dir).with_context(|| format!("Could not create {}", dir.display()))?;
+        let path = dir.join("state.toml");
+        std::fs::write(&path, toml::to_string(self)?).with_context(|| format!("Could not write {}", path.display()))
+    }
+}
+
+// $XDG_CONFIG_HOME/sledit, falling back to ~/.config/sledit
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("sledit"))
+}
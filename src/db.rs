@@ -0,0 +1,100 @@
+// file src/db.rs
+// Opening databases, with sled's open errors turned into something actionable
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+
+// Major.minor of the sled crate sledit is linked against. sled only opens databases
+// whose on-disk format carries the same version.
+pub const SLED_VERSION: &str = "0.34";
+
+
+// The sled version recorded in a database's conf file. None for a directory that is not
+// a database yet, or one written before sled 0.29, which kept no readable version.
+pub fn format_version(path: &Path) -> Option<String> {
+    let conf = std::fs::read(path.join("conf")).ok()?;
+    String::from_utf8_lossy(&conf)
+        .lines()
+        .find_map(|line| line.strip_prefix("version: ").map(str::to_string))
+}
+
+
+// Whether a path already holds files, as a database would
+pub fn holds_files(path: &Path) -> bool {
+    match std::fs::read_dir(path) {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(_) => path.exists(),
+    }
+}
+
+
+// A path for a test database in the system temp directory, removed again when dropped
+#[cfg(test)]
+pub struct TempDb(pub std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDb {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("sledit-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        Self(path)
+    }
+
+    // A database there holding `keys` in the tree `tree`, closed again so sledit can open it
+    pub fn with_keys(name: &str, tree: &str, keys: &[(&str, &str)]) -> Self {
+        let temp = Self::new(name);
+        let db = sled::open(&temp.0).expect("test database opens");
+        let tree = db.open_tree(tree).expect("test tree opens");
+        for (key, value) in keys {
+            tree.insert(key.as_bytes(), value.as_bytes()).expect("test key is written");
+        }
+        db.flush().expect("test database flushes");
+        drop((tree, db));
+        temp.wait_for_lock();
+        temp
+    }
+
+    // sled's background threads can hold a closed database's lock a moment longer, so wait
+    // for it before the database is opened again
+    pub fn wait_for_lock(&self) {
+        let Ok(file) = std::fs::File::open(self.0.join("db")) else { return };
+        while file.try_lock().is_err() {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+
+// Open a database, refusing up front when it was written by an incompatible sled
+pub fn open(path: &Path) -> Result<sled::Db> {
+    if let Some(version) = format_version(path) {
+        if version != SLED_VERSION {
+            bail!(
+                "{} was written by sled {}, but sledit is built with sled {}, which can't read it. \
+                 Open it with a sledit built against sled {}, or migrate it with sled's export/import.",
+                path.display(), version, SLED_VERSION, version
+            );
+        }
+    }
+    sled::open(path).map_err(|e| match e {
+        sled::Error::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            anyhow!("No permission to open {}: {}", path.display(), e)
+        }
+        sled::Error::Io(e) if e.to_string().contains("could not acquire lock") => {
+            anyhow!("{} is open in another process - sled allows only one at a time", path.display())
+        }
+        sled::Error::Unsupported(message) => {
+            anyhow!("sled {} can't open {}: {}", SLED_VERSION, path.display(), message)
+        }
+        sled::Error::Corruption { .. } => {
+            anyhow!("{} is corrupt - sled found damaged data in its files", path.display())
+        }
+        e => anyhow!("Could not open {}: {}", path.display(), e),
+    })
+}
@@ -0,0 +1,101 @@
+// file src/disk_index.rs
+// The key hierarchy of a tree held in a temporary on-disk sled database rather than the
+// in-memory KeyTree, for trees too large to index in RAM (--disk-index)
+use crate::app::KeyEntry;
+use crate::task::Progress;
+use anyhow::Result;
+
+pub struct DiskIndex {
+    _db: sled::Db,      // temporary, deleted when dropped
+    nodes: sled::Tree,  // level key + segment -> [1] for a branch, [0] for a leaf
+    counts: sled::Tree, // level key -> number of entries in the level, u64 big-endian
+}
+
+// Identifies one level of the hierarchy: the path's length, then each of its segments
+// NUL-terminated, so a prefix scan finds exactly that level's entries in segment order.
+// Segments that hold a NUL themselves can bleed into a sibling level.
+fn level_key(path: &[impl AsRef<str>]) -> Vec<u8> {
+    let mut key = (path.len() as u32).to_be_bytes().to_vec();
+    for segment in path {
+        key.extend_from_slice(segment.as_ref().as_bytes());
+        key.push(0);
+    }
+    key
+}
+
+impl DiskIndex {
+    // Split every key of the tree into the index. Runs on a worker thread.
+    pub fn build(tree: &sled::Tree, delimiter: &str, max_depth: Option<usize>, progress: &Progress) -> Result<Self> {
+        let db = sled::Config::new().temporary(true).open()?;
+        let nodes = db.open_tree("nodes")?;
+        let counts = db.open_tree("counts")?;
+        for result in tree.iter().keys() {
+            progress.check()?;
+            let key = String::from_utf8_lossy(&result?).to_string();
+            let parts: Vec<&str> = match max_depth {
+                Some(depth) => key.splitn(depth.max(1), delimiter).collect(),
+                None => key.split(delimiter).collect(),
+            };
+            for depth in 0..parts.len() {
+                let level = level_key(&parts[..depth]);
+                let mut node = level.clone();
+                node.extend_from_slice(parts[depth].as_bytes());
+                let branch = depth + 1 < parts.len();
+                match nodes.get(&node)? {
+                    None => {
+                        let count = counts.get(&level)?.map_or(0, |count| read_u64(&count));
+                        counts.insert(&level, &(count + 1).to_be_bytes())?;
+                        nodes.insert(node, &[branch as u8])?;
+                    }
+                    // a leaf that turns out to have keys under it as well
+                    Some(flag) if branch && flag.as_ref() == [0] => {
+                        nodes.insert(node, &[1])?;
+                    }
+                    Some(_) => {}
+                }
+            }
+            progress.inc();
+        }
+        Ok(Self { _db: db, nodes, counts })
+    }
+
+    pub fn child_count(&self, path: &[String]) -> Result<usize> {
+        Ok(self.counts.get(level_key(path))?.map_or(0, |count| read_u64(&count) as usize))
+    }
+
+    // A window of the entries of one level, seeking through the index rather than loading it
+    pub fn children(&self, path: &[String], offset: usize, count: usize) -> Result<Vec<KeyEntry>> {
+        let level = level_key(path);
+        self.nodes.scan_prefix(&level)
+            .skip(offset)
+            .take(count)
+            .map(|result| {
+                let (node, flag) = result?;
+                let key = String::from_utf8_lossy(&node[level.len()..]).to_string();
+                let has_children = flag.as_ref() == [1];
+                // each level's size is kept in the index, so this is one lookup
+                let child_count = match has_children {
+                    true => self.child_count(&[path, std::slice::from_ref(&key)].concat())?,
+                    false => 0,
+                };
+                Ok(KeyEntry { key, has_children, child_count })
+            })
+            .collect()
+    }
+
+    // Where an entry sits in its level, None when it isn't there
+    pub fn position(&self, path: &[String], name: &str) -> Result<Option<usize>> {
+        let level = level_key(path);
+        let mut node = level.clone();
+        node.extend_from_slice(name.as_bytes());
+        if !self.nodes.contains_key(&node)? {
+            return Ok(None);
+        }
+        // everything from the level key up to the entry belongs to the level
+        Ok(Some(self.nodes.range(level..node).count()))
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    bytes.try_into().map(u64::from_be_bytes).unwrap_or(0)
+}
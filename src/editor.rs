@@ -0,0 +1,326 @@
+// file src/editor.rs
+use crate::encoding;
+use anyhow::{Context, Result};
+use encoding_rs::{Encoding, UTF_8};
+
+// An in-progress edit of one value. `original` is the value as it was read, so saving
+// can refuse to overwrite a change made by someone else in the meantime. Values in another
+// text encoding are edited decoded and encoded back on save.
+pub struct Editor {
+    pub full_key: String,
+    pub original: Vec<u8>,
+    pub encoding: Option<&'static Encoding>, // None for UTF-8
+    original_text: String,
+    pub buffer: String,
+    cursor: usize,                // byte offset into buffer, always on a char boundary
+    goal_column: Option<usize>,   // column Up / Down aim for, kept across short lines
+    pub top: usize,               // first line shown
+    pub left: usize,              // first column shown
+    pub confirm_reload: bool, // asked whether to throw away unsaved edits
+    pub confirm_save: bool,   // showing the diff and asking whether to save it
+    pub show_diff: bool,      // value pane shows the changes instead of the buffer
+}
+
+impl Editor {
+    pub fn new(full_key: String, original: Vec<u8>, encoding: Option<&'static Encoding>) -> Result<Self> {
+        // decoding as UTF-8 would quietly replace the bytes that aren't
+        let encoding = encoding.filter(|encoding| *encoding != UTF_8);
+        let original_text = match encoding {
+            Some(encoding) => encoding::decode(&original, encoding),
+            None => String::from_utf8(original.clone()).context("Binary value, can't edit it as text - U picks a text encoding")?,
+        };
+        let buffer = original_text.clone();
+        Ok(Self {
+            full_key,
+            original,
+            encoding,
+            original_text,
+            buffer,
+            cursor: 0,
+            goal_column: None,
+            top: 0,
+            left: 0,
+            confirm_reload: false,
+            confirm_save: false,
+            show_diff: false,
+        })
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.buffer != self.original_text
+    }
+
+    // The buffer as the bytes to store, in the value's encoding
+    pub fn encoded(&self) -> Result<Vec<u8>> {
+        match self.encoding {
+            Some(encoding) => encoding::encode(&self.buffer, encoding),
+            None => Ok(self.buffer.as_bytes().to_vec()),
+        }
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+        self.goal_column = None;
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        self.buffer.insert_str(self.cursor, text);
+        self.cursor += text.len();
+        self.goal_column = None;
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some(c) = self.buffer[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+            self.buffer.remove(self.cursor);
+        }
+        self.goal_column = None;
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.buffer.len() {
+            self.buffer.remove(self.cursor);
+        }
+        self.goal_column = None;
+    }
+
+    pub fn move_left(&mut self) {
+        if let Some(c) = self.buffer[..self.cursor].chars().next_back() {
+            self.cursor -= c.len_utf8();
+        }
+        self.goal_column = None;
+    }
+
+    pub fn move_right(&mut self) {
+        if let Some(c) = self.buffer[self.cursor..].chars().next() {
+            self.cursor += c.len_utf8();
+        }
+        self.goal_column = None;
+    }
+
+    // Up (negative) or down by a number of lines, keeping to the column the move started
+    // from where the lines are long enough
+    pub fn move_lines(&mut self, lines: isize) {
+        let column = self.goal_column.unwrap_or_else(|| self.cursor_position().1);
+        let mut start = self.line_start(self.cursor);
+        for _ in 0..lines.unsigned_abs() {
+            if lines < 0 {
+                if start == 0 {
+                    break;
+                }
+                start = self.line_start(start - 1);
+            } else {
+                let end = self.line_end(start);
+                if end == self.buffer.len() {
+                    break;
+                }
+                start = end + 1;
+            }
+        }
+        let end = self.line_end(start);
+        self.cursor = self.buffer[start..end].char_indices().nth(column).map_or(end, |(offset, _)| start + offset);
+        self.goal_column = Some(column);
+    }
+
+    pub fn move_to_line_start(&mut self) {
+        self.cursor = self.line_start(self.cursor);
+        self.goal_column = None;
+    }
+
+    pub fn move_to_line_end(&mut self) {
+        self.cursor = self.line_end(self.cursor);
+        self.goal_column = None;
+    }
+
+    pub fn move_to(&mut self, start: bool) {
+        self.cursor = if start { 0 } else { self.buffer.len() };
+        self.goal_column = None;
+    }
+
+    // Line and column of the cursor, both from 0, the column in chars
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let row = self.buffer[..self.cursor].matches('\n').count();
+        let column = self.buffer[self.line_start(self.cursor)..self.cursor].chars().count();
+        (row, column)
+    }
+
+    // Scroll just far enough to show the cursor in a pane of this many lines and columns
+    pub fn follow_cursor(&mut self, height: usize, width: usize) {
+        let (row, column) = self.cursor_position();
+        if row < self.top {
+            self.top = row;
+        } else if row >= self.top + height.max(1) {
+            self.top = row + 1 - height.max(1);
+        }
+        if column < self.left {
+            self.left = column;
+        } else if column >= self.left + width.max(1) {
+            self.left = column + 1 - width.max(1);
+        }
+    }
+
+    fn line_start(&self, at: usize) -> usize {
+        self.buffer[..at].rfind('\n').map_or(0, |newline| newline + 1)
+    }
+
+    fn line_end(&self, at: usize) -> usize {
+        self.buffer[at..].find('\n').map_or(self.buffer.len(), |newline| at + newline)
+    }
+
+    // Line diff of the buffer against the value as it was read
+    pub fn diff(&self) -> Vec<DiffLine> {
+        diff_lines(&self.original_text, &self.buffer)
+    }
+
+    // Start over from the value now stored
+    pub fn reload(&mut self, current: Vec<u8>) -> Result<()> {
+        *self = Self::new(std::mem::take(&mut self.full_key), current, self.encoding)?;
+        Ok(())
+    }
+}
+
+
+// Lines of the longest value diffed line by line; longer ones are shown as replaced whole
+const MAX_DIFF_LINES: usize = 2000;
+
+#[derive(Debug, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+// Line diff from the longest common subsequence of lines
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.split('\n').collect();
+    let new: Vec<&str> = new.split('\n').collect();
+    if old.len() > MAX_DIFF_LINES || new.len() > MAX_DIFF_LINES {
+        return old.iter().map(|line| DiffLine::Removed(line.to_string()))
+            .chain(new.iter().map(|line| DiffLine::Added(line.to_string())))
+            .collect();
+    }
+    // common[i][j]: length of the LCS of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        } else {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        }
+    }
+    lines
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(text: &str) -> Editor {
+        Editor::new("key".to_string(), text.as_bytes().to_vec(), None).unwrap()
+    }
+
+    #[test]
+    fn multi_byte_characters() {
+        let mut editor = editor("aé日b");
+        editor.move_to(false);
+        assert_eq!(editor.cursor_position(), (0, 4));
+        editor.move_left();
+        editor.backspace(); // 日
+        assert_eq!(editor.buffer, "aéb");
+        assert_eq!(editor.cursor_position(), (0, 2));
+        editor.move_left();
+        editor.delete(); // é
+        assert_eq!(editor.buffer, "ab");
+        editor.insert('ü');
+        assert_eq!(editor.buffer, "aüb");
+        assert_eq!(editor.cursor_position(), (0, 2));
+        editor.move_to(true);
+        editor.backspace(); // nothing before the start
+        editor.move_to(false);
+        editor.delete(); // nothing after the end
+        assert_eq!(editor.buffer, "aüb");
+    }
+
+    #[test]
+    fn goal_column_is_kept_across_short_lines() {
+        let mut editor = editor("héllo wörld\nab\n日本語です、長い行");
+        editor.move_lines(0);
+        for _ in 0..7 {
+            editor.move_right();
+        }
+        assert_eq!(editor.cursor_position(), (0, 7));
+        editor.move_lines(1);
+        assert_eq!(editor.cursor_position(), (1, 2)); // the end of the short line
+        editor.move_lines(1);
+        assert_eq!(editor.cursor_position(), (2, 7)); // back at the column it started from
+        editor.move_lines(-2);
+        assert_eq!(editor.cursor_position(), (0, 7));
+        // any other movement forgets the goal
+        editor.move_lines(1);
+        editor.move_left();
+        editor.move_lines(1);
+        assert_eq!(editor.cursor_position(), (2, 1));
+    }
+
+    #[test]
+    fn moving_past_the_first_and_last_lines_stops_there() {
+        let mut editor = editor("one\ntwo\nthree");
+        editor.move_right();
+        editor.move_lines(-1);
+        assert_eq!(editor.cursor_position(), (0, 1));
+        editor.move_lines(-5);
+        assert_eq!(editor.cursor_position(), (0, 1));
+        editor.move_lines(10);
+        assert_eq!(editor.cursor_position(), (2, 1));
+        editor.move_lines(1);
+        assert_eq!(editor.cursor_position(), (2, 1));
+    }
+
+    #[test]
+    fn follow_cursor_scrolls_just_far_enough() {
+        let mut editor = editor(&(0..20).map(|n| format!("line {} {}", n, "x".repeat(n))).collect::<Vec<_>>().join("\n"));
+        editor.move_lines(12);
+        editor.move_to_line_end();
+        editor.follow_cursor(5, 10);
+        assert_eq!((editor.top, editor.left), (8, 11)); // row 12 at the bottom, column 20 at the right
+        editor.move_to(true);
+        editor.follow_cursor(5, 10);
+        assert_eq!((editor.top, editor.left), (0, 0));
+    }
+
+    #[test]
+    fn diff_lines_marks_changes() {
+        use DiffLine::*;
+        let same = |text: &str| Same(text.to_string());
+        let added = |text: &str| Added(text.to_string());
+        let removed = |text: &str| Removed(text.to_string());
+        assert_eq!(diff_lines("a\nb\nc", "a\nb\nc"), [same("a"), same("b"), same("c")]);
+        assert_eq!(diff_lines("a\nb\nc", "a\nx\nc\nd"), [same("a"), added("x"), removed("b"), same("c"), added("d")]);
+        assert_eq!(diff_lines("a\nb", "b"), [removed("a"), same("b")]);
+
+        let mut editor = editor("x\ny");
+        editor.move_to(false);
+        editor.insert_str("\nz");
+        assert!(editor.is_dirty());
+        assert_eq!(editor.diff(), [same("x"), same("y"), added("z")]);
+    }
+}
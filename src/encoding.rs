@@ -0,0 +1,44 @@
+// file src/encoding.rs
+// Text stored in an encoding other than UTF-8 (Latin-1, UTF-16, Shift_JIS...), decoded for
+// the value pane and the editor and encoded back when an edit is saved
+use anyhow::{bail, Context, Result};
+use encoding_rs::{
+    Encoding, BIG5, EUC_JP, EUC_KR, GBK, ISO_8859_2, KOI8_R, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8,
+    WINDOWS_1251, WINDOWS_1252,
+};
+
+// What U steps a value through, before going back to its tree's encoding
+pub const CYCLE: [&Encoding; 12] = [
+    WINDOWS_1252, ISO_8859_2, WINDOWS_1251, KOI8_R, UTF_16LE, UTF_16BE, SHIFT_JIS, EUC_JP, GBK, BIG5, EUC_KR, UTF_8,
+];
+
+// An encoding from a WHATWG label, as used in the config: "utf-16le", "shift_jis", "latin1"
+pub fn by_label(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes()).with_context(|| format!("Unknown text encoding {}", label))
+}
+
+// Bytes to text exactly as stored, a BOM included; bytes that aren't valid in the encoding
+// become U+FFFD
+pub fn decode(bytes: &[u8], encoding: &'static Encoding) -> String {
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+// Text back into an encoding. Fails rather than save characters the encoding has no room
+// for, which encoding_rs would otherwise write as &#...; references.
+pub fn encode(text: &str, encoding: &'static Encoding) -> Result<Vec<u8>> {
+    // encoding_rs only decodes UTF-16
+    if encoding == UTF_16LE {
+        return Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect());
+    }
+    if encoding == UTF_16BE {
+        return Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect());
+    }
+    if encoding.output_encoding() != encoding {
+        bail!("Text can't be written in {}", encoding.name());
+    }
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        bail!("The text has characters {} can't represent", encoding.name());
+    }
+    Ok(bytes.into_owned())
+}
@@ -0,0 +1,71 @@
+// file src/events.rs
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Most recent events kept for display
+const MAX_EVENTS: usize = 200;
+
+// A live feed of inserts and removes on one tree. A worker thread waits on sled's
+// subscriber and forwards each event as a display line.
+pub struct EventFeed {
+    pub tree_name: String,
+    pub lines: VecDeque<String>,
+    receiver: Receiver<String>,
+    stop: Arc<AtomicBool>,
+}
+
+impl EventFeed {
+    pub fn subscribe(tree: &sled::Tree) -> Self {
+        let tree_name = String::from_utf8_lossy(&tree.name()).to_string();
+        let mut subscriber = tree.watch_prefix(vec![]);
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let worker_stop = stop.clone();
+        thread::spawn(move || {
+            // wake up regularly so the thread notices when the feed is closed
+            while !worker_stop.load(Ordering::Relaxed) {
+                let line = match subscriber.next_timeout(Duration::from_millis(250)) {
+                    Ok(sled::Event::Insert { key, value }) => {
+                        format!("{} insert {} ({} bytes)", clock(), String::from_utf8_lossy(&key), value.len())
+                    }
+                    Ok(sled::Event::Remove { key }) => {
+                        format!("{} remove {}", clock(), String::from_utf8_lossy(&key))
+                    }
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { tree_name, lines: VecDeque::new(), receiver, stop }
+    }
+
+    // Move newly arrived events into `lines`, dropping the oldest past the cap
+    pub fn drain(&mut self) {
+        while let Ok(line) = self.receiver.try_recv() {
+            if self.lines.len() == MAX_EVENTS {
+                self.lines.pop_front();
+            }
+            self.lines.push_back(line);
+        }
+    }
+}
+
+impl Drop for EventFeed {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+
+// Time of day (UTC) for event lines
+fn clock() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) % 86400;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
@@ -0,0 +1,445 @@
+// file src/format.rs
+use crate::config::PrettyPrint;
+use anyhow::Result;
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use serde::{Deserialize, Serialize};
+
+// Serialization formats we can recognise in stored values
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+    Ron,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Yaml => "yaml",
+            Format::Ron => "ron",
+        }
+    }
+}
+
+
+// A tree's declared value format, from [formats] in the config. Skips detection, which
+// can misfire and costs parse attempts on every value of a large uniform tree.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormatHint {
+    Json,
+    Toml,
+    Yaml,
+    Ron,
+    Hex,
+    Text,
+    Csv,
+    Tsv,
+    Ndjson,
+}
+
+
+// Broad content type of a stored value, used to audit what a tree holds
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ValueKind {
+    Structured(Format),
+    Text,
+    Binary,
+    Empty,
+}
+
+impl ValueKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueKind::Structured(format) => format.name(),
+            ValueKind::Text => "text",
+            ValueKind::Binary => "binary",
+            ValueKind::Empty => "empty",
+        }
+    }
+}
+
+
+pub fn classify(bytes: &[u8]) -> ValueKind {
+    if bytes.is_empty() {
+        return ValueKind::Empty;
+    }
+    if let Some(format) = detect(bytes) {
+        return ValueKind::Structured(format);
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        ValueKind::Text
+    } else {
+        ValueKind::Binary
+    }
+}
+
+
+// Work out which format a value is stored in, if any. Stricter formats are tried first,
+// and YAML / RON only count when they parse to a map or sequence, since almost any
+// plain text is a valid YAML scalar.
+pub fn detect(bytes: &[u8]) -> Option<Format> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    if text.trim().is_empty() {
+        return None;
+    }
+    if serde_json::from_str::<serde_json::Value>(text).is_ok() {
+        return Some(Format::Json);
+    }
+    if text.parse::<toml::Table>().is_ok() {
+        return Some(Format::Toml);
+    }
+    if matches!(
+        serde_yaml::from_str::<serde_yaml::Value>(text),
+        Ok(serde_yaml::Value::Mapping(_) | serde_yaml::Value::Sequence(_))
+    ) {
+        return Some(Format::Yaml);
+    }
+    if matches!(ron::from_str::<ron::Value>(text), Ok(ron::Value::Map(_) | ron::Value::Seq(_))) {
+        return Some(Format::Ron);
+    }
+    None
+}
+
+
+// Lines looked at to decide whether a value is newline-delimited JSON
+const NDJSON_SAMPLE_LINES: usize = 100;
+
+// Whether a value is newline-delimited JSON: two or more records, the first and most of
+// the rest a JSON object or array on a line of its own. A stray line that isn't JSON,
+// like a truncated last record, doesn't stop it counting.
+pub fn is_ndjson(bytes: &[u8]) -> bool {
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return false;
+    };
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty()).take(NDJSON_SAMPLE_LINES).peekable();
+    if lines.peek().is_none_or(|first| !is_json_record(first)) {
+        return false;
+    }
+    let (records, total) = lines.fold((0, 0), |(records, total), line| (records + is_json_record(line) as usize, total + 1));
+    records >= 2 && records * 2 > total
+}
+
+fn is_json_record(line: &str) -> bool {
+    matches!(serde_json::from_str(line), Ok(serde_json::Value::Object(_) | serde_json::Value::Array(_)))
+}
+
+
+// A JSON / TOML / YAML / RON value read into serde_json's model, with the format it was
+// detected as, for picking fields out of it
+pub fn to_json(bytes: &[u8]) -> Option<(Format, serde_json::Value)> {
+    let format = detect(bytes)?;
+    let text = std::str::from_utf8(bytes).ok()?;
+    let value = match format {
+        Format::Json => serde_json::from_str(text).ok()?,
+        Format::Toml => toml::from_str(text).ok()?,
+        Format::Yaml => serde_yaml::from_str(text).ok()?,
+        Format::Ron => ron::from_str(text).ok()?,
+    };
+    Some((format, value))
+}
+
+// The field at a dotted path ("meta.updated_at"), with numbers indexing into arrays
+pub fn lookup<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.').try_fold(value, |value, segment| match value {
+        serde_json::Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => value.get(segment),
+    })
+}
+
+
+// How the lines of a text value are terminated, when it has more than one line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEndings {
+    Lf,
+    Crlf,
+    Mixed,
+}
+
+impl LineEndings {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineEndings::Lf => "LF",
+            LineEndings::Crlf => "CRLF",
+            LineEndings::Mixed => "mixed EOL",
+        }
+    }
+}
+
+pub fn line_endings(text: &str) -> Option<LineEndings> {
+    let breaks = text.matches('\n').count();
+    let crlf = text.matches("\r\n").count();
+    match (breaks, crlf) {
+        (0, _) => None,
+        (_, 0) => Some(LineEndings::Lf),
+        _ if crlf == breaks => Some(LineEndings::Crlf),
+        _ => Some(LineEndings::Mixed),
+    }
+}
+
+// The text with each CRLF as a plain line break, as the value pane shows it. A lone \r
+// isn't a line break and is left in place.
+pub fn to_lf(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+
+// A value formatted for display in its tree's hinted format, or its detected one without
+// a hint, along with the format's name. None when it should be shown as stored.
+pub fn render(bytes: &[u8], hint: Option<FormatHint>, settings: &PrettyPrint) -> Option<(&'static str, String)> {
+    let format = match hint {
+        Some(FormatHint::Json) => Format::Json,
+        Some(FormatHint::Toml) => Format::Toml,
+        Some(FormatHint::Yaml) => Format::Yaml,
+        Some(FormatHint::Ron) => Format::Ron,
+        Some(FormatHint::Hex) => return Some(("hex", hex_dump(bytes))),
+        Some(FormatHint::Text | FormatHint::Csv | FormatHint::Tsv | FormatHint::Ndjson) => return None,
+        None => detect(bytes)?,
+    };
+    pretty(bytes, format, settings).ok().map(|text| (format.name(), text))
+}
+
+
+// Most rows parsed from a CSV/TSV value for the table view
+const MAX_TABLE_ROWS: usize = 1000;
+
+// A CSV or TSV value split into rows of fields; the first row is taken as the header
+pub struct Table {
+    pub rows: Vec<Vec<String>>,
+    pub truncated: bool, // stopped at MAX_TABLE_ROWS
+}
+
+// Parse a value as CSV/TSV: always for trees hinted csv/tsv, otherwise only when it looks
+// like a table - a header and at least one row, every row the same 2+ fields wide. An empty
+// value isn't a table either way.
+pub fn parse_table(bytes: &[u8], hint: Option<FormatHint>) -> Option<Table> {
+    let separator = match hint {
+        Some(FormatHint::Csv) => b',',
+        Some(FormatHint::Tsv) => b'\t',
+        Some(_) => return None,
+        None => {
+            let first_line = std::str::from_utf8(bytes).ok()?.lines().next()?;
+            if first_line.contains('\t') {
+                b'\t'
+            } else if first_line.contains(',') {
+                b','
+            } else {
+                return None;
+            }
+        }
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(separator)
+        .flexible(hint.is_some())
+        .from_reader(bytes);
+    let mut table = Table { rows: vec![], truncated: false };
+    for record in reader.records() {
+        if table.rows.len() == MAX_TABLE_ROWS {
+            table.truncated = true;
+            break;
+        }
+        table.rows.push(record.ok()?.iter().map(String::from).collect());
+    }
+    if table.rows.is_empty() || (hint.is_none() && (table.rows.len() < 2 || table.rows[0].len() < 2)) {
+        return None;
+    }
+    Some(table)
+}
+
+
+// Classic hex dump: offset, 16 bytes in hex, then the printable ASCII
+pub fn hex_dump(bytes: &[u8]) -> String {
+    bytes.chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let ascii: String = chunk.iter()
+                .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+                .collect();
+            format!("{:08x}  {:<47}  |{}|", row * 16, hex.join(" "), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+
+// The bytes as UTF-8 text, with every byte that isn't part of valid UTF-8 written as a
+// \xNN escape instead of replaced, and backslashes doubled so the escapes can't be
+// mistaken for text. Nothing is lost, unlike a lossy conversion.
+pub fn escaped_utf8(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(&chunk.valid().replace('\\', "\\\\"));
+        for byte in chunk.invalid() {
+            text.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    text
+}
+
+
+// A byte count for display: exact ("1536 bytes") or in binary units ("1.5 KiB"),
+// following the # toggle. Under 1 KiB both read the same.
+pub fn size(bytes: usize, exact: bool) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if exact || bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut amount = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while amount >= 1024.0 && unit < UNITS.len() - 1 {
+        amount /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", amount, UNITS[unit])
+}
+
+
+// Colours for pretty-printed JSON / TOML / YAML / RON
+const KEY_STYLE: Style = Style::new().fg(Color::Cyan);
+const STRING_STYLE: Style = Style::new().fg(Color::Green);
+const NUMBER_STYLE: Style = Style::new().fg(Color::Magenta);
+const LITERAL_STYLE: Style = Style::new().fg(Color::Yellow);
+
+// Pretty-printed text split into styled lines: keys, strings, numbers and true / false /
+// null each in their own colour. A light scan of each line rather than a parse, which
+// serves all four formats and anything folded or wrapped after printing.
+pub fn highlight(text: &str) -> Vec<Line<'static>> {
+    text.split('\n').map(highlight_line).collect()
+}
+
+fn highlight_line(line: &str) -> Line<'static> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans = vec![];
+    let mut plain = String::new();
+    let mut first_token = true;
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let start = index;
+        let style = if c == '"' || c == '\'' {
+            index += 1;
+            while index < chars.len() && chars[index] != c {
+                index += if chars[index] == '\\' { 2 } else { 1 };
+            }
+            index = (index + 1).min(chars.len());
+            Some(if names_field(&chars[index..]) { KEY_STYLE } else { STRING_STYLE })
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(index + 1).is_some_and(char::is_ascii_digit)) {
+            index += 1;
+            while index < chars.len() && (chars[index].is_ascii_alphanumeric() || matches!(chars[index], '.' | '_' | '+' | '-')) {
+                index += 1;
+            }
+            Some(NUMBER_STYLE)
+        } else if c.is_alphabetic() || c == '_' {
+            while index < chars.len() && (chars[index].is_alphanumeric() || matches!(chars[index], '_' | '-' | '.')) {
+                index += 1;
+            }
+            let word: String = chars[start..index].iter().collect();
+            if first_token && names_field(&chars[index..]) {
+                Some(KEY_STYLE)
+            } else if matches!(word.as_str(), "true" | "false" | "null" | "None" | "Some") {
+                Some(LITERAL_STYLE)
+            } else {
+                None
+            }
+        } else {
+            index += 1;
+            None
+        };
+        let token: String = chars[start..index].iter().collect();
+        // a YAML list item's "- " doesn't stop what follows from being a key
+        if !(c.is_whitespace() || (c == '-' && style.is_none())) {
+            first_token = false;
+        }
+        match style {
+            Some(style) => {
+                if !plain.is_empty() {
+                    spans.push(Span::raw(std::mem::take(&mut plain)));
+                }
+                spans.push(Span::styled(token, style));
+            }
+            None => plain.push_str(&token),
+        }
+    }
+    if !plain.is_empty() {
+        spans.push(Span::raw(plain));
+    }
+    Line::from(spans)
+}
+
+// Whether a token is a key: followed by : (JSON, YAML, RON) or = (TOML)
+fn names_field(rest: &[char]) -> bool {
+    rest.iter().find(|c| !c.is_whitespace()).is_some_and(|&c| c == ':' || c == '=')
+}
+
+
+// Re-serialize a value in its own format using the configured indentation
+pub fn pretty(bytes: &[u8], format: Format, settings: &PrettyPrint) -> Result<String> {
+    let text = std::str::from_utf8(bytes)?;
+    let pretty = match format {
+        Format::Json => {
+            let mut value: serde_json::Value = serde_json::from_str(text)?;
+            if settings.json_sort_keys {
+                value.sort_all_objects();
+            }
+            let indent = " ".repeat(settings.json_indent);
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            let mut out = Vec::new();
+            value.serialize(&mut serde_json::Serializer::with_formatter(&mut out, formatter))?;
+            String::from_utf8(out)?
+        }
+        Format::Toml => toml::to_string_pretty(&text.parse::<toml::Table>()?)?,
+        Format::Yaml => serde_yaml::to_string(&serde_yaml::from_str::<serde_yaml::Value>(text)?)?,
+        Format::Ron => {
+            let value: ron::Value = ron::from_str(text)?;
+            let config = ron::ser::PrettyConfig::new().indentor(" ".repeat(settings.ron_indent));
+            ron::ser::to_string_pretty(&value, config)?
+        }
+    };
+    Ok(pretty)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shown_lines(text: &str) -> usize {
+        to_lf(text).split('\n').count()
+    }
+
+    #[test]
+    fn line_endings_and_line_counts() {
+        assert_eq!(line_endings("a\nb\nc"), Some(LineEndings::Lf));
+        assert_eq!(shown_lines("a\nb\nc"), 3);
+
+        assert_eq!(line_endings("a\r\nb\r\nc"), Some(LineEndings::Crlf));
+        assert_eq!(shown_lines("a\r\nb\r\nc"), 3);
+        assert!(!to_lf("a\r\nb\r\nc").contains('\r'));
+
+        assert_eq!(line_endings("a\r\nb\nc"), Some(LineEndings::Mixed));
+        assert_eq!(shown_lines("a\r\nb\nc"), 3);
+
+        // a lone \r isn't a line break
+        assert_eq!(line_endings("a\rb"), None);
+        assert_eq!(shown_lines("a\rb"), 1);
+        assert_eq!(to_lf("a\rb"), "a\rb");
+
+        assert_eq!(line_endings("one line"), None);
+        assert_eq!(shown_lines("one line"), 1);
+    }
+
+    #[test]
+    fn empty_values_are_not_tables() {
+        assert!(parse_table(b"", Some(FormatHint::Csv)).is_none());
+        assert!(parse_table(b"", Some(FormatHint::Tsv)).is_none());
+        assert!(parse_table(b"", None).is_none());
+        assert_eq!(parse_table(b"name", Some(FormatHint::Csv)).map(|table| table.rows.len()), Some(1));
+        assert!(parse_table(b"name", None).is_none());
+    }
+}
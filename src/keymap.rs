@@ -0,0 +1,444 @@
+// file src/keymap.rs
+// The keys of the main view: every command is an action with a default key, and keys.toml
+// beside the config can bind keys to actions, e.g. for vim-style movement with fingerprint
+// and line numbers moved out of the way:
+//   unbind = ["h", "l"]
+//   h = "navigate_back"
+//   l = "navigate_into"
+//   "alt-h" = "fingerprint"
+//   "alt-l" = "line_numbers"
+// Bindings there are added to the defaults. A key that already has a default action has to
+// be listed under unbind before it can be bound to another, so nothing is given up by
+// accident. Digits are the count prefix and can't be bound.
+use crate::config::config_dir;
+use anyhow::{bail, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    SwitchPane,   // between the key list and the value pane
+    ScrollUp,     // move the selection, or scroll the value pane
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+    PageUp,
+    PageDown,
+    Top,          // of the value or the list; in the key list, the tree's top level
+    Bottom,
+    NavigateInto, // open the selected tree or branch
+    NavigateBack, // up to the parent branch
+    TreeList,
+    ToggleWrap,
+    Edit,
+    Help,
+    // the commands of the main view
+    Cancel,             // close a search, range, diff or filter, or back to the tree list
+    DiffFile,
+    MarkRange,
+    ValueTypes,
+    ExplainKey,
+    Delete,             // the selected key, or tree in the tree list
+    Copy,               // also confirms an armed delete
+    CopyShellQuoted,
+    CopyPretty,
+    CopyDumpCommand,
+    WatchEvents,
+    Peek,
+    ShowHiddenTrees,
+    CycleEncoding,
+    RebuildIndex,
+    CyclePathBar,
+    TallInfoBar,
+    ExactSizes,
+    CompactKeys,
+    Reload,
+    SortDescending,
+    Compare,
+    NewTreeOrNextMatch, // one key for both, as they never apply at once
+    PreviousMatch,
+    MoreTrees,
+    SetMark,
+    JumpToMark,
+    Marks,
+    HexView,
+    CycleView,
+    TableView,
+    AbsoluteKeys,
+    ConcatBranches,
+    ClearTree,
+    Template,
+    Fingerprint,
+    ExportMatches,
+    ExportTree,
+    Pager,
+    SharedValues,
+    Pin,                // pin the selected value, or unpin
+    GoToKey,
+    Search,             // in the value, the keys or the tree names
+    CycleDelimiter,
+    FlatPaths,
+    Baseline,
+    DiffBaseline,
+    ExportOutline,
+    ExportOutlineJson,
+    EditFields,
+    PrettyPrint,
+    Fold,
+    FoldAll,
+    LineNumbers,
+    HangingIndent,
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::SwitchPane => "switch_pane",
+            Action::ScrollUp => "scroll_up",
+            Action::ScrollDown => "scroll_down",
+            Action::ScrollLeft => "scroll_left",
+            Action::ScrollRight => "scroll_right",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::NavigateInto => "navigate_into",
+            Action::NavigateBack => "navigate_back",
+            Action::TreeList => "tree_list",
+            Action::ToggleWrap => "toggle_wrap",
+            Action::Edit => "edit",
+            Action::Help => "help",
+            Action::Cancel => "cancel",
+            Action::DiffFile => "diff_file",
+            Action::MarkRange => "mark_range",
+            Action::ValueTypes => "value_types",
+            Action::ExplainKey => "explain_key",
+            Action::Delete => "delete",
+            Action::Copy => "copy",
+            Action::CopyShellQuoted => "copy_shell_quoted",
+            Action::CopyPretty => "copy_pretty",
+            Action::CopyDumpCommand => "copy_dump_command",
+            Action::WatchEvents => "watch_events",
+            Action::Peek => "peek",
+            Action::ShowHiddenTrees => "show_hidden_trees",
+            Action::CycleEncoding => "cycle_encoding",
+            Action::RebuildIndex => "rebuild_index",
+            Action::CyclePathBar => "cycle_path_bar",
+            Action::TallInfoBar => "tall_info_bar",
+            Action::ExactSizes => "exact_sizes",
+            Action::CompactKeys => "compact_keys",
+            Action::Reload => "reload",
+            Action::SortDescending => "sort_descending",
+            Action::Compare => "compare",
+            Action::NewTreeOrNextMatch => "new_tree_or_next_match",
+            Action::PreviousMatch => "previous_match",
+            Action::MoreTrees => "more_trees",
+            Action::SetMark => "set_mark",
+            Action::JumpToMark => "jump_to_mark",
+            Action::Marks => "marks",
+            Action::HexView => "hex_view",
+            Action::CycleView => "cycle_view",
+            Action::TableView => "table_view",
+            Action::AbsoluteKeys => "absolute_keys",
+            Action::ConcatBranches => "concat_branches",
+            Action::ClearTree => "clear_tree",
+            Action::Template => "template",
+            Action::Fingerprint => "fingerprint",
+            Action::ExportMatches => "export_matches",
+            Action::ExportTree => "export_tree",
+            Action::Pager => "pager",
+            Action::SharedValues => "shared_values",
+            Action::Pin => "pin",
+            Action::GoToKey => "go_to_key",
+            Action::Search => "search",
+            Action::CycleDelimiter => "cycle_delimiter",
+            Action::FlatPaths => "flat_paths",
+            Action::Baseline => "baseline",
+            Action::DiffBaseline => "diff_baseline",
+            Action::ExportOutline => "export_outline",
+            Action::ExportOutlineJson => "export_outline_json",
+            Action::EditFields => "edit_fields",
+            Action::PrettyPrint => "pretty_print",
+            Action::Fold => "fold",
+            Action::FoldAll => "fold_all",
+            Action::LineNumbers => "line_numbers",
+            Action::HangingIndent => "hanging_indent",
+        }
+    }
+}
+
+const DEFAULT_BINDINGS: &[(&str, Action)] = &[
+    ("q", Action::Quit),
+    ("tab", Action::SwitchPane),
+    ("up", Action::ScrollUp),
+    ("k", Action::ScrollUp),
+    ("down", Action::ScrollDown),
+    ("j", Action::ScrollDown),
+    ("left", Action::ScrollLeft),
+    ("right", Action::ScrollRight),
+    ("pageup", Action::PageUp),
+    ("pagedown", Action::PageDown),
+    ("home", Action::Top),
+    ("end", Action::Bottom),
+    ("enter", Action::NavigateInto),
+    ("backspace", Action::NavigateBack),
+    ("t", Action::TreeList),
+    ("w", Action::ToggleWrap),
+    ("e", Action::Edit),
+    ("?", Action::Help),
+    ("esc", Action::Cancel),
+    ("<", Action::DiffFile),
+    ("V", Action::MarkRange),
+    ("T", Action::ValueTypes),
+    ("K", Action::ExplainKey),
+    ("d", Action::Delete),
+    ("y", Action::Copy),
+    ("S", Action::CopyShellQuoted),
+    ("Y", Action::CopyPretty),
+    ("X", Action::CopyDumpCommand),
+    ("F", Action::WatchEvents),
+    ("space", Action::Peek),
+    ("H", Action::ShowHiddenTrees),
+    ("U", Action::CycleEncoding),
+    ("R", Action::RebuildIndex),
+    ("L", Action::CyclePathBar),
+    ("I", Action::TallInfoBar),
+    ("#", Action::ExactSizes),
+    ("*", Action::CompactKeys),
+    ("r", Action::Reload),
+    ("s", Action::SortDescending),
+    ("=", Action::Compare),
+    ("n", Action::NewTreeOrNextMatch),
+    ("N", Action::PreviousMatch),
+    ("+", Action::MoreTrees),
+    ("m", Action::SetMark),
+    ("'", Action::JumpToMark),
+    ("M", Action::Marks),
+    ("x", Action::HexView),
+    ("c", Action::CycleView),
+    ("|", Action::TableView),
+    ("A", Action::AbsoluteKeys),
+    ("a", Action::ConcatBranches),
+    ("C", Action::ClearTree),
+    ("G", Action::Template),
+    ("h", Action::Fingerprint),
+    ("W", Action::ExportMatches),
+    ("E", Action::ExportTree),
+    ("!", Action::Pager),
+    ("u", Action::SharedValues),
+    ("P", Action::Pin),
+    ("g", Action::GoToKey),
+    ("/", Action::Search),
+    ("D", Action::CycleDelimiter),
+    ("v", Action::FlatPaths),
+    ("b", Action::Baseline),
+    ("B", Action::DiffBaseline),
+    ("o", Action::ExportOutline),
+    ("O", Action::ExportOutlineJson),
+    ("f", Action::EditFields),
+    ("p", Action::PrettyPrint),
+    ("z", Action::Fold),
+    ("Z", Action::FoldAll),
+    ("l", Action::LineNumbers),
+    ("i", Action::HangingIndent),
+];
+
+type Key = (KeyCode, KeyModifiers);
+
+#[derive(Deserialize)]
+struct KeysFile {
+    #[serde(default)]
+    unbind: Vec<String>,
+    #[serde(flatten)]
+    bindings: BTreeMap<String, Action>,
+}
+
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+    pub custom: Vec<(String, &'static str)>, // keys.toml's keys and their action names, for the help screen
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS.iter()
+            .map(|(name, action)| (parse_key(name).expect("default key names are valid"), *action))
+            .collect();
+        Self { bindings, custom: vec![] }
+    }
+}
+
+impl Keymap {
+    // The defaults, with keys.toml on top when there is one
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_dir().map(|dir| dir.join("keys.toml")) else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read key bindings file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid key bindings file {}", path.display()))
+    }
+
+    // The defaults with the bindings of a keys.toml. A key name or action that isn't known,
+    // two names for the same key, or a key that would silently lose its default action is
+    // an error rather than a binding that doesn't do what it says.
+    fn parse(text: &str) -> Result<Self> {
+        let mut keymap = Self::default();
+        let file: KeysFile = toml::from_str(text)?;
+        let mut unbound = vec![];
+        for name in &file.unbind {
+            let key = parse_key(name)?;
+            keymap.bindings.remove(&key);
+            unbound.push((name, key));
+        }
+        let mut seen: HashMap<Key, &str> = HashMap::new();
+        let mut bound = vec![];
+        for (name, action) in &file.bindings {
+            let key = parse_key(name)?;
+            if let Some(other) = seen.insert(key, name) {
+                bail!("{} and {} are the same key", other, name);
+            }
+            if matches!(key.0, KeyCode::Char('0'..='9')) {
+                bail!("{} can't be bound, digits are the count prefix", name);
+            }
+            if let Some(taken) = keymap.bindings.get(&key).filter(|taken| *taken != action) {
+                bail!("{} is {} by default - add it to unbind to give that up, and bind {} to another key if you want to keep it",
+                    name, taken.name(), taken.name());
+            }
+            keymap.bindings.insert(key, *action);
+            bound.push((name.clone(), action.name()));
+        }
+        // keys unbound and not bound again do nothing
+        keymap.custom = unbound.into_iter()
+            .filter(|(_, key)| !seen.contains_key(key))
+            .map(|(name, _)| (name.clone(), "unbound"))
+            .chain(bound)
+            .collect();
+        Ok(keymap)
+    }
+
+    // The action bound to a key press: to the key with its modifiers, or else to the bare
+    // key, so Shift still scrolls faster and a stray modifier doesn't lose the key. Shift is
+    // part of a character itself (G), so it doesn't count for characters.
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        let modifiers = match key.code {
+            KeyCode::Char(_) => key.modifiers - KeyModifiers::SHIFT,
+            _ => key.modifiers,
+        };
+        self.bindings.get(&(key.code, modifiers))
+            .or_else(|| self.bindings.get(&(key.code, KeyModifiers::NONE)))
+            .copied()
+    }
+}
+
+
+// A key name: one character ("h", "G", "?") or a named key (enter, tab, esc, backspace,
+// space, up, down, left, right, pageup, pagedown, home, end, insert, delete, f1 to f12),
+// either after any of ctrl-, alt- and shift-
+fn parse_key(name: &str) -> Result<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let (modifier, prefix) = if lower.starts_with("ctrl-") && rest.len() > 5 {
+            (KeyModifiers::CONTROL, 5)
+        } else if lower.starts_with("alt-") && rest.len() > 4 {
+            (KeyModifiers::ALT, 4)
+        } else if lower.starts_with("shift-") && rest.len() > 6 {
+            (KeyModifiers::SHIFT, 6)
+        } else {
+            break;
+        };
+        modifiers |= modifier;
+        rest = &rest[prefix..];
+    }
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(_), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+            bail!("Write {} as the character itself, e.g. G rather than shift-g", name)
+        }
+        // terminals report ctrl with the lower-case letter
+        (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => KeyCode::Char(c.to_ascii_lowercase()),
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest.to_ascii_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "esc" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "insert" => KeyCode::Insert,
+            "delete" => KeyCode::Delete,
+            function => match function.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                Some(n @ 1..=12) => KeyCode::F(n),
+                _ => bail!("Unknown key {} - use one character or a key name like enter, tab, esc or pageup", name),
+            },
+        },
+    };
+    Ok((code, modifiers))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn keys_with_a_default_action_must_be_unbound_before_they_are_bound() {
+        for key in ["h", "s", "#", "r", "esc", "space", "q", "enter"] {
+            let error = Keymap::parse(&format!("{:?} = \"navigate_back\"", key)).err().expect("taking a default key fails");
+            assert!(error.to_string().contains("unbind"), "{}: {}", key, error);
+        }
+        assert!(Keymap::parse("5 = \"top\"").is_err());
+        assert!(Keymap::parse("\"ctrl-5\" = \"top\"").is_err());
+        // binding a key to the action it already has is harmless
+        assert!(Keymap::parse("q = \"quit\"").is_ok());
+
+        let keymap = Keymap::parse("unbind = [\"h\", \"l\", \"j\"]\nh = \"navigate_back\"\nl = \"navigate_into\"\n\"alt-h\" = \"fingerprint\"\n\"ctrl-d\" = \"page_down\"").unwrap();
+        let action = |code, modifiers| keymap.action(&press(code, modifiers));
+        assert_eq!(action(KeyCode::Char('h'), KeyModifiers::NONE), Some(Action::NavigateBack));
+        assert_eq!(action(KeyCode::Char('l'), KeyModifiers::NONE), Some(Action::NavigateInto));
+        assert_eq!(action(KeyCode::Char('h'), KeyModifiers::ALT), Some(Action::Fingerprint));
+        assert_eq!(action(KeyCode::Char('d'), KeyModifiers::CONTROL), Some(Action::PageDown));
+        assert_eq!(action(KeyCode::Char('d'), KeyModifiers::NONE), Some(Action::Delete));
+        // unbound and not bound again: does nothing
+        assert_eq!(action(KeyCode::Char('j'), KeyModifiers::NONE), None);
+        // the help screen lists j as unbound, and h and l only with their new actions
+        assert_eq!(keymap.custom, [
+            ("j".to_string(), "unbound"),
+            ("alt-h".to_string(), "fingerprint"),
+            ("ctrl-d".to_string(), "page_down"),
+            ("h".to_string(), "navigate_back"),
+            ("l".to_string(), "navigate_into"),
+        ]);
+    }
+
+    #[test]
+    fn every_action_has_a_default_key() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.bindings.len(), DEFAULT_BINDINGS.len(), "no key is bound twice");
+        let toml: String = DEFAULT_BINDINGS.iter().map(|(_, action)| format!("\"alt-{}\" = \"{}\"\n", action.name(), action.name())).collect();
+        assert!(Keymap::parse(&toml).is_err(), "action names aren't key names");
+        for (_, action) in DEFAULT_BINDINGS {
+            let parsed: BTreeMap<String, Action> = toml::from_str(&format!("a = \"{}\"", action.name())).unwrap();
+            assert_eq!(parsed["a"], *action, "name() matches what keys.toml reads");
+        }
+    }
+}
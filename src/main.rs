@@ -1,23 +1,152 @@
 // file src/main.rs
 mod app;
+mod clipboard;
+mod commands;
+mod compare;
+mod config;
+mod db;
+mod disk_index;
+mod editor;
+mod encoding;
 mod tui_app;
+mod events;
 mod example;
+mod format;
+mod keymap;
+mod map_editor;
+mod modified;
+mod owner;
+mod protobuf;
+mod renderer;
+mod task;
+mod template;
 
+use crate::config::Config;
+use crate::example::ExampleShape;
 use crate::tui_app::TuiApp;
 use clap::*;
 use std::path::PathBuf;
 use anyhow::Result;
-use ctrlc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("json_output").args(["inspect", "outline"])))]
 struct Cli {
     /// Create example database with test data
     #[arg(long)]
     make_example_db: bool,
 
+    /// What the example database's values look like
+    #[arg(long, value_enum, default_value_t = ExampleShape::Rich, requires = "make_example_db")]
+    example_shape: ExampleShape,
+
+    /// Shorthand for --example-shape simple
+    #[arg(long, requires = "make_example_db", conflicts_with = "example_shape")]
+    example_simple: bool,
+
+    /// Write the value of --key to stdout and exit
+    #[arg(long, requires = "key", conflicts_with_all = ["set", "make_example_db"])]
+    get: bool,
+
+    /// Store stdin as the value of --key and exit
+    #[arg(long, requires = "key", conflicts_with = "make_example_db")]
+    set: bool,
+
+    /// Print a summary of the value of --key (size, format, UTF-8, preview) and exit
+    #[arg(long, requires = "key", conflicts_with_all = ["get", "set", "make_example_db"])]
+    inspect: bool,
+
+    /// Print the --inspect summary, or the --outline, as JSON
+    #[arg(long, requires = "json_output")]
+    json: bool,
+
+    /// Write the keys and values of --tree as JSON lines and exit
+    #[arg(long, conflicts_with_all = ["get", "set", "inspect", "make_example_db"])]
+    dump: bool,
+
+    /// Dump every tree instead of one
+    #[arg(long, requires = "dump", conflicts_with_all = ["tree", "tree_hex"])]
+    all_trees: bool,
+
+    /// Only dump keys starting with this
+    #[arg(long, requires = "dump")]
+    prefix: Option<String>,
+
+    /// Only dump keys containing this (case-insensitive)
+    #[arg(long, requires = "dump")]
+    search: Option<String>,
+
+    /// Write the key hierarchy of --tree (names only, split on --delimiter or the detected
+    /// one) as an indented outline and exit
+    #[arg(long, conflicts_with_all = ["get", "set", "inspect", "dump", "make_example_db"])]
+    outline: bool,
+
+    /// Create the database at DB_PATH from a --dump file (JSON lines or a JSON array) and exit
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["get", "set", "inspect", "dump", "outline", "make_example_db"])]
+    restore: Option<PathBuf>,
+
+    /// Let --restore write into a path that already holds files
+    #[arg(long, requires = "restore")]
+    force: bool,
+
+    /// Add the records of a --dump file (JSON lines or a JSON array) to the database at
+    /// DB_PATH and exit
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["get", "set", "inspect", "dump", "outline", "restore", "make_example_db"])]
+    import: Option<PathBuf>,
+
+    /// Tree every --import record goes to (default: the tree each record names)
+    #[arg(long, value_name = "NAME", requires = "import")]
+    import_tree: Option<String>,
+
+    /// Check and count the records of --import without writing anything
+    #[arg(long, requires = "import")]
+    dry_run: bool,
+
+    /// Tree for --get / --set / --inspect / --dump (default: the default tree), or to open at startup
+    #[arg(long, value_name = "NAME")]
+    tree: Option<String>,
+
+    /// --tree given as hex, for tree names that aren't UTF-8
+    #[arg(long, value_name = "HEX", conflicts_with = "tree")]
+    tree_hex: Option<String>,
+
+    /// Key for --get / --set / --inspect
+    #[arg(long)]
+    key: Option<String>,
+
+    /// Split every tree's keys on this delimiter (e.g. /) instead of detecting each tree's;
+    /// "" lists whole keys
+    #[arg(long, short = 'd')]
+    delimiter: Option<String>,
+
+    /// Enable debugging actions (K explains how the selected key is resolved)
+    #[arg(long)]
+    debug: bool,
+
+    /// Index the key hierarchy in a temporary on-disk database instead of memory, for
+    /// trees too large to index in RAM
+    #[arg(long)]
+    disk_index: bool,
+
+    /// List at most this many trees at first; + in the tree list loads as many more
+    #[arg(long, value_name = "N")]
+    max_trees: Option<usize>,
+
+    /// Read every key back after it is written or deleted (in the TUI and by --set), and
+    /// report any mismatch
+    #[arg(long)]
+    verify_writes: bool,
+
+    /// Refuse every edit, delete and other write in the TUI, for browsing live data safely
+    #[arg(long, conflicts_with_all = ["set", "restore", "import", "make_example_db"])]
+    read_only: bool,
+
+    /// List trees in sled's order instead of sorting them by name
+    #[arg(long)]
+    no_sort_trees: bool,
+
     /// Path to the Sled database directory
     #[arg(value_name = "DB_PATH")]
     db_path: PathBuf,
@@ -34,10 +163,36 @@ fn main() -> Result<()> {
         r.store(false, Ordering::SeqCst);
     })?;
     
+    let tree = match (&cli.tree, &cli.tree_hex) {
+        (Some(name), _) => Some(name.as_bytes().to_vec()),
+        (None, Some(hex)) => Some(commands::decode_hex(hex)?),
+        (None, None) => None,
+    };
+
     if cli.make_example_db {
-        example::create_example_db(&cli.db_path, running)?;
+        let shape = if cli.example_simple { ExampleShape::Simple } else { cli.example_shape };
+        example::create_example_db(&cli.db_path, running, shape)?;
+    } else if let (true, Some(key)) = (cli.get, &cli.key) {
+        commands::get_value(&cli.db_path, tree.as_deref(), key)?;
+    } else if let (true, Some(key)) = (cli.set, &cli.key) {
+        commands::set_value(&cli.db_path, tree.as_deref(), key, cli.verify_writes)?;
+    } else if let (true, Some(key)) = (cli.inspect, &cli.key) {
+        commands::inspect_value(&cli.db_path, tree.as_deref(), key, cli.json)?;
+    } else if cli.dump {
+        commands::dump_tree(&cli.db_path, tree.as_deref(), cli.all_trees, cli.prefix.as_deref(), cli.search.as_deref())?;
+    } else if cli.outline {
+        commands::outline_tree(&cli.db_path, tree.as_deref(), cli.delimiter.as_deref(), cli.json)?;
+    } else if let Some(dump_path) = &cli.restore {
+        commands::restore(dump_path, &cli.db_path, cli.force, running)?;
+    } else if let Some(dump_path) = &cli.import {
+        commands::import(dump_path, &cli.db_path, cli.import_tree.as_deref().map(str::as_bytes), cli.dry_run, running)?;
     } else {
-        let mut tui = TuiApp::new(cli.db_path)?;
+        let mut config = Config::load()?;
+        config.keymap = keymap::Keymap::load()?;
+        if cli.delimiter.is_some() {
+            config.delimiter = cli.delimiter;
+        }
+        let mut tui = TuiApp::new(cli.db_path, config, cli.debug, cli.disk_index, cli.max_trees, !cli.no_sort_trees, tree, cli.verify_writes, cli.read_only)?;
         tui.run(running)?;
     }
 
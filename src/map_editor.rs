@@ -0,0 +1,124 @@
+// file src/map_editor.rs
+// Editing a value that is a flat map (a JSON object, TOML table or YAML mapping of scalars)
+// field by field, then writing it back in its own format
+use crate::config::PrettyPrint;
+use crate::format::{self, Format};
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+pub struct Field {
+    pub name: String,
+    pub value: String,  // as shown and edited: strings bare, anything else as JSON
+    pub string: bool,   // keep it a string on save, even if it now reads as a number
+}
+
+// Which part of a field is being typed into
+#[derive(Clone, Copy, PartialEq)]
+pub enum Target {
+    Name,
+    Value,
+    NewField, // the name of a field about to be added; its value is typed next
+}
+
+pub struct FieldEdit {
+    pub target: Target,
+    pub buffer: String,
+}
+
+pub struct MapEditor {
+    pub full_key: String,
+    pub original: Vec<u8>,
+    pub format: Format,
+    pub fields: Vec<Field>,
+    pub selected: usize,
+    pub edit: Option<FieldEdit>,
+    dirty: bool, // a field was changed, added or removed
+}
+
+impl MapEditor {
+    pub fn new(full_key: String, original: Vec<u8>) -> Result<Self> {
+        let (format, map) = format::to_json(&original)
+            .filter(|(format, _)| matches!(format, Format::Json | Format::Toml | Format::Yaml))
+            .context("Not a JSON, TOML or YAML map - e edits it as text")?;
+        let Value::Object(map) = map else {
+            bail!("Not a map - e edits it as text");
+        };
+        let fields = map.into_iter()
+            .map(|(name, value)| match value {
+                Value::String(text) => Ok(Field { name, value: text, string: true }),
+                Value::Array(_) | Value::Object(_) => bail!("{} is nested, so this map can only be edited as text (e)", name),
+                value => Ok(Field { name, value: value.to_string(), string: false }),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { full_key, original, format, fields, selected: 0, edit: None, dirty: false })
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn start_edit(&mut self, target: Target) {
+        let buffer = match (target, self.fields.get(self.selected)) {
+            (Target::Name, Some(field)) => field.name.clone(),
+            (Target::Value, Some(field)) => field.value.clone(),
+            (Target::NewField, _) => String::new(),
+            (_, None) => return,
+        };
+        self.edit = Some(FieldEdit { target, buffer });
+    }
+
+    // Apply the typed text to the field. A new field's name moves on to its value.
+    pub fn finish_edit(&mut self) -> Result<()> {
+        let Some(edit) = self.edit.take() else { return Ok(()) };
+        match edit.target {
+            Target::Name | Target::NewField if edit.buffer.is_empty() => bail!("A field needs a name"),
+            Target::Name | Target::NewField if self.fields.iter().enumerate()
+                .any(|(index, field)| field.name == edit.buffer && (edit.target == Target::NewField || index != self.selected)) => {
+                bail!("There is already a field {}", edit.buffer);
+            }
+            Target::Name => self.fields[self.selected].name = edit.buffer,
+            Target::Value => {
+                let field = &mut self.fields[self.selected];
+                // a typed value that isn't valid JSON (a bare word, say) becomes a string
+                field.string = field.string || serde_json::from_str::<Value>(&edit.buffer).is_err();
+                field.value = edit.buffer;
+            }
+            Target::NewField => {
+                self.fields.push(Field { name: edit.buffer, value: String::new(), string: false });
+                self.selected = self.fields.len() - 1;
+                self.edit = Some(FieldEdit { target: Target::Value, buffer: String::new() });
+            }
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn remove_selected(&mut self) {
+        if self.selected < self.fields.len() {
+            self.fields.remove(self.selected);
+            self.selected = self.selected.min(self.fields.len().saturating_sub(1));
+            self.dirty = true;
+        }
+    }
+
+    // The map written back in the value's own format
+    pub fn serialize(&self, settings: &PrettyPrint) -> Result<Vec<u8>> {
+        let map: serde_json::Map<String, Value> = self.fields.iter()
+            .map(|field| {
+                let value = if field.string {
+                    Value::String(field.value.clone())
+                } else {
+                    serde_json::from_str(&field.value)?
+                };
+                Ok((field.name.clone(), value))
+            })
+            .collect::<Result<_>>()?;
+        let map = Value::Object(map);
+        let text = match self.format {
+            Format::Json => format::pretty(serde_json::to_string(&map)?.as_bytes(), Format::Json, settings)?,
+            Format::Toml => toml::to_string(&map).context("TOML can't store this map (null values?)")?,
+            _ => serde_yaml::to_string(&map)?,
+        };
+        Ok(text.into_bytes())
+    }
+}
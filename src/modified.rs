@@ -0,0 +1,93 @@
+// file src/modified.rs
+// "Modified 5m ago" annotations for keys whose values carry their own last-modified
+// timestamp, configured under [modified]. Anything missing or unparseable just leaves the
+// key unannotated.
+use crate::config::{Modified, TimestampFormat};
+use crate::format;
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// When a parsed value was modified, in seconds since the Unix epoch, going by the
+// configured field
+pub fn modified_at(parsed: &Value, config: &Modified) -> Option<i64> {
+    let path = config.field.as_deref()?;
+    timestamp(format::lookup(parsed, path)?, config.format)
+}
+
+// How long before `now` a modified_at time was, e.g. "5m ago"
+pub fn age(seconds: i64, now: SystemTime) -> Option<String> {
+    let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some(describe(now - seconds))
+}
+
+
+// Seconds since the Unix epoch. Unix timestamps may be stored as numbers or strings.
+fn timestamp(field: &Value, format: TimestampFormat) -> Option<i64> {
+    let number = || match field {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse::<f64>().ok(),
+        _ => None,
+    };
+    match format {
+        TimestampFormat::Unix => number().map(|seconds| seconds as i64),
+        TimestampFormat::UnixMs => number().map(|millis| (millis / 1000.0) as i64),
+        TimestampFormat::Rfc3339 => rfc3339(field.as_str()?),
+    }
+}
+
+
+// 2024-05-01T12:30:00Z, 2024-05-01 12:30:00.123+02:00 and the like
+fn rfc3339(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let digits = |range: std::ops::Range<usize>| -> Option<i64> {
+        let part = text.get(range).filter(|part| part.bytes().all(|byte| byte.is_ascii_digit()))?;
+        part.parse().ok()
+    };
+    let (year, month, day) = (digits(0..4)?, digits(5..7)?, digits(8..10)?);
+    let (hour, minute, second) = (digits(11..13)?, digits(14..16)?, digits(17..19)?);
+    if !matches!(text.as_bytes().get(10), Some(b'T' | b't' | b' ')) || !(1..=12).contains(&month) {
+        return None;
+    }
+    // skip fractional seconds, then read the offset
+    let rest = text[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && rest.is_ascii() && rest.as_bytes()[3] == b':' => {
+            let sign = match &rest[..1] {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            sign * (rest[1..3].parse::<i64>().ok()? * 3600 + rest[4..6].parse::<i64>().ok()? * 60)
+        }
+        _ => return None,
+    };
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+// Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+
+// A signed number of seconds as "5m ago" or "in 2h"
+pub fn describe(seconds: i64) -> String {
+    let magnitude = seconds.unsigned_abs();
+    let amount = match magnitude {
+        0..=59 => format!("{}s", magnitude),
+        60..=3599 => format!("{}m", magnitude / 60),
+        3600..=86399 => format!("{}h", magnitude / 3600),
+        _ => format!("{}d", magnitude / 86400),
+    };
+    if seconds < 0 {
+        format!("in {}", amount)
+    } else {
+        format!("{} ago", amount)
+    }
+}
@@ -0,0 +1,31 @@
+// file src/owner.rs
+// Owner / ACL badges for keys whose values record who they belong to, configured under
+// [owner]. Purely informational: a value without the field just gets no badge.
+use crate::config::Owner;
+use crate::format;
+use serde_json::Value;
+
+// Longest badge shown; longer ACLs are cut short
+const MAX_BADGE_CHARS: usize = 24;
+
+// The configured field of a parsed value as a short badge: strings bare, lists joined with
+// commas, anything else as JSON
+pub fn badge(parsed: &Value, config: &Owner) -> Option<String> {
+    let path = config.field.as_deref()?;
+    let text = match format::lookup(parsed, path)? {
+        Value::Null => return None,
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter()
+            .map(|item| item.as_str().map_or_else(|| item.to_string(), str::to_string))
+            .collect::<Vec<_>>()
+            .join(","),
+        other => other.to_string(),
+    };
+    if text.is_empty() {
+        return None;
+    }
+    Some(match text.chars().count() > MAX_BADGE_CHARS {
+        true => format!("{}...", text.chars().take(MAX_BADGE_CHARS).collect::<String>()),
+        false => text,
+    })
+}
@@ -0,0 +1,45 @@
+// file src/protobuf.rs
+// Protobuf decoding for trees mapped to a message type under [protobuf] in the config.
+// The decoder needs the `protobuf` cargo feature; without it Descriptors can't exist.
+use crate::config::PrettyPrint;
+use anyhow::Result;
+use std::path::Path;
+
+#[cfg(feature = "protobuf")]
+pub type Descriptors = prost_reflect::DescriptorPool;
+
+#[cfg(not(feature = "protobuf"))]
+pub type Descriptors = std::convert::Infallible;
+
+
+// Load a FileDescriptorSet, as written by `protoc --include_imports --descriptor_set_out`
+#[cfg(feature = "protobuf")]
+pub fn load(path: &Path) -> Result<Descriptors> {
+    use anyhow::Context;
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Could not read protobuf descriptor set {}", path.display()))?;
+    prost_reflect::DescriptorPool::decode(bytes.as_slice())
+        .with_context(|| format!("Invalid protobuf descriptor set {}", path.display()))
+}
+
+#[cfg(not(feature = "protobuf"))]
+pub fn load(_path: &Path) -> Result<Descriptors> {
+    anyhow::bail!("protobuf.descriptor_set is set, but sledit was built without the protobuf feature")
+}
+
+
+// Decode a value as the named message and pretty-print it as JSON
+#[cfg(feature = "protobuf")]
+pub fn decode(descriptors: &Descriptors, message: &str, bytes: &[u8], settings: &PrettyPrint) -> Result<String> {
+    use anyhow::Context;
+    let descriptor = descriptors.get_message_by_name(message)
+        .with_context(|| format!("Unknown protobuf message type {}", message))?;
+    let decoded = prost_reflect::DynamicMessage::decode(descriptor, bytes)?;
+    let json = serde_json::to_vec(&decoded)?;
+    crate::format::pretty(&json, crate::format::Format::Json, settings)
+}
+
+#[cfg(not(feature = "protobuf"))]
+pub fn decode(descriptors: &Descriptors, _message: &str, _bytes: &[u8], _settings: &PrettyPrint) -> Result<String> {
+    match *descriptors {}
+}
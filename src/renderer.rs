@@ -0,0 +1,171 @@
+// file src/renderer.rs
+// How a value is turned into the text of the value pane. Each view implements ValueRenderer
+// and is registered in Renderers::builtin; the first that detects a value renders it, and
+// plain text is the fallback.
+use crate::config::PrettyPrint;
+use crate::format::{self, Format, FormatHint};
+use std::ops::Range;
+use crate::protobuf::{self, Descriptors};
+
+// What a renderer may need besides the bytes
+pub struct RenderContext<'a> {
+    pub format_hint: Option<FormatHint>,   // the tree's declared format
+    pub protobuf_message: Option<&'a str>, // the tree's protobuf message type
+    pub descriptors: Option<&'a Descriptors>,
+    pub pretty: bool,                      // pretty-printing is switched on
+    pub settings: &'a PrettyPrint,
+}
+
+// A value as shown, with the name of the form it was rendered in ("json", "hex") for the
+// pane title. No label means it is shown as stored. Values holding several records list
+// the lines of each record's body, which the value pane can fold.
+pub struct Rendered {
+    pub label: Option<&'static str>,
+    pub text: String,
+    pub records: Vec<Range<usize>>,
+}
+
+impl Rendered {
+    fn new(label: &'static str, text: String) -> Self {
+        Self { label: Some(label), text, records: vec![] }
+    }
+
+    fn as_stored(value: &[u8]) -> Self {
+        Self { label: None, text: String::from_utf8_lossy(value).to_string(), records: vec![] }
+    }
+}
+
+pub trait ValueRenderer {
+    fn detect(&self, value: &[u8], context: &RenderContext) -> bool;
+    fn render(&self, value: &[u8], context: &RenderContext) -> Rendered;
+}
+
+
+// Protobuf trees are always decoded, and hex-dumped when decoding fails
+struct ProtobufRenderer;
+
+impl ValueRenderer for ProtobufRenderer {
+    fn detect(&self, _value: &[u8], context: &RenderContext) -> bool {
+        context.protobuf_message.is_some()
+    }
+
+    fn render(&self, value: &[u8], context: &RenderContext) -> Rendered {
+        let decoded = context.descriptors.zip(context.protobuf_message)
+            .map(|(descriptors, message)| protobuf::decode(descriptors, message, value, context.settings));
+        match decoded {
+            Some(Ok(text)) => Rendered::new("protobuf", text),
+            _ => Rendered::new("hex", format::hex_dump(value)),
+        }
+    }
+}
+
+
+// A hex tree is unreadable as stored, so it is always dumped
+struct HexRenderer;
+
+impl ValueRenderer for HexRenderer {
+    fn detect(&self, _value: &[u8], context: &RenderContext) -> bool {
+        matches!(context.format_hint, Some(FormatHint::Hex))
+    }
+
+    fn render(&self, value: &[u8], _context: &RenderContext) -> Rendered {
+        Rendered::new("hex", format::hex_dump(value))
+    }
+}
+
+
+// JSON / TOML / YAML / RON, pretty-printed in the tree's declared format or the detected one
+struct StructuredRenderer;
+
+impl ValueRenderer for StructuredRenderer {
+    fn detect(&self, value: &[u8], context: &RenderContext) -> bool {
+        context.pretty && match context.format_hint {
+            Some(FormatHint::Json | FormatHint::Toml | FormatHint::Yaml | FormatHint::Ron) => true,
+            Some(_) => false,
+            None => format::detect(value).is_some(),
+        }
+    }
+
+    fn render(&self, value: &[u8], context: &RenderContext) -> Rendered {
+        match format::render(value, context.format_hint, context.settings) {
+            Some((label, text)) => Rendered::new(label, text),
+            None => Rendered::as_stored(value),
+        }
+    }
+}
+
+
+// Newline-delimited JSON: each line's record pretty-printed under a separator of its own.
+// Lines that aren't JSON are shown as stored.
+struct NdjsonRenderer;
+
+impl ValueRenderer for NdjsonRenderer {
+    fn detect(&self, value: &[u8], context: &RenderContext) -> bool {
+        context.pretty && match context.format_hint {
+            Some(FormatHint::Ndjson) => true,
+            Some(_) => false,
+            None => format::is_ndjson(value),
+        }
+    }
+
+    fn render(&self, value: &[u8], context: &RenderContext) -> Rendered {
+        let text = String::from_utf8_lossy(value);
+        let mut lines: Vec<String> = vec![];
+        let mut records = vec![];
+        for (number, line) in text.lines().filter(|line| !line.trim().is_empty()).enumerate() {
+            let pretty = format::pretty(line.as_bytes(), Format::Json, context.settings).ok();
+            let raw = if pretty.is_none() { " (not JSON)" } else { "" };
+            lines.push(format!("── record {}{} ──", number + 1, raw));
+            let start = lines.len();
+            match pretty {
+                Some(pretty) => lines.extend(pretty.lines().map(String::from)),
+                None => lines.push(line.to_string()),
+            }
+            records.push(start..lines.len());
+        }
+        Rendered { label: Some("ndjson"), text: lines.join("\n"), records }
+    }
+}
+
+
+struct TextRenderer;
+
+impl ValueRenderer for TextRenderer {
+    fn detect(&self, _value: &[u8], _context: &RenderContext) -> bool {
+        true
+    }
+
+    fn render(&self, value: &[u8], _context: &RenderContext) -> Rendered {
+        Rendered::as_stored(value)
+    }
+}
+
+
+pub struct Renderers {
+    renderers: Vec<Box<dyn ValueRenderer>>,
+    fallback: TextRenderer,
+}
+
+impl Renderers {
+    pub fn builtin() -> Self {
+        let mut renderers = Self { renderers: vec![], fallback: TextRenderer };
+        renderers.register(Box::new(ProtobufRenderer));
+        renderers.register(Box::new(HexRenderer));
+        renderers.register(Box::new(NdjsonRenderer));
+        renderers.register(Box::new(StructuredRenderer));
+        renderers
+    }
+
+    // Add a renderer, tried after those already registered
+    pub fn register(&mut self, renderer: Box<dyn ValueRenderer>) {
+        self.renderers.push(renderer);
+    }
+
+    pub fn render(&self, value: &[u8], context: &RenderContext) -> Rendered {
+        let renderer = self.renderers.iter()
+            .map(Box::as_ref)
+            .find(|renderer| renderer.detect(value, context))
+            .unwrap_or(&self.fallback);
+        renderer.render(value, context)
+    }
+}
@@ -0,0 +1,86 @@
+// file src/task.rs
+use anyhow::{bail, Result};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+// Handed to a background worker so it can report progress and notice cancellation
+pub struct Progress {
+    done: Arc<AtomicUsize>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Progress {
+    pub fn inc(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Bail out of the worker if the user cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.cancel.load(Ordering::Relaxed) {
+            bail!("Cancelled");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Progress {
+    // For running a worker's scan directly in a test, never cancelled
+    pub fn unwatched() -> Self {
+        Self { done: Arc::new(AtomicUsize::new(0)), cancel: Arc::new(AtomicBool::new(false)) }
+    }
+}
+
+
+// A long-running scan (over a whole tree, say) on a worker thread, so the UI can keep
+// drawing progress and accept a cancel key while it runs
+pub struct Task<T> {
+    pub label: String,
+    pub total: Option<usize>,
+    done: Arc<AtomicUsize>,
+    cancel: Arc<AtomicBool>,
+    receiver: Receiver<Result<T>>,
+}
+
+impl<T: Send + 'static> Task<T> {
+    pub fn spawn<F>(label: &str, total: Option<usize>, work: F) -> Self
+    where
+        F: FnOnce(&Progress) -> Result<T> + Send + 'static,
+    {
+        let done = Arc::new(AtomicUsize::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let progress = Progress { done: done.clone(), cancel: cancel.clone() };
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(work(&progress));
+        });
+        Self { label: label.to_string(), total, done, cancel, receiver }
+    }
+
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    // The worker's result once it has finished, without blocking
+    pub fn try_finish(&self) -> Option<Result<T>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow::anyhow!("{} stopped unexpectedly", self.label))),
+        }
+    }
+
+    // One-line progress summary for the info bar
+    pub fn status(&self) -> String {
+        match self.total {
+            Some(total) => format!("{}... {}/{} (esc to cancel)", self.label, self.done(), total),
+            None => format!("{}... {} (esc to cancel)", self.label, self.done()),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// file src/template.rs
+// Many similar keys from one key and value template, for seeding test data by hand:
+// item/{n} with {"id": {n}} for n in 1..=100. A lighter cousin of --make-example-db.
+use crate::app::verify_key;
+use crate::task::Progress;
+use anyhow::{bail, Context, Result};
+
+// Most keys one template may create, so a typo in a range can't fill the disk
+const MAX_KEYS: usize = 100_000;
+
+const PLACEHOLDER: &str = "{n}";
+
+const SAMPLE_CHARS: usize = 30;
+
+pub struct Template {
+    pub key: String,
+    pub value: String,
+    pub items: Vec<String>, // what {n} takes, in order
+}
+
+impl Template {
+    pub fn new(key: String, value: String, items: &str) -> Result<Self> {
+        if !key.contains(PLACEHOLDER) {
+            bail!("The key template needs {} in it, or every key would be the same", PLACEHOLDER);
+        }
+        Ok(Self { key, value, items: parse_items(items)? })
+    }
+
+    // The key and value for one item
+    pub fn expand(&self, item: &str) -> (String, String) {
+        (self.key.replace(PLACEHOLDER, item), self.value.replace(PLACEHOLDER, item))
+    }
+
+    // One key and value for the confirmation prompt, shortened to fit on the line
+    pub fn sample(&self, item: &str) -> String {
+        let (key, value) = self.expand(item);
+        let short = |text: String| match text.chars().count() > SAMPLE_CHARS {
+            true => format!("{}...", text.chars().take(SAMPLE_CHARS).collect::<String>()),
+            false => text,
+        };
+        format!("{} = {}", short(key), short(value))
+    }
+
+    // Write every key in one batch, so a cancel part way through leaves the tree untouched.
+    // With verify, every key is read back afterwards.
+    pub fn write(&self, tree: &sled::Tree, verify: bool, progress: &Progress) -> Result<usize> {
+        let mut batch = sled::Batch::default();
+        for item in &self.items {
+            progress.check()?;
+            progress.inc();
+            let (key, value) = self.expand(item);
+            batch.insert(key.as_bytes(), value.as_bytes());
+        }
+        tree.apply_batch(batch)?;
+        tree.flush()?;
+        if verify {
+            for item in &self.items {
+                let (key, value) = self.expand(item);
+                verify_key(tree, key.as_bytes(), Some(value.as_bytes()))?;
+            }
+        }
+        Ok(self.items.len())
+    }
+}
+
+
+// 1..=100 and 1..101 count, keeping any zero padding of the start the way shell brace
+// expansion does (001..=100); anything else is a comma-separated list
+fn parse_items(text: &str) -> Result<Vec<String>> {
+    let text = text.trim();
+    let range = text.split_once("..=").map(|(start, end)| (start, end, true))
+        .or_else(|| text.split_once("..").map(|(start, end)| (start, end, false)));
+    let items: Vec<String> = match range {
+        Some((start, end, inclusive)) => {
+            let (start, end) = (start.trim(), end.trim());
+            let first: i64 = start.parse().with_context(|| format!("{} isn't a number", start))?;
+            let last: i64 = end.parse().with_context(|| format!("{} isn't a number", end))?;
+            let last = match inclusive {
+                true => last,
+                false => last.checked_sub(1).with_context(|| format!("The range {} is empty", text))?,
+            };
+            if last < first {
+                bail!("The range {} is empty", text);
+            }
+            // the span of i64::MIN..=i64::MAX doesn't fit in an i64
+            if last.checked_sub(first).is_none_or(|span| span as u64 >= MAX_KEYS as u64) {
+                bail!("That's more than {} keys", MAX_KEYS);
+            }
+            let width = if start.trim_start_matches('-').len() > 1 && start.trim_start_matches('-').starts_with('0') {
+                start.len()
+            } else {
+                0
+            };
+            (first..=last).map(|n| format!("{:0width$}", n, width = width)).collect()
+        }
+        None => text.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect(),
+    };
+    if items.is_empty() {
+        bail!("Give a range like 1..=100 or a list like a,b,c");
+    }
+    if items.len() > MAX_KEYS {
+        bail!("That's more than {} keys", MAX_KEYS);
+    }
+    Ok(items)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_and_lists() {
+        assert_eq!(parse_items("1..=3").unwrap(), ["1", "2", "3"]);
+        assert_eq!(parse_items("1..3").unwrap(), ["1", "2"]);
+        assert_eq!(parse_items("-2..=0").unwrap(), ["-2", "-1", "0"]);
+        assert_eq!(parse_items("08..=10").unwrap(), ["08", "09", "10"]);
+        assert_eq!(parse_items(" a, b ,,c ").unwrap(), ["a", "b", "c"]);
+        assert_eq!(parse_items("1..=100000").unwrap().len(), MAX_KEYS);
+    }
+
+    #[test]
+    fn bad_ranges_are_errors() {
+        for text in ["3..=1", "1..1", "x..=3", "1..=100001", "", " , "] {
+            assert!(parse_items(text).is_err(), "{} should be refused", text);
+        }
+    }
+
+    #[test]
+    fn ranges_at_the_ends_of_i64_are_errors_not_panics() {
+        let (min, max) = (i64::MIN, i64::MAX);
+        assert!(parse_items(&format!("{}..={}", min, max)).is_err());
+        assert!(parse_items(&format!("0..{}", min)).is_err());
+        assert!(parse_items(&format!("{}..{}", min, min)).is_err());
+        assert!(parse_items(&format!("{}..=-1", min)).is_err());
+        assert_eq!(parse_items(&format!("{}..={}", max - 1, max)).unwrap().len(), 2);
+        assert_eq!(parse_items(&format!("{}..={}", min, min + 1)).unwrap().len(), 2);
+    }
+}
@@ -1,19 +1,76 @@
 // file src/tui_app.rs
 
 use crate::app::*;
+use crate::clipboard::{self, Clipboard, ShellQuoting};
+use crate::compare::ComparePane;
+use crate::config::{Config, EnterOnLeaf, Highlight, StartupTree, TabOnBranch, UiState};
+use crate::commands::DEFAULT_TREE;
+use crate::db;
+use crate::disk_index::DiskIndex;
+use crate::editor::{diff_lines, DiffLine, Editor};
+use crate::encoding;
+use crate::events::EventFeed;
+use crate::format::{self, LineEndings, ValueKind};
+use crate::keymap::Action;
+use crate::map_editor::{MapEditor, Target};
+use crate::modified;
+use crate::owner;
+use crate::protobuf;
+use crate::task::Task;
+use crate::template::Template;
 use anyhow::Result;
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect}, prelude::Stylize, style::{Color, Style}, widgets::{Block, Borders, List, ListItem, ListState, Paragraph}, 
+    layout::{Constraint, Direction, Layout, Margin, Position, Rect}, prelude::Stylize, style::{Color, Style}, text::{Line, Span, Text}, widgets::{Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table}, 
     DefaultTerminal, Frame
 };
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::{Range, RangeInclusive};
+use std::io::Write;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use unicode_width::UnicodeWidthChar;
+
+// Where the TUI draws: the real terminal, or an in-memory buffer for tests
+enum Screen {
+    Terminal(DefaultTerminal),
+    #[cfg(test)]
+    Test(ratatui::Terminal<ratatui::backend::TestBackend>),
+}
+
+impl Screen {
+    fn draw(&mut self, render: impl FnOnce(&mut Frame)) -> std::io::Result<()> {
+        match self {
+            Screen::Terminal(terminal) => terminal.draw(render).map(|_| ()),
+            #[cfg(test)]
+            Screen::Test(terminal) => terminal.draw(render).map(|_| ()),
+        }
+    }
+
+    fn clear(&mut self) -> std::io::Result<()> {
+        match self {
+            Screen::Terminal(terminal) => terminal.clear(),
+            #[cfg(test)]
+            Screen::Test(terminal) => terminal.clear(),
+        }
+    }
+
+    fn is_terminal(&self) -> bool {
+        match self {
+            Screen::Terminal(_) => true,
+            #[cfg(test)]
+            Screen::Test(_) => false,
+        }
+    }
+}
 
 pub struct TuiApp {
-    terminal: DefaultTerminal,
+    terminal: Screen,
     app: App,
+    db_path: PathBuf,
     view_mode: ViewMode,
     list_state: ListState,
     focused_pane: Pane,
@@ -21,11 +78,289 @@ pub struct TuiApp {
     max_scroll: u16,
     page_height: u16,
     wrap_text: bool,
+    hanging_indent: bool,   // indent wrapped continuation lines
+    line_numbers: bool,     // number the value's lines in a gutter
+    pretty_print: bool,
     horizontal_scroll: u16,
     max_horizontal_scroll: u16,
     status_message: Option<String>,
     list_offset: usize,     // Starting index of the current window
     list_height: u16,
+    list_area: Rect,  // where the last draw put the list and value panes, for the mouse
+    value_area: Rect,
+    pending_count: Option<usize>, // vim-style numeric prefix typed before an action
+    config: Config,
+    show_help: bool,
+    info_popup: Option<InfoPopup>, // read-only popup, closed by any key
+    debug: bool,
+    delete_armed: Option<(Instant, String)>, // first d press and the full key it will delete
+    clipboard: Clipboard,
+    events: Option<EventFeed>, // live insert/remove feed panel
+    peek: bool,             // value pane previews the tree or branch under the cursor
+    concat: bool,           // show a branch as the concatenation of the values under it
+    absolute_keys: bool,    // list rows show full keys rather than the segment under the path
+    table_view: bool,       // show CSV/TSV values as a table
+    value_view: ValueView,
+    value_search: Option<ValueSearch>, // / in the value pane: matches marked, n / N step through them
+    marks: BTreeMap<char, Mark>, // vim-style bookmarks, for this session only
+    pending_mark: Option<MarkAction>, // m or ' pressed, waiting for the letter
+    task: Option<Task<TaskOutput>>, // background scan in progress
+    last_refresh: Instant,  // when the view was last re-read for auto-refresh
+    last_activity: Instant, // last key press or paste, for stale_after_secs
+    baseline: Option<(String, Arc<Baseline>)>, // snapshot of a tree (by name) to diff against
+    baseline_changes: Option<BTreeMap<String, Change>>, // full keys that differ from the baseline
+    value_types: Option<ValueTypesPopup>,
+    shared_values: Option<SharedValuesPopup>,
+    prompt: Option<Prompt>,
+    editor: Option<Editor>, // replaces the value pane while a value is being edited
+    map_editor: Option<MapEditor>, // likewise, for editing a flat map field by field
+    max_trees: Option<usize>, // how many more trees + lists, from --max-trees
+    folds: Folds,
+    record_view: Option<RecordView>, // set while the value pane shows a multi-record value
+    pending_leave: Option<Leave>, // asking whether to save unsaved edits first
+    compare: Option<ComparePane>, // read-only second view beside the main one
+    compare_focused: bool,        // keys go to the comparison view
+    rebuild_started: Option<(Instant, Vec<String>)>, // R was pressed, and the path it was pressed at
+    range_anchor: Option<RangeAnchor>, // V started a range of keys here
+    pinned: Option<Pin>, // the value pane stays on this key while the selection moves
+    file_diff: Option<FileDiff>, // < compared the selected value with a file
+    compact_keys: bool, // * lays the keys out in columns across the whole width
+    columns: usize,     // of the key list as last drawn; list_height counts every cell
+    window_height: u16, // list_height when the key window was last read
+    path_bar: PathBar,
+    tall_info_bar: bool, // I gives the info bar a few lines, for long messages and key hints
+    ui_state: UiState,   // # and anything else remembered between sessions
+}
+
+// Where V started a range of keys, and the list it belongs to. Moving to another tree,
+// branch or filter leaves the range behind.
+struct RangeAnchor {
+    position: usize,
+    tree: Option<sled::IVec>,
+    path: Vec<String>,
+    filter: Option<String>,
+}
+
+// A key whose value P keeps in the value pane. The tree is kept too, so the pin holds
+// across trees.
+struct Pin {
+    tree: sled::Tree,
+    full_key: String,
+}
+
+// A value compared with a file on disk by <, shown in the value pane while its key stays
+// selected. Read-only: nothing is written from it.
+struct FileDiff {
+    full_key: String,
+    path: String,
+    lines: Option<Vec<DiffLine>>, // None when either side isn't text
+    summary: String,
+    sizes: (usize, usize), // stored, file
+}
+
+// How V copies a range of values
+#[derive(Clone, Copy)]
+enum RangeCopy {
+    Concatenated, // one after another, a line apart
+    Array,        // a JSON array
+    Ndjson,       // a JSON document per line
+}
+
+impl RangeCopy {
+    fn from_input(input: &str) -> Option<Self> {
+        match input {
+            "c" => Some(RangeCopy::Concatenated),
+            "a" => Some(RangeCopy::Array),
+            "n" => Some(RangeCopy::Ndjson),
+            _ => None,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            RangeCopy::Concatenated => "concatenated",
+            RangeCopy::Array => "as a JSON array",
+            RangeCopy::Ndjson => "as NDJSON",
+        }
+    }
+}
+
+// How much room the path bar at the top takes, stepped through with L for short terminals
+#[derive(Clone, Copy)]
+enum PathBar {
+    Boxed, // three lines, with a border
+    Line,
+    Hidden,
+}
+
+impl PathBar {
+    fn height(self) -> u16 {
+        match self {
+            PathBar::Boxed => 3,
+            PathBar::Line => 1,
+            PathBar::Hidden => 0,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            PathBar::Boxed => PathBar::Line,
+            PathBar::Line => PathBar::Hidden,
+            PathBar::Hidden => PathBar::Boxed,
+        }
+    }
+}
+
+// Results of the scans that run through `task`
+enum TaskOutput {
+    ValueTypes(BTreeMap<ValueKind, Vec<String>>),
+    Search(String, Vec<String>),
+    Baseline(String, Baseline),
+    BaselineDiff(BTreeMap<String, Change>),
+    DiskIndex(DiskIndex),
+    KeyIndex(KeyIndex),
+    Created(usize),
+    Fingerprint(String, Fingerprint), // what it covers, e.g. "tree users"
+    SharedValues(Vec<SharedValue>),
+    Exported(String, usize), // file name, keys written
+}
+
+// A bookmarked location: a tree, and the key or branch selected in it (None for the tree itself)
+#[derive(Clone)]
+struct Mark {
+    tree: Vec<u8>,
+    tree_name: String,
+    full_key: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum MarkAction {
+    Set,
+    Jump,
+}
+
+// Records folded away in a multi-record value, kept while the same value is shown
+#[derive(Default)]
+struct Folds {
+    value_hash: u64,
+    folded: BTreeSet<usize>,
+}
+
+// The records of the value on screen as of the last draw, for z / Z to fold
+struct RecordView {
+    count: usize,
+    top: usize, // the record at the top of the pane
+}
+
+// Something that would throw away unsaved edits, held while asking what to do with them
+#[derive(Clone, Copy)]
+enum Leave {
+    CancelEdit,
+    Quit,
+}
+
+struct InfoPopup {
+    title: String,
+    lines: Vec<String>,
+}
+
+// A one-line text input shown in the info bar
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+enum PromptKind {
+    Search,
+    FindInValue,
+    ClearTree(String), // tree name, typed back to confirm
+    FilterTrees,       // applied as it is typed
+    Compare,           // database path to compare against, empty for this one
+    TemplateKey,       // G: the key template, then its value, then what {n} takes
+    TemplateValue(String),
+    TemplateItems(String, String),
+    GoToKey,
+    DiffFile,
+    ConfirmTemplate(Template), // y writes it
+    CopyRange,                 // c, a or n: how to combine the range's values
+    NewTree,
+    DropTree(Vec<u8>, String, usize), // raw and display name, and key count; y drops it
+}
+
+impl PromptKind {
+    fn label(&self) -> String {
+        match self {
+            PromptKind::Search => "Search keys".to_string(),
+            PromptKind::FindInValue => "Find in value".to_string(),
+            PromptKind::ClearTree(tree) => format!("Remove ALL keys from {}? Type the tree name to confirm", tree),
+            PromptKind::FilterTrees => "Filter trees".to_string(),
+            PromptKind::Compare => "Compare with database (empty for this one)".to_string(),
+            PromptKind::TemplateKey => "Key template, {n} is replaced (1/3)".to_string(),
+            PromptKind::TemplateValue(key) => format!("Value for {}, {{n}} is replaced (2/3)", key),
+            PromptKind::TemplateItems(..) => "{n} takes: a range like 1..=100, or a list like a,b,c (3/3)".to_string(),
+            PromptKind::GoToKey => "Go to key".to_string(),
+            PromptKind::DiffFile => "Compare the value with file".to_string(),
+            PromptKind::CopyRange => "Copy the range as c)oncatenated values, a JSON a)rray or n)djson".to_string(),
+            PromptKind::NewTree => "New tree name".to_string(),
+            PromptKind::DropTree(_, name, keys) => format!("Delete tree {} and its {} keys? Type y to delete it", name, keys),
+            PromptKind::ConfirmTemplate(template) => {
+                let count = template.items.len();
+                let last = match count {
+                    1 => String::new(),
+                    _ => format!(" ... {}", template.sample(&template.items[count - 1])),
+                };
+                format!("Create {} keys, {}{}, overwriting any that exist? Type y to write them", count, template.sample(&template.items[0]), last)
+            }
+        }
+    }
+}
+
+// Breakdown of a tree's values by content type, with one row per type to filter on
+struct ValueTypesPopup {
+    kinds: Vec<(ValueKind, Vec<String>)>,
+    state: ListState,
+}
+
+// u's result: groups of keys holding the same value
+struct SharedValuesPopup {
+    groups: Vec<SharedValue>,
+    state: ListState,
+}
+
+// Rows (including borders) given to the events feed when it is open
+const EVENTS_PANEL_HEIGHT: u16 = 10;
+
+// Largest numeric prefix accepted before an action, so a stray run of digits can't stall the UI
+const MAX_PENDING_COUNT: usize = 9999;
+
+// Narrowest column of keys in compact mode
+const COMPACT_COLUMN_WIDTH: u16 = 28;
+
+// Lines the value pane scrolls for one notch of the mouse wheel
+const MOUSE_SCROLL_LINES: u16 = 3;
+
+// Rows the info bar takes when I makes it taller
+const TALL_INFO_BAR_HEIGHT: u16 = 3;
+
+// Shown under the usual key hint in the taller info bar
+const TALL_INFO_HELP: &str = "? help - / search - e edit - tab switch pane - m/' marks - L path bar - I shorter info bar - q quit";
+
+// Most bytes shown when concatenating a branch's values
+const MAX_CONCAT_BYTES: usize = 4 * 1024 * 1024;
+
+// Widest a table column is drawn, in characters
+const MAX_COLUMN_WIDTH: usize = 40;
+
+// Extra indent, beyond the line's own, given to wrapped continuation lines
+const HANGING_INDENT: usize = 4;
+
+// A search within the shown value. The rows of its matches, counted the way the pane
+// wraps, are worked out on every draw, since they move with the width and the toggles.
+struct ValueSearch {
+    needle: String,
+    current: usize,   // the match n / N last moved to
+    rows: Vec<usize>, // the pane row of each match as of the last draw
+    jump: bool,       // scroll to the first match on the next draw
 }
 
 #[derive(PartialEq)]
@@ -41,42 +376,146 @@ pub enum ViewMode {
 
 
 impl TuiApp {
-    pub fn new(db_path: PathBuf) -> Result<Self> {
-        let mut terminal = ratatui::init();
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(db_path: PathBuf, config: Config, debug: bool, disk_index: bool, max_trees: Option<usize>, sort_trees: bool, start_tree: Option<Vec<u8>>, verify_writes: bool, read_only: bool) -> Result<Self> {
+        let screen = || {
+            let terminal = ratatui::init();
+            // a pasted key arrives whole, rather than as keystrokes that would run as commands
+            crossterm::execute!(std::io::stdout(), EnableBracketedPaste, EnableMouseCapture)?;
+            Ok(Screen::Terminal(terminal))
+        };
+        Self::open(screen, db_path, config, debug, disk_index, max_trees, sort_trees, start_tree, verify_writes, read_only)
+    }
+
+
+    #[allow(clippy::too_many_arguments)]
+    fn open(screen: impl FnOnce() -> Result<Screen>, db_path: PathBuf, config: Config, debug: bool, disk_index: bool, max_trees: Option<usize>, sort_trees: bool, start_tree: Option<Vec<u8>>, verify_writes: bool, read_only: bool) -> Result<Self> {
+        // before the terminal is taken over, so a bad descriptor set reports cleanly
+        let protobuf = config.protobuf.descriptor_set.as_deref().map(protobuf::load).transpose()?;
+        let format_version = db::format_version(&db_path);
+        let existed = db::holds_files(&db_path);
+        let db = db::open(&db_path)?;
+        let db_path = db_path.canonicalize().unwrap_or(db_path);
+        let mut terminal = screen()?;
         terminal.clear()?;
         println!("Opening database....");
         let mut app = App::new();
+        app.max_depth = config.max_depth;
+        app.configure_delimiter(config.detect_delimiter, config.delimiter.as_deref());
+        app.use_disk_index = disk_index;
+        let max_trees = max_trees.map(|max| max.max(1));
+        app.tree_limit = max_trees;
+        app.sort_trees = sort_trees;
+        app.verify_writes = verify_writes;
+        app.read_only = read_only;
+        app.format_hints = config.formats.clone();
+        app.modified = config.modified.clone();
+        app.owner = config.owner.clone();
+        // the labels were checked when the config was loaded
+        app.encodings = config.encodings.iter()
+            .filter_map(|(tree, label)| Some((tree.clone(), encoding::by_label(label).ok()?)))
+            .collect();
+        app.hidden_trees = config.hidden_trees.clone();
+        app.protobuf = protobuf;
+        app.protobuf_messages = config.protobuf.messages.clone();
         terminal.clear()?;
         let mut list_state = ListState::default();
         list_state.select(Some(0));
         
-        app.db = Some(sled::open(db_path)?);
+        app.db = Some(db);
         app.refresh_trees()?;
 
-        Ok(Self {
+        let mut tui = Self {
             terminal,
-            app: app,
+            app,
+            db_path,
             view_mode: ViewMode::Trees,
             list_state,
             focused_pane: Pane::List,
             scroll_state: 0,
             max_scroll: 0,
             page_height: 0, 
+            list_area: Rect::default(),
+            value_area: Rect::default(),
             wrap_text: true,
+            hanging_indent: false,
+            line_numbers: false,
+            pretty_print: config.pretty.on_start,
             horizontal_scroll: 0,
             max_horizontal_scroll: 0,
-            status_message: None,
+            status_message: Some(match (format_version, existed) {
+                (Some(version), _) => format!("sled format {} - compatible with the linked sled {}", version, db::SLED_VERSION),
+                (None, false) => format!("New database - created in sled {} format", db::SLED_VERSION),
+                // files were there already, but no conf sled could have written
+                (None, true) => format!("sled format unknown - no readable version in conf (from before sled 0.29?); opened with sled {}", db::SLED_VERSION),
+            }),
             list_offset: 0,
             list_height: 0,     
-        })
+            pending_count: None,
+            config,
+            show_help: false,
+            info_popup: None,
+            debug,
+            delete_armed: None,
+            clipboard: Clipboard::default(),
+            events: None,
+            peek: false,
+            concat: false,
+            absolute_keys: false,
+            table_view: true,
+            value_view: ValueView::Text,
+            value_search: None,
+            marks: BTreeMap::new(),
+            pending_mark: None,
+            task: None,
+            last_refresh: Instant::now(),
+            last_activity: Instant::now(),
+            baseline: None,
+            baseline_changes: None,
+            value_types: None,
+            shared_values: None,
+            prompt: None,
+            editor: None,
+            map_editor: None,
+            max_trees,
+            folds: Folds::default(),
+            record_view: None,
+            pending_leave: None,
+            compare: None,
+            compare_focused: false,
+            rebuild_started: None,
+            range_anchor: None,
+            pinned: None,
+            file_diff: None,
+            compact_keys: false,
+            columns: 1,
+            window_height: 0,
+            path_bar: PathBar::Boxed,
+            tall_info_bar: false,
+            ui_state: UiState::load(),
+        };
+        // the first draw sizes the list, which the tree's first window of keys is read to fill
+        tui.draw()?;
+        tui.open_startup_tree(start_tree)?;
+        Ok(tui)
     }
 
 
     pub fn run(&mut self, running: Arc<AtomicBool>) -> Result<()> {
         loop {
             self.draw()?;
+            self.follow_list_height()?;
             self.handle_input(running.clone())?;
+            self.poll_task()?;
+            self.start_disk_index();
+            self.auto_refresh()?;
             if !running.load(Ordering::SeqCst) {
+                // a Ctrl-C from outside asks about unsaved edits once; a second one quits
+                if self.unsaved_edits().is_some() && self.pending_leave.is_none() {
+                    running.store(true, Ordering::SeqCst);
+                    self.pending_leave = Some(Leave::Quit);
+                    continue;
+                }
                 break;
             }
         }
@@ -84,17 +523,87 @@ impl TuiApp {
     }
 
     fn draw(&mut self) -> Result<()> {
+        let delete_timeout = Duration::from_millis(self.config.delete_confirm_timeout_ms);
+        if self.delete_armed.as_ref().is_some_and(|(armed_at, _)| armed_at.elapsed() >= delete_timeout) {
+            self.delete_armed = None;
+        }
+        let peek = if self.peek { self.peek_content() } else { None };
+        self.record_view = None;
+        // "5m ago" and owner badges after each row, from the fields [modified] and [owner] name
+        // the fields were read with the key window, only the age moves on
+        let row_notes: Vec<Option<String>> = match self.view_mode {
+            ViewMode::Keys => {
+                let now = SystemTime::now();
+                self.app.row_notes.iter()
+                    .map(|note| {
+                        let age = note.modified_at.and_then(|seconds| modified::age(seconds, now)).map(|age| format!("({})", age));
+                        let owner = note.owner.as_ref().map(|owner| format!("[{}]", owner));
+                        let notes: Vec<String> = age.into_iter().chain(owner).collect();
+                        (!notes.is_empty()).then(|| notes.join(" "))
+                    })
+                    .collect()
+            }
+            ViewMode::Trees => vec![],
+        };
+        let leaving_unsaved = self.pending_leave.and(self.unsaved_edits()).map(str::to_string);
+        let row_changes: Vec<Option<Change>> = match &self.baseline_changes {
+            Some(changes) => (0..self.app.current_key_range.keys.len())
+                .map(|index| self.app.full_key(index).and_then(|key| changes.get(&key).copied()))
+                .collect(),
+            None => vec![],
+        };
+        if let Some(events) = &mut self.events {
+            events.drain();
+        }
+        let events_height = if self.events.is_some() { EVENTS_PANEL_HEIGHT } else { 0 };
+        // 1-based position of the selection among all keys, not just the loaded window
+        let position = (self.config.show_position && self.app.total_keys > 0)
+            .then(|| self.list_offset + self.list_state.selected().unwrap_or(0) + 1);
+        // only level by level navigation shows keys relative to a path
+        let absolute_keys: Option<Vec<KeyEntry>> = (self.absolute_keys
+            && self.app.delimiter.is_some()
+            && self.app.key_filter.is_none()
+            && !self.app.flat_paths)
+            .then(|| {
+                self.app.current_key_range.keys.iter().enumerate().map(|(index, entry)| KeyEntry {
+                    key: self.app.full_key(index).unwrap_or_else(|| entry.key.clone()),
+                    has_children: entry.has_children,
+                    child_count: entry.child_count,
+                }).collect()
+            });
+        // rows of the loaded window inside a V range
+        let range_rows = self.selected_range()
+            .map(|range| range.start().saturating_sub(self.list_offset)..(range.end() + 1).saturating_sub(self.list_offset));
+        let exact_sizes = self.ui_state.exact_sizes;
+        let unsaved_count = self.unsaved_count();
+        // nothing pressed for a while and nothing re-reading the data: say so, for a monitor
+        let idle = self.last_activity.elapsed().as_secs();
+        let stale = (self.config.auto_refresh_secs.is_none()
+            && self.config.stale_after_secs.is_some_and(|secs| idle >= secs))
+            .then(|| format!(" Data may be stale - nothing pressed for {}. Press r to refresh ", modified::describe(idle as i64).trim_end_matches(" ago")));
+        // keys in columns while the list has the focus; the value pane comes back with Tab
+        let compact = self.compact_keys
+            && matches!(self.view_mode, ViewMode::Keys)
+            && matches!(self.focused_pane, Pane::List)
+            && self.editor.is_none()
+            && self.map_editor.is_none();
         self.terminal.draw(|frame| {
             let vertical_chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
-                    Constraint::Length(3),  // Path display
+                    Constraint::Length(self.path_bar.height()),  // Path display
                     Constraint::Min(0),     // Main content
-                    Constraint::Length(1),  // info bar
+                    Constraint::Length(events_height),  // events feed
+                    Constraint::Length(if self.tall_info_bar { TALL_INFO_BAR_HEIGHT } else { 1 }),  // info bar
                 ].as_ref())
                 .split(frame.area());
 
-            self.list_height = vertical_chunks[1].height.saturating_sub(2);
+            let list_width = match self.compare {
+                Some(_) => vertical_chunks[1].width / 2,
+                None => vertical_chunks[1].width,
+            };
+            self.columns = if compact { (list_width.saturating_sub(2) / COMPACT_COLUMN_WIDTH).max(2) as usize } else { 1 };
+            self.list_height = vertical_chunks[1].height.saturating_sub(2) * self.columns as u16;
             self.page_height = vertical_chunks[1].height.saturating_sub(2); // calculate this again, don't just copy list_height as may not be same in future
 
 
@@ -107,32 +616,140 @@ impl TuiApp {
                     } else {
                         "default".to_string()
                     };
-                    format!("Tree: {} | Path: /{}", tree_name, self.app.current_path.join("/"))
+                    let depth_cap = match self.app.max_depth {
+                        Some(depth) if self.app.at_max_depth() => format!(" | depth capped at {}", depth),
+                        _ => String::new(),
+                    };
+                    let depth_cap = if self.app.flat_paths && self.app.delimiter.is_some() {
+                        format!("{} | flat paths", depth_cap)
+                    } else {
+                        depth_cap
+                    };
+                    let depth_cap = match self.app.descending {
+                        true => format!("{} | largest first", depth_cap),
+                        false => depth_cap,
+                    };
+                    let delimiter = match &self.app.delimiter {
+                        Some(delimiter) if self.app.delimiter_detected => format!(" | delimiter {} (detected, D changes)", delimiter),
+                        Some(delimiter) => format!(" | delimiter {}", delimiter),
+                        None => String::new(),
+                    };
+                    let delimiter = match (&self.app.delimiter, &self.app.unsplittable_keys) {
+                        (Some(_), Some((count, _))) => format!("{} | {} keys split badly", delimiter, count),
+                        _ => delimiter,
+                    };
+                    format!("Tree: {} | Path: /{}{}{}", tree_name, self.app.current_path.join("/"), delimiter, depth_cap)
                 }
             };
+            let path_text = match self.app.read_only {
+                true => format!("READ-ONLY | {}", path_text),
+                false => path_text,
+            };
+            let path_text = match self.config.auto_refresh_secs {
+                Some(secs) => format!("{} | auto-refresh {}s, last {}s ago", path_text, secs, self.last_refresh.elapsed().as_secs()),
+                None => path_text,
+            };
+            let path_text = match unsaved_count {
+                0 => path_text,
+                1 => format!("{} | 1 unsaved change", path_text),
+                count => format!("{} | {} unsaved changes", path_text, count),
+            };
             
-            let path_widget = Paragraph::new(path_text)
-                .block(Block::default().borders(Borders::ALL));
+            let path_widget = match self.path_bar {
+                PathBar::Boxed => Paragraph::new(path_text).block(Block::default().borders(Borders::ALL)),
+                PathBar::Line | PathBar::Hidden => Paragraph::new(path_text).reversed(),
+            };
             frame.render_widget(path_widget, vertical_chunks[0]);
 
 
+            // render events feed, newest at the bottom
+            if let Some(events) = &self.events {
+                let visible = events_height.saturating_sub(2) as usize;
+                let lines: Vec<&str> = events.lines.iter().skip(events.lines.len().saturating_sub(visible)).map(String::as_str).collect();
+                let events_widget = Paragraph::new(lines.join("\n"))
+                    .block(Block::default().title(format!(" Events: {} ({}) ", events.tree_name, events.lines.len())).borders(Borders::ALL));
+                frame.render_widget(events_widget, vertical_chunks[2]);
+            }
+
+
             // render info bar
-            if let Some(message) = &self.status_message {
-                frame.render_widget(Paragraph::new(message.to_owned()), vertical_chunks[2]);
+            let info_bar = if let Some(prompt) = &self.prompt {
+                Paragraph::new(format!("{}: {}█", prompt.kind.label(), prompt.input))
+            } else if let Some(key) = &leaving_unsaved {
+                let message = format!("Unsaved edits to {}: s save them, d discard them, esc keep editing", key);
+                Paragraph::new(message).fg(Color::Red)
+            } else if self.editor.as_ref().is_some_and(|editor| editor.confirm_reload) {
+                Paragraph::new("Discard your edits and reload the stored value? y/n").fg(Color::Red)
+            } else if self.editor.as_ref().is_some_and(|editor| editor.confirm_save) {
+                Paragraph::new("Save these changes? y/n").fg(Color::Yellow)
+            } else if let Some(task) = &self.task {
+                Paragraph::new(task.status())
+            } else if let Some(action) = self.pending_mark {
+                let message = match action {
+                    MarkAction::Set => "mark: press a letter to bookmark this location",
+                    MarkAction::Jump => "jump to mark: press its letter",
+                };
+                Paragraph::new(message)
+            } else if let Some(count) = self.pending_count {
+                Paragraph::new(format!("count: {}", count))
+            } else if let Some((armed_at, key)) = &self.delete_armed {
+                let remaining = delete_timeout.saturating_sub(armed_at.elapsed());
+                // a branch's own value goes; the keys under it are separate keys and stay
+                let selected = self.list_state.selected().unwrap_or(0);
+                let children = match self.app.current_key_range.keys.get(selected).is_some_and(|entry| entry.has_children) {
+                    true => " - the keys under it stay",
+                    false => "",
+                };
+                let message = format!("press d or y to delete {} ({:.1}s){}", key, remaining.as_secs_f32(), children);
+                Paragraph::new(message).fg(Color::Red)
+            } else if let Some(message) = &self.status_message {
+                Paragraph::new(message.to_owned())
+            } else if self.compare_focused {
+                let key_help = "comparison (read-only): ↓↑ select - [enter] open - [backspace] up - pgup/pgdn scroll value - = main view - esc close";
+                Paragraph::new(key_help)
             } else {
                 let key_help = match self.focused_pane {
                     // Pane::List =>   "q)uit - [enter] show subkeys - [backspace] show parent key - ↓↑ select key - [tab] select value pane - ←→ resize panes",
-                    Pane::List =>   &format!("list_height {} - list_offset {} - total_keys {} - num trees {}", self.list_height, self.list_offset, self.app.total_keys, self.app.sled_trees.len()),
-                    Pane::Value =>  "↓↑←→ scroll - [shift] x10 - [tab] select key pane - e)dit"
+                    Pane::List =>   format!("{}list_height {} - list_offset {} - total_keys {} - num trees {}",
+                        if absolute_keys.is_some() { "[absolute keys] " } else { "" },
+                        self.list_height, self.list_offset, self.app.total_keys, self.app.sled_trees.len()),
+                    // e has already been pressed: say how to finish rather than offer it again
+                    Pane::Value if self.editor.is_some() || self.map_editor.is_some() => {
+                        "editing - ctrl-s saves - esc cancels, asking first about unsaved changes".to_string()
+                    }
+                    Pane::Value =>  "↓↑←→ scroll - [shift] x10 - [tab] select key pane - e)dit - ? help".to_string()
                 };
-                frame.render_widget(Paragraph::new(key_help), vertical_chunks[2]);
+                // the taller info area has room for the keys used most
+                match self.tall_info_bar {
+                    true => Paragraph::new(format!("{}\n{}", key_help, TALL_INFO_HELP)),
+                    false => Paragraph::new(key_help),
+                }
+            };
+            frame.render_widget(info_bar.wrap(ratatui::widgets::Wrap { trim: false }), vertical_chunks[3]);
 
+            // the comparison view takes the right half
+            let (main_area, compare_area) = if self.compare.is_some() {
+                let halves = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .split(vertical_chunks[1]);
+                (halves[0], Some(halves[1]))
+            } else {
+                (vertical_chunks[1], None)
+            };
+            if let (Some(compare), Some(area)) = (&mut self.compare, compare_area) {
+                draw_compare(frame, area, compare, self.compare_focused, self.pretty_print, &self.config);
             }
 
             let chunks = Layout::default()
                 .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
-                .split(vertical_chunks[1]);
+                .constraints(match compact {
+                    true => [Constraint::Percentage(100), Constraint::Percentage(0)],
+                    false => [Constraint::Percentage(30), Constraint::Percentage(70)],
+                })
+                .split(main_area);
+            self.list_area = chunks[0];
+            self.value_area = if compact { Rect::default() } else { chunks[1] };
 
 
             // render tree or key list
@@ -141,31 +758,284 @@ impl TuiApp {
                     draw_tree_list(
                         frame,
                         chunks[0],
-                        &self.app.sled_trees,
+                        &self.app,
                         &mut self.list_state,
-                        self.app.total_keys
+                        self.list_offset,
+                        &self.config.highlight,
                     );
                 }
                 ViewMode::Keys => {
+                    let filter_label = match (&self.app.key_filter, &self.app.key_search) {
+                        (Some(filter), _) => Some(filter.label.clone()),
+                        (None, Some(needle)) => Some(format!("/{}", needle)),
+                        (None, None) => None,
+                    };
                     draw_key_list(
                         frame,
                         chunks[0],
-                        &self.app.current_key_range.keys,
+                        absolute_keys.as_deref().unwrap_or(&self.app.current_key_range.keys),
                         &mut self.list_state,
                         self.app.total_keys,
-                        self.app.current_tree.as_ref()
+                        position,
+                        self.app.current_tree.as_ref(),
+                        filter_label.as_deref(),
+                        &row_changes,
+                        &row_notes,
+                        range_rows.clone(),
+                        self.columns,
+                        &self.config.highlight,
                     );
                 }
             }
 
 
-            
-            if let Ok(Some(value)) = &self.app.get_value(self.list_state.selected().unwrap_or(0)) {
-                let content = String::from_utf8_lossy(value).to_string();
+            let selected = self.list_state.selected().unwrap_or(0);
+            let concatenated = if self.concat {
+                self.app.concat_branch(selected, MAX_CONCAT_BYTES).ok().flatten()
+            } else {
+                None
+            };
+            let value = match (&self.pinned, concatenated) {
+                (Some(pin), _) => match pin.tree.get(pin.full_key.as_bytes()) {
+                    Ok(Some(value)) => Some((value.to_vec(), format!(" pinned {} (P unpins)", pin.full_key))),
+                    _ => Some((vec![], format!(" pinned {}, which no longer exists (P unpins)", pin.full_key))),
+                },
+                (None, Some(concatenated)) => {
+                    let truncated = if concatenated.truncated { ", truncated" } else { "" };
+                    Some((concatenated.bytes, format!(" concat of {} keys{}", concatenated.keys, truncated)))
+                }
+                (None, None) => self.app.get_value(selected).ok().flatten().map(|value| (value, String::new())),
+            };
+            // whether the value is UTF-8 as stored, for the title, before any decoding
+            let stored_utf8 = value.as_ref().is_some_and(|(bytes, _)| std::str::from_utf8(bytes).is_ok());
+            // text in another encoding is decoded here, so everything below sees UTF-8
+            let shown_key = match &self.pinned {
+                Some(pin) => Some(pin.full_key.clone()),
+                None => self.app.full_key(selected),
+            };
+            let encoding = shown_key.and_then(|full_key| self.app.encoding_of(&full_key));
+            // the raw forms show the bytes as stored, so skip decoding and the table view
+            let raw = self.value_view != ValueView::Text;
+            let value = match (value, encoding) {
+                (Some((bytes, indicator)), Some(encoding)) if !raw => {
+                    Some((encoding::decode(&bytes, encoding).into_bytes(), format!("{} {}", indicator, encoding.name())))
+                }
+                (value, _) => value,
+            };
+
+            if compact {
+                // no value pane; the key list has the whole width
+            } else if let Some(map_editor) = &self.map_editor {
+                let items: Vec<ListItem> = map_editor.fields.iter().enumerate()
+                    .map(|(index, field)| {
+                        let typing = map_editor.edit.as_ref().filter(|_| index == map_editor.selected);
+                        let (name, value) = match typing.map(|edit| (edit.target, edit.buffer.as_str())) {
+                            Some((Target::Name, buffer)) => (format!("{}█", buffer), field.value.clone()),
+                            Some((Target::Value, buffer)) => (field.name.clone(), format!("{}█", buffer)),
+                            _ => (field.name.clone(), field.value.clone()),
+                        };
+                        let value = if field.string && typing.is_none() { format!("{:?}", value) } else { value };
+                        ListItem::new(format!("{} = {}", name, value))
+                    })
+                    .chain(map_editor.edit.as_ref()
+                        .filter(|edit| edit.target == Target::NewField)
+                        .map(|edit| ListItem::new(format!("{}█ = (new field)", edit.buffer))))
+                    .collect();
+                let dirty = if map_editor.is_dirty() { " *" } else { "" };
+                let mut state = ListState::default();
+                state.select(Some(map_editor.selected));
+                let fields_widget = List::new(items)
+                    .block(Block::default()
+                        .title(format!("Fields: {} [{}]{} [enter edit - r rename - n new - d delete - ctrl-s save - esc cancel]",
+                            map_editor.full_key, map_editor.format.name(), dirty))
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(Color::Yellow)))
+                    .highlight_style(self.config.highlight.style())
+                    .highlight_symbol(self.config.highlight.symbol.as_str());
+                frame.render_stateful_widget(fields_widget, chunks[1], &mut state);
+            } else if let Some(editor) = self.editor.as_ref().filter(|editor| editor.show_diff || editor.confirm_save) {
+                let diff = editor.diff();
+                let added = diff.iter().filter(|line| matches!(line, DiffLine::Added(_))).count();
+                let removed = diff.iter().filter(|line| matches!(line, DiffLine::Removed(_))).count();
+                let diff_widget = Paragraph::new(diff_text(&diff))
+                    .block(Block::default()
+                        .title(format!("Changes to {}: +{} -{} lines, {} -> {} [ctrl-d back to editing]",
+                            editor.full_key, added, removed, format::size(editor.original.len(), exact_sizes), format::size(editor.buffer.len(), exact_sizes)))
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(Color::Yellow)));
+                frame.render_widget(diff_widget, chunks[1]);
+            } else if let Some(file_diff) = self.file_diff.as_ref()
+                .filter(|diff| self.editor.is_none() && self.app.full_key(selected).as_ref() == Some(&diff.full_key)) {
+                let text = match &file_diff.lines {
+                    Some(lines) => diff_text(lines),
+                    None => vec![
+                        Line::from(format!("stored: {}", format::size(file_diff.sizes.0, exact_sizes))),
+                        Line::from(format!("file:   {}", format::size(file_diff.sizes.1, exact_sizes))),
+                    ],
+                };
+                let diff_widget = Paragraph::new(text)
+                    .block(Block::default()
+                        .title(format!("{} against {}: {} [esc closes - sledit --set writes the file]",
+                            file_diff.full_key, file_diff.path, file_diff.summary))
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(Color::Yellow)));
+                frame.render_widget(diff_widget, chunks[1]);
+            } else if let Some(editor) = &mut self.editor {
+                let height = chunks[1].height.saturating_sub(2) as usize;
+                editor.follow_cursor(height, chunks[1].width.saturating_sub(2) as usize);
+                let (row, column) = editor.cursor_position();
+                // only the lines in view are laid out; tabs show as a space so columns line up
+                let lines: Vec<Line> = editor.buffer.split('\n').enumerate().skip(editor.top).take(height)
+                    .map(|(index, line)| {
+                        let line = line.replace('\t', " ");
+                        if index != row {
+                            return Line::from(line);
+                        }
+                        let before: String = line.chars().take(column).collect();
+                        let at = line.chars().nth(column).unwrap_or(' ').to_string();
+                        let after: String = line.chars().skip(column + 1).collect();
+                        Line::from(vec![before.into(), at.reversed(), after.into()])
+                    })
+                    .collect();
+                let total_lines = editor.buffer.matches('\n').count() + 1;
+                let dirty = if editor.is_dirty() { " *" } else { "" };
+                let editor_widget = Paragraph::new(lines)
+                    .block(Block::default()
+                        .title(format!("Edit: {}{} {}/{}:{} [ctrl-s save - ctrl-d diff - ctrl-r reload - esc cancel]",
+                            editor.full_key, dirty, row + 1, total_lines, column + 1))
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(Color::Yellow)))
+                    .scroll((0, editor.left as u16));
+                frame.render_widget(editor_widget, chunks[1]);
+            } else if let Some((title, content)) = peek {
+                let peek_widget = Paragraph::new(content)
+                    .block(Block::default().title(title).borders(Borders::ALL));
+                frame.render_widget(peek_widget, chunks[1]);
+            } else if let Some(table) = value.as_ref()
+                .filter(|_| self.table_view && !raw && self.app.protobuf_message.is_none())
+                .and_then(|(value, _)| format::parse_table(value, self.app.format_hint)) {
+                // scroll_state counts body rows, horizontal_scroll counts columns
+                let body_height = chunks[1].height.saturating_sub(3) as usize; // borders and header
+                let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+                self.max_scroll = table.rows.len().saturating_sub(1).saturating_sub(body_height) as u16;
+                self.scroll_state = self.scroll_state.min(self.max_scroll);
+                self.max_horizontal_scroll = columns.saturating_sub(1) as u16;
+                self.horizontal_scroll = self.horizontal_scroll.min(self.max_horizontal_scroll);
+                let first_column = self.horizontal_scroll as usize;
+                let body = table.rows.get(1..).unwrap_or_default();
+                let visible = &body[(self.scroll_state as usize).min(body.len())..];
+                let visible = &visible[..body_height.min(visible.len())];
+                // size columns to what is on screen rather than the whole table
+                let widths: Vec<Constraint> = (first_column..columns)
+                    .map(|column| {
+                        let widest = std::iter::once(&table.rows[0]).chain(visible)
+                            .filter_map(|row| row.get(column))
+                            .map(|field| field.chars().count())
+                            .max()
+                            .unwrap_or(0);
+                        Constraint::Length(widest.clamp(1, MAX_COLUMN_WIDTH) as u16)
+                    })
+                    .collect();
+                fn row(fields: &[String], first_column: usize) -> Row<'_> {
+                    Row::new(fields.iter().skip(first_column).map(|field| Cell::from(field.as_str())))
+                }
+                let truncated = if table.truncated { " (first rows only)" } else { "" };
+                let table_widget = Table::new(visible.iter().map(|fields| row(fields, first_column)), widths)
+                    .header(row(&table.rows[0], first_column).bold())
+                    .block(Block::default()
+                        .title(format!("Value [table {}x{}{}] [{}/{}] <col {}> (| for raw)", body.len(), columns, truncated,
+                            self.scroll_state + 1, self.max_scroll + 1, first_column + 1))
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(
+                            if matches!(self.focused_pane, Pane::Value) { Color::Blue } else { Color::White }
+                        )));
+                frame.render_widget(table_widget, chunks[1]);
+            } else if let Some((value, concat_indicator)) = &value {
+                let rendered = self.app.view_value(value, self.value_view, self.pretty_print, &self.config.pretty);
+                // size, UTF-8 or not and line count of the value itself, whatever the pane shows
+                let line_count = match value.is_empty() {
+                    true => 0,
+                    false => value.split(|byte| *byte == b'\n').count() - value.ends_with(b"\n") as usize,
+                };
+                let metadata = format!("{} · {} · {} line{}",
+                    format::size(value.len(), exact_sizes),
+                    if stored_utf8 { "UTF-8" } else { "not UTF-8" },
+                    line_count,
+                    if line_count == 1 { "" } else { "s" });
+                let format_indicator = match rendered.label {
+                    Some(label) => format!("{} {} · {}", concat_indicator, metadata, label),
+                    None => format!("{} {}", concat_indicator, metadata),
+                };
+                // the selected key's badge was read with the key window; a pinned or
+                // concatenated value is parsed here
+                let owner = match (&self.pinned, self.concat) {
+                    (None, false) => self.app.row_notes.get(selected).and_then(|note| note.owner.clone()),
+                    _ if self.config.owner.field.is_some() => {
+                        format::to_json(value).and_then(|(_, parsed)| owner::badge(&parsed, &self.config.owner))
+                    }
+                    _ => None,
+                };
+                let format_indicator = match owner {
+                    Some(owner) => format!("{} [owner {}]", format_indicator, owner),
+                    None => format_indicator,
+                };
+                // the line number gutter is as wide as the last line's number, plus a space
+                let gutter = match self.line_numbers {
+                    true => rendered.text.split('\n').count().to_string().len() as u16 + 1,
+                    false => 0,
+                };
+                let visible_width = chunks[1].width.saturating_sub(2 + gutter);
+                let content = if rendered.records.is_empty() {
+                    rendered.text
+                } else {
+                    let mut hasher = DefaultHasher::new();
+                    value.hash(&mut hasher);
+                    let value_hash = hasher.finish();
+                    if self.folds.value_hash != value_hash {
+                        self.folds = Folds { value_hash, folded: BTreeSet::new() };
+                    }
+                    let (content, owners) = fold_records(&rendered.text, &rendered.records, &self.folds.folded);
+                    // the record whose lines are at the top of the pane, counting wrapped rows
+                    let mut rows = 0;
+                    let top = content.split('\n').zip(&owners)
+                        .find(|(line, _)| {
+                            rows += if self.wrap_text { calculate_wrapped_lines(line, visible_width) } else { 1 };
+                            rows > self.scroll_state as usize
+                        })
+                        .map_or(0, |(_, owner)| *owner);
+                    self.record_view = Some(RecordView { count: rendered.records.len(), top });
+                    content
+                };
+                let format_indicator = if rendered.records.is_empty() {
+                    format_indicator
+                } else {
+                    format!("{} {} records, {} folded (z / Z fold)", format_indicator, rendered.records.len(), self.folds.folded.len())
+                };
+                // a stray \r at the end of each line garbles the terminal, so CRLF is shown as
+                // plain lines and flagged in the title instead
+                let format_indicator = match format::line_endings(&content) {
+                    Some(endings) if endings != LineEndings::Lf => format!("{} {}", format_indicator, endings.name()),
+                    _ => format_indicator,
+                };
+                let content = format::to_lf(&content);
+                // hanging indent wraps the text itself, so the paragraph no longer needs to
+                let hang = self.wrap_text && self.hanging_indent;
+                // one number per line of the value, with a blank gutter beside its wrapped rows
+                let numbers = self.line_numbers.then(|| content.split('\n').enumerate()
+                    .map(|(index, line)| {
+                        let rows = match (self.wrap_text, hang) {
+                            (false, _) => 1,
+                            (true, true) => hanging_indent_wrap(line, visible_width).split('\n').count(),
+                            (true, false) => calculate_wrapped_lines(line, visible_width),
+                        };
+                        format!("{:>width$}{}", index + 1, "\n".repeat(rows - 1), width = gutter as usize - 1)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"));
+                let content = if hang { hanging_indent_wrap(&content, visible_width) } else { content };
                 let lines: Vec<&str> = content.split('\n').collect();
-                let visible_width = chunks[1].width.saturating_sub(2);
 
-                let total_lines = if self.wrap_text {
+                let total_lines = if self.wrap_text && !hang {
                     calculate_wrapped_lines(&content, visible_width)
                 } else {
                     content.split('\n').count()
@@ -173,6 +1043,17 @@ impl TuiApp {
 
                 // Calculate max scroll based on total wrapped lines
                 self.max_scroll = total_lines.saturating_sub(self.page_height as usize) as u16;
+                if let Some(search) = &mut self.value_search {
+                    let wrapped = self.wrap_text && !hang;
+                    search.rows = match_rows(&lines, &search.needle, wrapped.then_some(visible_width));
+                    search.current = search.current.min(search.rows.len().saturating_sub(1));
+                    if std::mem::take(&mut search.jump) {
+                        match search.rows.first() {
+                            Some(row) => self.scroll_state = (*row).min(u16::MAX as usize) as u16,
+                            None => self.status_message = Some(format!("No match for {} in the value", search.needle)),
+                        }
+                    }
+                }
                 self.scroll_state = self.scroll_state.min(self.max_scroll);
 
                 self.max_horizontal_scroll = if !self.wrap_text {
@@ -186,7 +1067,15 @@ impl TuiApp {
                 };
                 self.horizontal_scroll = self.horizontal_scroll.min(self.max_horizontal_scroll);
 
-                let wrap_indicator = if self.wrap_text { "W" } else { "NW" };
+                let wrap_indicator = match (self.wrap_text, hang) {
+                    (true, true) => "WI",
+                    (true, false) => "W",
+                    (false, _) => "NW",
+                };
+                let wrap_indicator = match self.value_view.name() {
+                    Some(name) => format!("{} {}", wrap_indicator, name),
+                    None => wrap_indicator.to_string(),
+                };
                 let scroll_indicator = if self.max_scroll > 0 {
                     format!(" [{}/{}]", self.scroll_state + 1, self.max_scroll + 1)
                 } else {
@@ -197,13 +1086,28 @@ impl TuiApp {
                 } else {
                     String::new()
                 };                    
+                let search_indicator = match &self.value_search {
+                    Some(search) if search.rows.is_empty() => format!(" /{} no match", search.needle),
+                    Some(search) => format!(" /{} {}/{} (n / N)", search.needle, search.current + 1, search.rows.len()),
+                    None => String::new(),
+                };
 
-                let value_widget = Paragraph::new(content)
-                .block(Block::default()
-                    .title(format!("Value [{}]{}{}", 
+                // pretty-printed structured values are coloured; anything shown as stored isn't
+                let text = match rendered.label {
+                    Some("json" | "toml" | "yaml" | "ron" | "ndjson" | "protobuf") => Text::from(format::highlight(&content)),
+                    _ => Text::from(content),
+                };
+                let text = match &self.value_search {
+                    Some(search) => mark_matches(text, &search.needle, search.current),
+                    None => text,
+                };
+                let block = Block::default()
+                    .title(format!("Value [{}]{}{}{}{}", 
                         wrap_indicator, 
+                        format_indicator,
                         scroll_indicator,
-                        h_scroll_indicator
+                        h_scroll_indicator,
+                        search_indicator
                     ))
                     .borders(Borders::ALL)
                     .border_style(Style::default().fg(
@@ -212,18 +1116,95 @@ impl TuiApp {
                         } else {
                             Color::White
                         }
-                    )));
+                    ));
+                let value_widget = Paragraph::new(text);
             
-                let value_widget = if self.wrap_text {
+                let value_widget = if self.wrap_text && !hang {
                     value_widget.wrap(ratatui::widgets::Wrap { trim: false })
                 } else {
                     value_widget
                 };
                 
-                let value_widget = value_widget.scroll((self.scroll_state as u16, self.horizontal_scroll));
+                let value_widget = value_widget.scroll((self.scroll_state, self.horizontal_scroll));
             
+                match numbers {
+                    // the gutter scrolls with the text but stays put when it scrolls sideways
+                    Some(numbers) => {
+                        let inner = Layout::default()
+                            .direction(Direction::Horizontal)
+                            .constraints([Constraint::Length(gutter), Constraint::Min(0)])
+                            .split(block.inner(chunks[1]));
+                        frame.render_widget(block, chunks[1]);
+                        frame.render_widget(Paragraph::new(numbers).dark_gray().scroll((self.scroll_state, 0)), inner[0]);
+                        frame.render_widget(value_widget, inner[1]);
+                    }
+                    None => frame.render_widget(value_widget.block(block), chunks[1]),
+                }
+            }
+
+            if let Some(popup) = &mut self.value_types {
+                let mut items = vec![ListItem::new(format!("all keys: {}", popup.kinds.iter().map(|(_, keys)| keys.len()).sum::<usize>()))];
+                items.extend(popup.kinds.iter().map(|(kind, keys)| ListItem::new(format!("{}: {}", kind.name(), keys.len()))));
+                let area = centered_rect(40, 50, frame.area());
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(
+                    List::new(items)
+                        .block(Block::default().title(" Value types - [enter] filter - esc close ").borders(Borders::ALL))
+                        .highlight_style(self.config.highlight.style())
+                        .highlight_symbol(self.config.highlight.symbol.as_str()),
+                    area,
+                    &mut popup.state,
+                );
+            }
+
+            if let Some(popup) = &mut self.shared_values {
+                let items: Vec<ListItem> = popup.groups.iter()
+                    .map(|group| ListItem::new(format!("{} keys, {}: {}", group.keys.len(), format::size(group.size, exact_sizes), group.preview)))
+                    .collect();
+                let area = centered_rect(70, 60, frame.area());
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(
+                    List::new(items)
+                        .block(Block::default().title(format!(" Shared values: {} - [enter] list the keys - esc close ", popup.groups.len())).borders(Borders::ALL))
+                        .highlight_style(self.config.highlight.style())
+                        .highlight_symbol(self.config.highlight.symbol.as_str()),
+                    area,
+                    &mut popup.state,
+                );
+            }
+
+            if let Some(popup) = &self.info_popup {
+                let area = centered_rect(70, 50, frame.area());
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(popup.lines.join("\n"))
+                        .block(Block::default().title(format!(" {} - any key to close ", popup.title)).borders(Borders::ALL)),
+                    area,
+                );
+            }
+
+            if let Some(banner) = &stale {
+                let main = vertical_chunks[1];
+                let width = (banner.chars().count() as u16 + 2).min(main.width);
+                let area = Rect::new(main.x + (main.width - width) / 2, main.y + main.height.saturating_sub(3) / 2, width, 3.min(main.height));
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(banner.as_str())
+                        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow)))
+                        .fg(Color::Yellow),
+                    area,
+                );
+            }
 
-                frame.render_widget(value_widget, chunks[1]);
+            if self.show_help {
+                let lines = help_lines(&self.config);
+                let area = centered_rect(60, 80, frame.area());
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(lines.join("\n"))
+                        .block(Block::default().title(" Help - any key to close ").borders(Borders::ALL)),
+                    area,
+                );
             }
 
         })?;
@@ -236,237 +1217,2442 @@ impl TuiApp {
 
     fn handle_input(&mut self, running: Arc<AtomicBool>) -> Result<()> {
         if event::poll(std::time::Duration::from_millis(100))? {
-            match event::read()? {
-                Event::FocusGained => {},
-                Event::FocusLost => {},
-                Event::Mouse(_) => {},
-                Event::Resize(_,_) => {},                    
-                Event::Paste(_) => {},
-                Event::Key(key) => {
-                    self.status_message = None;
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            running.store(false, Ordering::SeqCst);
-                        },
-                        KeyCode::Tab => {
-                            self.focused_pane = match self.focused_pane {
-                                Pane::List => Pane::Value,
-                                Pane::Value => Pane::List,
-                            };
-                            self.scroll_state = 0; // Reset scroll when switching panes
-                        },
-                        KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right | KeyCode::PageUp | KeyCode::PageDown | KeyCode::Home | KeyCode::End => {
-                            if matches!(self.focused_pane, Pane::Value) {
-                                let shift_pressed = key.modifiers.contains(event::KeyModifiers::SHIFT);
-                                let movement = if shift_pressed { 10 } else { 1 };
-                                
-                                match key.code {
-                                    KeyCode::Up => {
-                                        self.scroll_state = self.scroll_state.saturating_sub(movement);
-                                    }
-                                    KeyCode::Down => {
-                                        self.scroll_state = (self.scroll_state + movement).min(self.max_scroll);
-                                    }
-                                    KeyCode::Left if !self.wrap_text => {
-                                        self.horizontal_scroll = self.horizontal_scroll.saturating_sub(movement);
-                                    }
-                                    KeyCode::Right if !self.wrap_text => {
-                                        self.horizontal_scroll = (self.horizontal_scroll + movement)
-                                            .min(self.max_horizontal_scroll);
-                                    }
-                                    KeyCode::PageUp => {
-                                        if matches!(self.focused_pane, Pane::Value) {
-                                            self.scroll_state = self.scroll_state.saturating_sub(self.page_height.saturating_sub(1));
-                                        }
-                                    },
-                                    KeyCode::PageDown => {
-                                        if matches!(self.focused_pane, Pane::Value) {
-                                            self.scroll_state = (self.scroll_state + self.page_height.saturating_sub(1)).min(self.max_scroll);
-                                        }
-                                    },
-                                    KeyCode::Home => {
-                                        if matches!(self.focused_pane, Pane::Value) {
-                                            self.scroll_state = 0;
-                                            self.horizontal_scroll = 0;
-                                        }
-                                    },
-                                    KeyCode::End => {
-                                        if matches!(self.focused_pane, Pane::Value) {
-                                            self.scroll_state = self.max_scroll;
-                                        }
-                                    },     
-        
-                                    _ => {}
-                                }
-                            } else {
-                                self.handle_list_navigation(key.code)?;
-                            }
-                        }
-                        KeyCode::Enter => {
-                            if matches!(self.focused_pane, Pane::List) {
-                                let index = self.list_state.selected().unwrap_or(0);
-                                match self.view_mode {
-                                    ViewMode::Trees => {
-                                        self.view_mode = ViewMode::Keys;
-                                        self.app.select_tree(index)?;
-                                        self.app.set_key_range(0, self.list_height as usize)?;
-                                    }
-                                    ViewMode::Keys => {
-                                        if self.app.delimiter.is_some() {
-                                            if self.app.current_key_range.keys[index].has_children {
-                                                self.app.select_key(index)?;
+            self.handle_event(event::read()?, running)?;
+        }
+        Ok(())
+    }
 
-                                            }
-                                        }
-                                    }
-                                }
+
+    // One key press, mouse action or paste
+    fn handle_event(&mut self, event: Event, running: Arc<AtomicBool>) -> Result<()> {
+        match event {
+            Event::FocusGained => {},
+            Event::FocusLost => {},
+            Event::Mouse(mouse) => {
+                self.last_activity = Instant::now();
+                self.handle_mouse(mouse)?;
+            }
+            Event::Resize(_,_) => {},                    
+            Event::Paste(text) => {
+                self.last_activity = Instant::now();
+                self.handle_paste(&text)?;
+            }
+            Event::Key(key) => {
+                self.status_message = None;
+                self.last_activity = Instant::now();
+
+                if self.show_help || self.info_popup.is_some() {
+                    self.show_help = false;
+                    self.info_popup = None;
+                    return Ok(());
+                }
+
+                if let Some(leave) = self.pending_leave.take() {
+                    match key.code {
+                        KeyCode::Char('s') => {
+                            self.save_edits();
+                            // a failed save keeps the edit open, with the reason shown
+                            if self.unsaved_edits().is_none() {
+                                self.finish_leave(leave, &running);
                             }
                         }
-                        KeyCode::Backspace => {
-                            self.focused_pane = Pane::List;
-                            if self.app.current_path.len() > 1 {
-                                self.app.go_back_in_path()?;
-                            } else { // go back to tree mode, assume at least Default tree available
-                                self.view_mode = ViewMode::Trees;
-                                self.list_offset = 0;
-                                self.app.total_keys = 0;
-                                self.app.current_tree = None;
-                            }
-                            self.list_state.select(Some(0));
-                        },
-                        KeyCode::Char('w') => {
-                            if matches!(self.focused_pane, Pane::Value) {
-                                self.wrap_text = !self.wrap_text;
-                                self.horizontal_scroll = 0;
-                            }
-                        },
+                        KeyCode::Char('d') => self.finish_leave(leave, &running),
+                        _ => {}
+                    }
+                    return Ok(());
+                }
 
+                // only cancel and quit while a background scan runs
+                if let Some(task) = &self.task {
+                    match key.code {
+                        KeyCode::Esc => task.cancel(),
+                        _ if self.config.keymap.action(&key) == Some(Action::Quit) => self.leave(Leave::Quit, &running),
                         _ => {}
                     }
+                    return Ok(());
                 }
-            }
-        }
-        Ok(())
-    }
 
+                if self.value_types.is_some() {
+                    return self.handle_value_types_input(key.code);
+                }
 
-    fn handle_list_navigation(&mut self, key: KeyCode) -> Result<()> {
-        let element_count = match self.view_mode {
-            ViewMode::Trees => self.app.sled_trees.len(),
-            ViewMode::Keys => self.app.total_keys,
-        };
+                if self.shared_values.is_some() {
+                    return self.handle_shared_values_input(key.code);
+                }
 
-        if matches!(self.view_mode, ViewMode::Keys) && element_count == 0 {
-            return Ok(());
-        }
+                if self.prompt.is_some() {
+                    return self.handle_prompt_input(key.code);
+                }
 
-        let relative_selection = self.list_state.selected().unwrap_or(0);  // Relative to visible items
-        let absolute_selection = self.list_offset + relative_selection;  // Actual position in full dataset
+                if self.editor.is_some() {
+                    return self.handle_editor_input(key, &running);
+                }
 
-        match key {
-            KeyCode::Up => {
-                if absolute_selection > 0 {
-                    if relative_selection > 0 {
-                        // Just move the selection up
-                        self.list_state.select(Some(relative_selection - 1));
-                    } else {
-                        // At top of window, need to shift window up
-                        self.list_offset = self.list_offset.saturating_sub(1);
-                        if matches!(self.view_mode, ViewMode::Keys) {
-                            self.update_list()?;
+                if self.map_editor.is_some() {
+                    return self.handle_map_editor_input(key, &running);
+                }
+
+                if self.compare_focused {
+                    if let Some(compare) = &mut self.compare {
+                        match key.code {
+                            KeyCode::Char('=') => self.compare_focused = false,
+                            _ if self.config.keymap.action(&key) == Some(Action::Quit) => self.leave(Leave::Quit, &running),
+                            _ if self.config.keymap.action(&key) == Some(Action::Help) => self.show_help = true,
+                            code => {
+                                compare.set_list_height(self.list_height as usize / self.columns)?;
+                                if !compare.handle_key(code)? {
+                                    self.compare = None;
+                                    self.compare_focused = false;
+                                }
+                            }
                         }
+                        return Ok(());
                     }
                 }
-            },
-            KeyCode::Down => {
-                if absolute_selection + 1 < element_count {
-                    if relative_selection + 1 < self.list_height as usize {
-                        // Just move the selection down
-                        self.list_state.select(Some(relative_selection + 1));
-                    } else {
-                        // At bottom of window, need to shift window down
-                        self.list_offset += 1;
-                        if matches!(self.view_mode, ViewMode::Keys) {
-                            self.update_list()?;
+
+                // the letter after m or '; anything else cancels
+                if let Some(action) = self.pending_mark.take() {
+                    if let KeyCode::Char(letter @ ('a'..='z' | 'A'..='Z')) = key.code {
+                        match action {
+                            MarkAction::Set => self.set_mark(letter),
+                            MarkAction::Jump => self.jump_to_mark(letter)?,
                         }
                     }
+                    return Ok(());
                 }
-            },
-            KeyCode::PageUp => {
-                if self.list_offset > 0 {
-                    // Move window up by visible_height or to start
-                    self.list_offset = self.list_offset.saturating_sub(self.list_height as usize);
-                    if matches!(self.view_mode, ViewMode::Keys) {
-                        self.update_list()?;
+
+                // Accumulate a numeric prefix; a leading 0 is not a count
+                if let KeyCode::Char(digit @ '0'..='9') = key.code {
+                    if digit != '0' || self.pending_count.is_some() {
+                        let count = self.pending_count.unwrap_or(0) * 10 + digit.to_digit(10).unwrap_or(0) as usize;
+                        self.pending_count = Some(count.min(MAX_PENDING_COUNT));
+                        return Ok(());
                     }
-            // Keep selection at top of new window
-                    self.list_state.select(Some(0));
-                } else if relative_selection > 0 {
-                    // Already at top of data, just move selection to top of window
-                    self.list_state.select(Some(0));
                 }
-            },
-            KeyCode::PageDown => {
-                let max_offset = element_count.saturating_sub(self.list_height as usize);
-                if self.list_offset < max_offset {
-                    // Move window down by visible_height or to end
-                    self.list_offset = (self.list_offset + self.list_height as usize).min(max_offset);
-                    if matches!(self.view_mode, ViewMode::Keys) {
-                        self.update_list()?;
-                    }
-            // Keep selection at bottom of new window
-                    self.list_state.select(Some(self.list_height as usize - 1));
-                } else if relative_selection < self.list_height as usize - 1 {
-                    // Already at bottom of data, just move selection to bottom of window
-                    self.list_state.select(Some(self.list_height as usize - 1));
+                let count = self.pending_count.take().unwrap_or(1);
+                let delete_armed = self.delete_armed.take(); // any other key disarms
+
+                // every command of the main view is an action of the keymap, which keys.toml
+                // can rebind
+                if let Some(action) = self.config.keymap.action(&key) {
+                    return self.handle_action(action, key, count, delete_armed, &running);
                 }
-            },
-            _ => {}
+            }
         }
-        // panic!("list height is {}", self.list_height);
-        Ok(())
-    }
-
-
-    fn update_list(&mut self) -> Result<()> {
-        // Get just enough items to fill the visible area
-        self.app.set_key_range(self.list_offset, self.list_height as usize)?;
         Ok(())
     }
 
 
-}    
-
-
-impl Drop for TuiApp {
-    fn drop(&mut self) {
-        let _ = ratatui::restore();
-    }
-}
+    // Carry out a key bound to an action in the keymap
+    fn handle_action(&mut self, action: Action, key: KeyEvent, count: usize, delete_armed: Option<(Instant, String)>, running: &AtomicBool) -> Result<()> {
+        match action {
+            Action::Quit => self.leave(Leave::Quit, running),
+            // nothing to look at in the value pane of a bare branch
+            Action::SwitchPane if matches!(self.focused_pane, Pane::List)
+                && !matches!(self.config.tab_on_branch, TabOnBranch::Focus)
+                && self.on_bare_branch() => {
+                let index = self.list_state.selected().unwrap_or(0);
+                match self.config.tab_on_branch {
+                    TabOnBranch::Open => self.enter_branch(index)?,
+                    _ => self.status_message = Some("This branch has no value of its own - Enter opens it".to_string()),
+                }
+            },
+            Action::SwitchPane => {
+                self.focused_pane = match self.focused_pane {
+                    Pane::List => Pane::Value,
+                    Pane::Value => Pane::List,
+                };
+                self.scroll_state = 0; // Reset scroll when switching panes
+            },
+            Action::Top if matches!(self.focused_pane, Pane::List)
+                && matches!(self.view_mode, ViewMode::Keys)
+                && self.app.delimiter.is_some()
+                && self.app.key_filter.is_none() => {
+                self.app.go_to_root();
+                self.list_offset = 0;
+                self.update_list()?;
+                self.list_state.select(Some(0));
+            },
+            Action::ScrollUp | Action::ScrollDown | Action::ScrollLeft | Action::ScrollRight | Action::PageUp | Action::PageDown | Action::Top | Action::Bottom => {
+                let code = match action {
+                    Action::ScrollUp => KeyCode::Up,
+                    Action::ScrollDown => KeyCode::Down,
+                    Action::ScrollLeft => KeyCode::Left,
+                    Action::ScrollRight => KeyCode::Right,
+                    Action::PageUp => KeyCode::PageUp,
+                    Action::PageDown => KeyCode::PageDown,
+                    Action::Top => KeyCode::Home,
+                    _ => KeyCode::End,
+                };
+                if matches!(self.focused_pane, Pane::Value) {
+                    let shift_pressed = key.modifiers.contains(event::KeyModifiers::SHIFT);
+                    let movement = if shift_pressed { 10 } else { 1 };
+                    let movement = (movement * count).min(u16::MAX as usize) as u16;
+                    let page_step = (self.page_height as usize).saturating_sub(self.config.page_overlap).max(1);
+                    let page = (page_step * count).min(u16::MAX as usize) as u16;
+                    
+                    match code {
+                        KeyCode::Up => {
+                            self.scroll_state = self.scroll_state.saturating_sub(movement);
+                        }
+                        KeyCode::Down => {
+                            self.scroll_state = self.scroll_state.saturating_add(movement).min(self.max_scroll);
+                        }
+                        // nothing to scroll sideways while wrapping, unless it is a table
+                        KeyCode::Left => {
+                            self.horizontal_scroll = self.horizontal_scroll.saturating_sub(movement);
+                        }
+                        KeyCode::Right => {
+                            self.horizontal_scroll = self.horizontal_scroll.saturating_add(movement)
+                                .min(self.max_horizontal_scroll);
+                        }
+                        KeyCode::PageUp => {
+                            if matches!(self.focused_pane, Pane::Value) {
+                                self.scroll_state = self.scroll_state.saturating_sub(page);
+                            }
+                        },
+                        KeyCode::PageDown => {
+                            if matches!(self.focused_pane, Pane::Value) {
+                                self.scroll_state = self.scroll_state.saturating_add(page).min(self.max_scroll);
+                            }
+                        },
+                        KeyCode::Home => {
+                            if matches!(self.focused_pane, Pane::Value) {
+                                self.scroll_state = 0;
+                                self.horizontal_scroll = 0;
+                            }
+                        },
+                        KeyCode::End => {
+                            if matches!(self.focused_pane, Pane::Value) {
+                                self.scroll_state = self.max_scroll;
+                            }
+                        },     
+        
+                        _ => {}
+                    }
+                } else {
+                    self.handle_list_navigation(code, count)?;
+                }
+            }
+            // a filtered key (search hit, scan result) opens where it lives in the tree
+            Action::NavigateInto if matches!(self.focused_pane, Pane::List)
+                && matches!(self.view_mode, ViewMode::Keys)
+                && self.app.key_filter.is_some() => {
+                self.reveal_selected_key()?;
+            }
+            Action::NavigateInto => {
+                if matches!(self.focused_pane, Pane::List) {
+                    match self.view_mode {
+                        ViewMode::Trees => {
+                            let index = self.list_offset + self.list_state.selected().unwrap_or(0);
+                            // every tree may be hidden
+                            if index >= self.app.sled_trees.len() {
+                                return Ok(());
+                            }
+                            self.open_tree(index)?;
+                        }
+                        ViewMode::Keys => {
+                            let index = self.list_state.selected().unwrap_or(0);
+                            let is_leaf = self.app.current_key_range.keys.get(index)
+                                .is_some_and(|entry| self.app.delimiter.is_none() || !entry.has_children);
+                            if is_leaf {
+                                match self.config.enter_on_leaf {
+                                    EnterOnLeaf::FocusValue => self.focused_pane = Pane::Value,
+                                    EnterOnLeaf::Edit => {
+                                        self.focused_pane = Pane::Value;
+                                        self.start_editing();
+                                    }
+                                    EnterOnLeaf::Nothing => {}
+                                }
+                            }
+                            // with a count, keep drilling into the selected branch
+                            for _ in 0..count {
+                                let index = self.list_state.selected().unwrap_or(0);
+                                let has_children = self.app.current_key_range.keys.get(index).is_some_and(|entry| entry.has_children);
+                                if self.app.delimiter.is_none() || !has_children {
+                                    break;
+                                }
+                                self.enter_branch(index)?;
+                            }
+                        }
+                    }
+                }
+            }
+            Action::NavigateBack if self.app.key_filter.is_some() => {
+                self.focused_pane = Pane::List;
+                self.apply_key_filter(None)?;
+            },
+            Action::NavigateBack => {
+                self.focused_pane = Pane::List;
+                if !self.app.current_path.is_empty() {
+                    self.app.go_back_in_path()?;
+                    self.list_offset = 0;
+                    self.update_list()?;
+                    self.list_state.select(Some(0));
+                    self.scroll_state = 0;
+                    self.horizontal_scroll = 0;
+                    self.value_search = None;
+                } else if self.config.backspace_leaves_tree {
+                    self.go_to_tree_list();
+                } else if matches!(self.view_mode, ViewMode::Keys) {
+                    self.status_message = Some("At tree root - Esc or t for the tree list".to_string());
+                }
+            },
+            Action::TreeList if matches!(self.view_mode, ViewMode::Keys) => {
+                self.focused_pane = Pane::List;
+                self.go_to_tree_list();
+            },
+            Action::TreeList => {},
+            Action::ToggleWrap => {
+                if matches!(self.focused_pane, Pane::Value) {
+                    self.wrap_text = !self.wrap_text;
+                    self.horizontal_scroll = 0;
+                }
+            },
+            Action::Edit if matches!(self.focused_pane, Pane::Value) && matches!(self.view_mode, ViewMode::Keys) => {
+                self.start_editing();
+            },
+            Action::Edit => {},
+            Action::Help => {
+                self.show_help = true;
+            },
+            Action::Cancel if self.value_search.is_some() && matches!(self.focused_pane, Pane::Value) => {
+                self.value_search = None;
+            },
+            Action::Cancel if self.range_anchor.is_some() => self.range_anchor = None,
+            Action::Cancel if self.file_diff.is_some() => self.file_diff = None,
+            Action::DiffFile if matches!(self.view_mode, ViewMode::Keys) => {
+                self.prompt = Some(Prompt { kind: PromptKind::DiffFile, input: String::new() });
+            },
+            Action::MarkRange if matches!(self.view_mode, ViewMode::Keys) => self.mark_range(),
+            Action::Cancel if self.app.key_filter.is_some() => {
+                self.focused_pane = Pane::List;
+                self.apply_key_filter(None)?;
+            },
+            Action::Cancel if matches!(self.view_mode, ViewMode::Keys) => {
+                self.focused_pane = Pane::List;
+                self.go_to_tree_list();
+            },
+            Action::ValueTypes if matches!(self.view_mode, ViewMode::Keys) => {
+                if let Some(tree) = self.app.current_tree.clone() {
+                    // counting a tree walks it, so only show a total when we already know it
+                    let total = (self.app.delimiter.is_none() && self.app.key_filter.is_none()).then_some(self.app.total_keys);
+                    self.task = Some(Task::spawn("Scanning value types", total, move |progress| {
+                        Ok(TaskOutput::ValueTypes(scan_value_types(&tree, progress)?))
+                    }));
+                }
+            },
+            Action::ExplainKey if self.debug && matches!(self.view_mode, ViewMode::Keys) => {
+                self.info_popup = Some(InfoPopup {
+                    title: "Key resolution".to_string(),
+                    lines: self.app.explain_key(self.list_state.selected().unwrap_or(0)),
+                });
+            },
+            Action::Delete if matches!(self.view_mode, ViewMode::Keys) && matches!(self.focused_pane, Pane::List) && self.writable() => {
+                let index = self.list_state.selected().unwrap_or(0);
+                if self.on_bare_branch() {
+                    let branch = self.app.full_key(index).unwrap_or_default();
+                    self.status_message = Some(format!("{} has no value of its own - delete the keys under it one by one", branch));
+                } else if let Some(full_key) = self.selected_key() {
+                    match delete_armed {
+                        Some((_, armed_key)) if armed_key == full_key => self.delete_selected_key(index, &full_key)?,
+                        _ => self.delete_armed = Some((Instant::now(), full_key)),
+                    }
+                }
+            },
+            Action::Copy if delete_armed.is_some() => {
+                let index = self.list_state.selected().unwrap_or(0);
+                if let Some((_, full_key)) = delete_armed {
+                    self.delete_selected_key(index, &full_key)?;
+                }
+            },
+            Action::Copy if matches!(self.view_mode, ViewMode::Keys) => self.copy_selection(),
+            Action::CopyShellQuoted if matches!(self.view_mode, ViewMode::Keys) => {
+                self.copy_value_shell_quoted();
+            },
+            Action::CopyPretty if matches!(self.view_mode, ViewMode::Keys) => {
+                self.copy_value_pretty();
+            },
+            Action::CopyDumpCommand if matches!(self.view_mode, ViewMode::Keys) => {
+                self.copy_dump_command();
+            },
+            Action::WatchEvents => {
+                if self.events.is_some() {
+                    self.events = None;
+                } else if let Some(tree) = &self.app.current_tree {
+                    self.events = Some(EventFeed::subscribe(tree));
+                } else {
+                    self.status_message = Some("Select a tree to watch its events".to_string());
+                }
+            },
+            Action::Peek => {
+                self.peek = !self.peek;
+            },
+            Action::ShowHiddenTrees if matches!(self.view_mode, ViewMode::Trees) => {
+                self.app.show_hidden_trees = !self.app.show_hidden_trees;
+                self.app.refresh_trees()?;
+                self.list_offset = 0;
+                self.list_state.select(Some(0));
+                self.status_message = Some(if self.app.show_hidden_trees {
+                    "Showing hidden trees".to_string()
+                } else {
+                    "Hiding trees matching hidden_trees".to_string()
+                });
+            },
+            Action::CycleEncoding if matches!(self.view_mode, ViewMode::Keys) => self.cycle_encoding(),
+            Action::RebuildIndex if matches!(self.view_mode, ViewMode::Keys) => self.rebuild_key_index(),
+            Action::CyclePathBar => self.path_bar = self.path_bar.next(),
+            Action::TallInfoBar => self.tall_info_bar = !self.tall_info_bar,
+            Action::ExactSizes => self.toggle_exact_sizes(),
+            Action::CompactKeys if matches!(self.view_mode, ViewMode::Keys) => {
+                self.compact_keys = !self.compact_keys;
+                self.focused_pane = Pane::List;
+            },
+            Action::Reload => self.reload()?,
+            Action::SortDescending if matches!(self.view_mode, ViewMode::Keys) => self.toggle_descending()?,
+            Action::Compare => {
+                if self.compare.is_some() {
+                    self.compare_focused = true;
+                } else {
+                    self.prompt = Some(Prompt { kind: PromptKind::Compare, input: String::new() });
+                }
+            },
+            Action::NewTreeOrNextMatch if matches!(self.view_mode, ViewMode::Trees) && self.writable() => {
+                self.prompt = Some(Prompt { kind: PromptKind::NewTree, input: String::new() });
+            },
+            Action::Delete if matches!(self.view_mode, ViewMode::Trees) && self.writable() => {
+                self.confirm_drop_tree();
+            },
+            Action::MoreTrees if matches!(self.view_mode, ViewMode::Trees) => {
+                if let (Some(limit), Some(max_trees)) = (self.app.tree_limit, self.max_trees) {
+                    self.app.tree_limit = Some(limit + max_trees);
+                    self.app.refresh_trees()?;
+                }
+            },
+            Action::SetMark => self.pending_mark = Some(MarkAction::Set),
+            Action::JumpToMark => self.pending_mark = Some(MarkAction::Jump),
+            Action::Marks => self.show_marks(),
+            Action::HexView if matches!(self.focused_pane, Pane::Value) => {
+                self.value_view = match self.value_view {
+                    ValueView::Hex => ValueView::Text,
+                    _ => ValueView::Hex,
+                };
+                self.scroll_state = 0;
+                self.horizontal_scroll = 0;
+            },
+            Action::CycleView if matches!(self.focused_pane, Pane::Value) => {
+                self.value_view = self.value_view.next();
+                self.scroll_state = 0;
+                self.horizontal_scroll = 0;
+            },
+            Action::TableView => {
+                self.table_view = !self.table_view;
+                self.scroll_state = 0;
+                self.horizontal_scroll = 0;
+            },
+            Action::AbsoluteKeys if matches!(self.view_mode, ViewMode::Keys) => {
+                self.absolute_keys = !self.absolute_keys;
+            },
+            Action::ConcatBranches if matches!(self.view_mode, ViewMode::Keys) => {
+                self.concat = !self.concat;
+                self.scroll_state = 0;
+                self.status_message = Some(if self.concat {
+                    "Branches show their values concatenated in key order".to_string()
+                } else {
+                    "Concatenated branch view off".to_string()
+                });
+            },
+            Action::ClearTree if matches!(self.view_mode, ViewMode::Keys) && self.writable() => {
+                if let Some(tree) = &self.app.current_tree {
+                    let name = String::from_utf8_lossy(&tree.name()).to_string();
+                    self.prompt = Some(Prompt { kind: PromptKind::ClearTree(name), input: String::new() });
+                }
+            },
+            Action::Template if matches!(self.view_mode, ViewMode::Keys) => self.start_template(),
+            Action::Fingerprint => self.fingerprint(),
+            Action::ExportMatches if matches!(self.view_mode, ViewMode::Keys) => self.export_matches(),
+            Action::ExportTree => self.export_tree()?,
+            Action::Pager if matches!(self.view_mode, ViewMode::Keys) => self.open_in_pager(running)?,
+            Action::SharedValues if matches!(self.view_mode, ViewMode::Keys) => {
+                if let Some(tree) = self.app.current_tree.clone() {
+                    let total = (self.app.delimiter.is_none() && self.app.key_filter.is_none()).then_some(self.app.total_keys);
+                    self.task = Some(Task::spawn("Finding shared values", total, move |progress| {
+                        Ok(TaskOutput::SharedValues(find_shared_values(&tree, progress)?))
+                    }));
+                }
+            },
+            Action::Pin if self.pinned.is_some() => {
+                self.pinned = None;
+                self.status_message = Some("Unpinned - the value pane follows the selection again".to_string());
+            },
+            Action::Pin if matches!(self.view_mode, ViewMode::Keys) => {
+                if let (Some(full_key), Some(tree)) = (self.selected_key(), self.app.current_tree.clone()) {
+                    self.status_message = Some(format!("Pinned {} to the value pane - P unpins", full_key));
+                    self.pinned = Some(Pin { tree, full_key });
+                }
+            },
+            Action::GoToKey if matches!(self.view_mode, ViewMode::Keys) => {
+                self.prompt = Some(Prompt { kind: PromptKind::GoToKey, input: String::new() });
+            },
+            Action::Search if matches!(self.view_mode, ViewMode::Keys) && matches!(self.focused_pane, Pane::Value) => {
+                self.prompt = Some(Prompt { kind: PromptKind::FindInValue, input: String::new() });
+            },
+            Action::NewTreeOrNextMatch if self.value_search.is_some() && matches!(self.focused_pane, Pane::Value) => {
+                self.step_value_search(true);
+            },
+            Action::PreviousMatch if self.value_search.is_some() && matches!(self.focused_pane, Pane::Value) => {
+                self.step_value_search(false);
+            },
+            Action::Search if matches!(self.view_mode, ViewMode::Keys) => {
+                self.prompt = Some(Prompt { kind: PromptKind::Search, input: String::new() });
+            },
+            Action::Search => {
+                let input = self.app.tree_filter.clone().unwrap_or_default();
+                self.prompt = Some(Prompt { kind: PromptKind::FilterTrees, input });
+            },
+            Action::Cancel if matches!(self.view_mode, ViewMode::Trees) && self.app.tree_filter.is_some() => {
+                self.filter_trees(None)?;
+            },
+            Action::CycleDelimiter if matches!(self.view_mode, ViewMode::Keys) => self.cycle_delimiter()?,
+            Action::FlatPaths if matches!(self.view_mode, ViewMode::Keys) => {
+                if self.app.use_disk_index {
+                    self.status_message = Some("Flat path view isn't available with --disk-index".to_string());
+                } else if self.app.delimiter.is_some() {
+                    self.app.set_flat_paths(!self.app.flat_paths)?;
+                    self.focused_pane = Pane::List;
+                    self.list_offset = 0;
+                    self.update_list()?;
+                    self.list_state.select(Some(0));
+                } else {
+                    self.status_message = Some("Flat path view needs a delimiter".to_string());
+                }
+            },
+            Action::Baseline if matches!(self.view_mode, ViewMode::Keys) => {
+                if let Some(tree) = self.app.current_tree.clone() {
+                    let tree_name = String::from_utf8_lossy(&tree.name()).to_string();
+                    self.task = Some(Task::spawn("Taking baseline", None, move |progress| {
+                        Ok(TaskOutput::Baseline(tree_name, snapshot_tree(&tree, progress)?))
+                    }));
+                }
+            },
+            Action::DiffBaseline if matches!(self.view_mode, ViewMode::Keys) => {
+                self.diff_against_baseline();
+            },
+            Action::ExportOutline | Action::ExportOutlineJson if matches!(self.view_mode, ViewMode::Keys) => {
+                let format = if action == Action::ExportOutlineJson { KeyTreeFormat::Json } else { KeyTreeFormat::Text };
+                self.export_key_tree(format);
+            },
+            Action::EditFields if matches!(self.focused_pane, Pane::Value) && matches!(self.view_mode, ViewMode::Keys) => {
+                self.start_map_editing();
+            },
+            Action::PrettyPrint => {
+                if matches!(self.focused_pane, Pane::Value) {
+                    self.pretty_print = !self.pretty_print;
+                    self.scroll_state = 0;
+                    self.horizontal_scroll = 0;
+                }
+            },
+            Action::Fold if matches!(self.focused_pane, Pane::Value) => {
+                match &self.record_view {
+                    Some(view) => {
+                        if !self.folds.folded.remove(&view.top) {
+                            self.folds.folded.insert(view.top);
+                        }
+                    },
+                    None => self.status_message = Some("Nothing to fold - z folds the records of NDJSON values, with p on".to_string()),
+                }
+            },
+            Action::FoldAll if matches!(self.focused_pane, Pane::Value) => {
+                if let Some(view) = &self.record_view {
+                    if self.folds.folded.is_empty() {
+                        self.folds.folded = (0..view.count).collect();
+                    } else {
+                        self.folds.folded.clear();
+                    }
+                    self.scroll_state = 0;
+                }
+            },
+            Action::LineNumbers => {
+                if matches!(self.focused_pane, Pane::Value) {
+                    self.line_numbers = !self.line_numbers;
+                }
+            },
+            Action::HangingIndent => {
+                if matches!(self.focused_pane, Pane::Value) {
+                    self.hanging_indent = !self.hanging_indent;
+                    self.status_message = Some(format!(
+                        "Hanging indent {}{}",
+                        if self.hanging_indent { "on" } else { "off" },
+                        if self.wrap_text { "" } else { " (applies when wrapping, w)" },
+                    ));
+                }
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+
+    fn handle_list_navigation(&mut self, key: KeyCode, count: usize) -> Result<()> {
+        let element_count = match self.view_mode {
+            ViewMode::Trees => self.app.sled_trees.len(),
+            ViewMode::Keys => self.app.total_keys,
+        };
+
+        if element_count == 0 {
+            return Ok(());
+        }
+
+        let relative_selection = self.list_state.selected().unwrap_or(0);  // Relative to visible items
+        let absolute_selection = self.list_offset + relative_selection;  // Actual position in full dataset
+        let page_step = (self.list_height as usize).saturating_sub(self.config.page_overlap).max(1);
+        // the window's last row, short of list_height when the list ends inside it
+        let height = (self.list_height as usize).max(1);
+        let last_row = |offset: usize| height.min(element_count - offset.min(element_count - 1)) - 1;
+
+        match key {
+            KeyCode::Up if absolute_selection > 0 => {
+                self.move_selection_to(absolute_selection.saturating_sub(count))?;
+            },
+            KeyCode::Down if absolute_selection + 1 < element_count => {
+                self.move_selection_to((absolute_selection + count).min(element_count - 1))?;
+            },
+            KeyCode::PageUp | KeyCode::PageDown if count > 1 => {
+                for _ in 0..count {
+                    self.handle_list_navigation(key, 1)?;
+                }
+            },
+            KeyCode::PageUp => {
+                if self.list_offset > 0 {
+                    // Move window up by visible_height or to start
+                    self.list_offset = self.list_offset.saturating_sub(page_step);
+                    if matches!(self.view_mode, ViewMode::Keys) {
+                        self.update_list()?;
+                    }
+            // Keep selection at top of new window
+                    self.list_state.select(Some(0));
+                } else if relative_selection > 0 {
+                    // Already at top of data, just move selection to top of window
+                    self.list_state.select(Some(0));
+                }
+            },
+            KeyCode::PageDown => {
+                let max_offset = element_count.saturating_sub(self.list_height as usize);
+                if self.list_offset < max_offset {
+                    // Move window down by visible_height or to end
+                    self.list_offset = (self.list_offset + page_step).min(max_offset);
+                    if matches!(self.view_mode, ViewMode::Keys) {
+                        self.update_list()?;
+                    }
+            // Keep selection at bottom of new window
+                    self.list_state.select(Some(last_row(self.list_offset)));
+                } else if relative_selection < last_row(self.list_offset) {
+                    // Already at bottom of data, just move selection to the last element
+                    self.list_state.select(Some(last_row(self.list_offset)));
+                }
+            },
+            KeyCode::Home => self.move_selection_to(0)?,
+            KeyCode::End => self.move_selection_to(element_count - 1)?,
+            // across the columns of compact mode, a column's height at a time
+            KeyCode::Left if self.columns > 1 => {
+                let rows = height / self.columns;
+                self.move_selection_to(absolute_selection.saturating_sub(rows * count))?;
+            },
+            KeyCode::Right if self.columns > 1 => {
+                let rows = height / self.columns;
+                self.move_selection_to((absolute_selection + rows * count).min(element_count - 1))?;
+            },
+            _ => {}
+        }
+        // a new key's value starts at its top, not where the last one was scrolled to
+        if self.list_offset + self.list_state.selected().unwrap_or(0) != absolute_selection {
+            self.scroll_state = 0;
+            self.horizontal_scroll = 0;
+            self.value_search = None;
+        }
+        // panic!("list height is {}", self.list_height);
+        Ok(())
+    }
+
+
+    // The wheel scrolls whichever pane it is over, and a left click selects a list row.
+    // Popups, prompts, editors and the comparison view keep the mouse out.
+    fn handle_mouse(&mut self, mouse: MouseEvent) -> Result<()> {
+        let busy = self.show_help
+            || self.info_popup.is_some()
+            || self.value_types.is_some()
+            || self.shared_values.is_some()
+            || self.task.is_some()
+            || self.prompt.is_some()
+            || self.editor.is_some()
+            || self.map_editor.is_some()
+            || self.pending_leave.is_some()
+            || self.compare_focused;
+        if busy {
+            return Ok(());
+        }
+        let position = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp if self.value_area.contains(position) => {
+                self.scroll_state = match mouse.kind {
+                    MouseEventKind::ScrollDown => self.scroll_state.saturating_add(MOUSE_SCROLL_LINES).min(self.max_scroll),
+                    _ => self.scroll_state.saturating_sub(MOUSE_SCROLL_LINES),
+                };
+            }
+            MouseEventKind::ScrollDown if self.list_area.contains(position) => self.handle_list_navigation(KeyCode::Down, 1)?,
+            MouseEventKind::ScrollUp if self.list_area.contains(position) => self.handle_list_navigation(KeyCode::Up, 1)?,
+            MouseEventKind::Down(MouseButton::Left) => {
+                // rows start inside the border; compact mode fills its columns top to bottom
+                let inner = self.list_area.inner(Margin::new(1, 1));
+                if !inner.contains(position) {
+                    return Ok(());
+                }
+                let rows = inner.height as usize;
+                let column = (mouse.column - inner.x) as usize * self.columns / inner.width as usize;
+                let absolute = self.list_offset + column * rows + (mouse.row - inner.y) as usize;
+                let element_count = match self.view_mode {
+                    ViewMode::Trees => self.app.sled_trees.len(),
+                    ViewMode::Keys => self.app.total_keys,
+                };
+                if absolute >= element_count {
+                    return Ok(());
+                }
+                self.focused_pane = Pane::List;
+                if absolute != self.list_offset + self.list_state.selected().unwrap_or(0) {
+                    self.move_selection_to(absolute)?;
+                    self.scroll_state = 0;
+                    self.horizontal_scroll = 0;
+                    self.value_search = None;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+
+    // Select an absolute index in the full list, shifting the window only as far as needed to show it
+    fn move_selection_to(&mut self, absolute_selection: usize) -> Result<()> {
+        let height = (self.list_height as usize).max(1);
+        if absolute_selection < self.list_offset {
+            self.list_offset = absolute_selection;
+        } else if absolute_selection >= self.list_offset + height {
+            self.list_offset = absolute_selection + 1 - height;
+        }
+        if matches!(self.view_mode, ViewMode::Keys) && self.app.current_key_range.offset != self.list_offset {
+            self.update_list()?;
+        }
+        self.list_state.select(Some(absolute_selection - self.list_offset));
+        Ok(())
+    }
+
+
+    // Title and text previewing the tree or branch under the cursor, None when there is
+    // nothing to peek into (a leaf just shows its value as usual)
+    fn peek_content(&self) -> Option<(String, String)> {
+        let index = self.list_state.selected().unwrap_or(0);
+        let rows = self.page_height as usize;
+        let (name, keys) = match self.view_mode {
+            ViewMode::Trees => {
+                let index = self.list_offset + index;
+                let name = self.app.sled_trees.get(index)?.1.clone();
+                let keys = match self.app.peek_tree(index, rows) {
+                    Ok(keys) => keys,
+                    Err(e) => vec![format!("Could not read tree: {}", e)],
+                };
+                (name, keys)
+            }
+            ViewMode::Keys => {
+                let name = self.app.current_key_range.keys.get(index)?.key.clone();
+                (name, self.app.peek_children(index, rows)?)
+            }
+        };
+        let content = if keys.is_empty() { "(no keys)".to_string() } else { keys.join("\n") };
+        Some((format!("Peek: {} [space to close]", name), content))
+    }
+
+
+    // The selected key's value for copying, or None with the reason in the status bar
+    fn value_to_copy(&mut self) -> Option<Vec<u8>> {
+        match self.app.get_value(self.list_state.selected().unwrap_or(0)) {
+            Ok(Some(value)) => Some(value),
+            Ok(None) => {
+                self.status_message = Some("No value to copy".to_string());
+                None
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Could not read value: {}", e));
+                None
+            }
+        }
+    }
+
+
+    // V: start a range of keys at the selection, or once started, ask how to copy it
+    fn mark_range(&mut self) {
+        if self.selected_range().is_some() {
+            self.prompt = Some(Prompt { kind: PromptKind::CopyRange, input: String::new() });
+            return;
+        }
+        self.range_anchor = Some(RangeAnchor {
+            position: self.list_offset + self.list_state.selected().unwrap_or(0),
+            tree: self.app.current_tree.as_ref().map(|tree| tree.name()),
+            path: self.app.current_path.clone(),
+            filter: self.app.key_filter.as_ref().map(|filter| filter.label.clone()),
+        });
+        self.status_message = Some("Range started - move to its other end and press V again to copy it, Esc drops it".to_string());
+    }
+
+    // List positions from the range anchor to the selection, while still in the anchor's list
+    fn selected_range(&self) -> Option<RangeInclusive<usize>> {
+        let anchor = self.range_anchor.as_ref()?;
+        let same_list = matches!(self.view_mode, ViewMode::Keys)
+            && anchor.tree == self.app.current_tree.as_ref().map(|tree| tree.name())
+            && anchor.path == self.app.current_path
+            && anchor.filter.as_deref() == self.app.key_filter.as_ref().map(|filter| filter.label.as_str());
+        if !same_list {
+            return None;
+        }
+        let selected = self.list_offset + self.list_state.selected().unwrap_or(0);
+        Some(anchor.position.min(selected)..=anchor.position.max(selected))
+    }
+
+    // Copy the values of the keys in the range as one document. Branches without a value
+    // are skipped.
+    fn copy_range(&mut self, form: RangeCopy) -> Result<()> {
+        let Some(range) = self.selected_range() else { return Ok(()) };
+        let count = range.end() - range.start() + 1;
+        self.app.set_key_range(*range.start(), count)?;
+        let keys: Vec<String> = (0..count).filter_map(|index| self.app.full_key(index)).collect();
+        self.update_list()?;
+
+        let mut values = vec![];
+        let mut size = 0;
+        for key in keys {
+            let Some(value) = self.app.value_of(&key)? else { continue };
+            size += value.len();
+            if size > MAX_CONCAT_BYTES {
+                self.status_message = Some(format!("Nothing copied - the range holds more than {}", format::size(MAX_CONCAT_BYTES, self.ui_state.exact_sizes)));
+                return Ok(());
+            }
+            let text = match self.app.encoding_of(&key) {
+                Some(encoding) => encoding::decode(&value, encoding),
+                None => String::from_utf8_lossy(&value).into_owned(),
+            };
+            values.push((value, text));
+        }
+        if values.is_empty() {
+            self.status_message = Some("No values in the range to copy".to_string());
+            return Ok(());
+        }
+        // structured values go in as themselves, anything else as a string
+        let element = |(value, text): &(Vec<u8>, String)| match format::to_json(value) {
+            Some((_, parsed)) => parsed,
+            None => serde_json::Value::String(text.clone()),
+        };
+        let text = match form {
+            RangeCopy::Concatenated => values.iter().map(|(_, text)| text.as_str()).collect::<Vec<_>>().join("\n"),
+            RangeCopy::Array => serde_json::to_string_pretty(&values.iter().map(element).collect::<Vec<_>>())?,
+            RangeCopy::Ndjson => values.iter().map(|value| element(value).to_string()).collect::<Vec<_>>().join("\n"),
+        };
+        self.status_message = Some(match self.clipboard.copy(&text) {
+            Ok(()) => format!("Copied {} values {}, {}", values.len(), form.describe(), format::size(text.len(), self.ui_state.exact_sizes)),
+            Err(e) => format!("{:#}", e),
+        });
+        self.range_anchor = None;
+        Ok(())
+    }
+
+
+    // n / N: scroll the next or previous match of the value search into view
+    fn step_value_search(&mut self, forward: bool) {
+        let Some(search) = &mut self.value_search else { return };
+        let count = search.rows.len();
+        if count == 0 {
+            self.status_message = Some(format!("No match for {} in the value", search.needle));
+            return;
+        }
+        search.current = if forward { (search.current + 1) % count } else { (search.current + count - 1) % count };
+        self.scroll_state = search.rows[search.current].min(u16::MAX as usize) as u16;
+    }
+
+
+    // y: the value as text when the value pane has focus, otherwise the selected key in full
+    fn copy_selection(&mut self) {
+        let (text, what) = match self.focused_pane {
+            Pane::Value => {
+                let Some(value) = self.value_to_copy() else { return };
+                (String::from_utf8_lossy(&value).into_owned(), "value")
+            }
+            Pane::List => {
+                let Some(full_key) = self.selected_key() else { return };
+                (full_key, "key")
+            }
+        };
+        self.status_message = Some(match self.clipboard.copy(&text) {
+            Ok(()) => format!("Copied {}, {}", what, format::size(text.len(), self.ui_state.exact_sizes)),
+            Err(e) => format!("{:#}", e),
+        });
+    }
+
+
+    fn copy_value_shell_quoted(&mut self) {
+        let Some(value) = self.value_to_copy() else { return };
+        let (quoted, quoting) = clipboard::shell_quote(&value);
+        self.status_message = Some(match self.clipboard.copy(&quoted) {
+            Ok(()) => match quoting {
+                ShellQuoting::SingleQuoted => "Copied value as a single-quoted shell argument".to_string(),
+                ShellQuoting::Base64 => "Copied multi-line/binary value as a base64-decoding shell argument".to_string(),
+            },
+            Err(e) => format!("{:#}", e),
+        });
+    }
+
+
+    // Show the value, as the value pane renders it, in $PAGER (less if unset) with the TUI
+    // suspended until the pager exits
+    fn open_in_pager(&mut self, running: &AtomicBool) -> Result<()> {
+        let Some(full_key) = self.selected_key() else { return Ok(()) };
+        let Some(value) = self.value_to_copy() else { return Ok(()) };
+        let value = match self.app.encoding_of(&full_key) {
+            Some(encoding) => encoding::decode(&value, encoding).into_bytes(),
+            None => value,
+        };
+        let text = self.app.render_value(&value, self.pretty_print, &self.config.pretty).text;
+        let pager = std::env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty()).unwrap_or_else(|| "less".to_string());
+        let mut words = pager.split_whitespace();
+        let program = words.next().unwrap_or("less");
+
+        crossterm::execute!(std::io::stdout(), DisableBracketedPaste, DisableMouseCapture)?;
+        ratatui::restore();
+        let result = std::process::Command::new(program)
+            .args(words)
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                // the pager may quit before reading everything, closing the pipe
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(text.as_bytes());
+                }
+                child.wait()
+            });
+        crossterm::terminal::enable_raw_mode()?;
+        crossterm::execute!(std::io::stdout(), crossterm::terminal::EnterAlternateScreen, EnableBracketedPaste, EnableMouseCapture)?;
+        self.terminal.clear()?;
+        // Ctrl-C in the pager reaches sledit too, and shouldn't quit it
+        running.store(true, Ordering::SeqCst);
+
+        if let Err(e) = result {
+            self.status_message = Some(format!("Could not run the pager {}: {} - set $PAGER to another", program, e));
+        }
+        Ok(())
+    }
+
+
+    // Copy the value pretty-printed in its detected format, or as stored when it doesn't parse
+    fn copy_value_pretty(&mut self) {
+        let Some(value) = self.value_to_copy() else { return };
+        let rendered = self.app.render_value(&value, true, &self.config.pretty);
+        let form = match rendered.label {
+            Some(label) => format!("pretty-printed {}", label),
+            None => "raw (not a structured format)".to_string(),
+        };
+        let text = rendered.text;
+        self.status_message = Some(match self.clipboard.copy(&text) {
+            Ok(()) => format!("Copied value {}", form),
+            Err(e) => format!("{:#}", e),
+        });
+    }
+
+
+    // Copy a --dump command line that extracts what the key list shows: the tree, the
+    // branch being viewed and the search filter
+    fn copy_dump_command(&mut self) {
+        let Some(tree) = &self.app.current_tree else { return };
+        let quote = |text: &str| clipboard::shell_quote(text.as_bytes()).0;
+        let mut command = vec!["sledit".to_string(), quote(&self.db_path.to_string_lossy()), "--dump".to_string()];
+        let tree_name = tree.name();
+        match std::str::from_utf8(&tree_name) {
+            Ok("__sled__default") => {}
+            Ok(name) => command.extend(["--tree".to_string(), quote(name)]),
+            Err(_) => {
+                let hex: String = tree_name.iter().map(|byte| format!("{:02x}", byte)).collect();
+                command.extend(["--tree-hex".to_string(), hex]);
+            }
+        }
+        if let Some(delimiter) = &self.app.delimiter {
+            if !self.app.current_path.is_empty() && !self.app.flat_paths {
+                let prefix = format!("{}{}", self.app.current_path.join(delimiter), delimiter);
+                command.extend(["--prefix".to_string(), quote(&prefix)]);
+            }
+        }
+        let mut note = "";
+        match &self.app.key_filter {
+            Some(KeyFilter { search: Some(needle), .. }) => command.extend(["--search".to_string(), quote(needle)]),
+            Some(_) => note = " (the key list filter has no command line equivalent, so this dumps the whole branch)",
+            None => {}
+        }
+        let command = command.join(" ");
+        self.status_message = Some(match self.clipboard.copy(&command) {
+            Ok(()) => format!("Copied {}{}", command, note),
+            Err(e) => format!("{:#}", e),
+        });
+    }
+
+
+    fn delete_selected_key(&mut self, index: usize, full_key: &str) -> Result<()> {
+        self.status_message = Some(match self.app.delete_key(index) {
+            Ok(true) => format!("Deleted {}{}", full_key, self.verified()),
+            Ok(false) => format!("{} has no value to delete", full_key),
+            Err(e) => format!("Delete failed: {}", e),
+        });
+        // keep the window and selection inside the now shorter list
+        self.list_offset = self.list_offset.min(self.app.total_keys.saturating_sub(self.list_height as usize));
+        self.update_list()?;
+        let last = self.app.current_key_range.keys.len().saturating_sub(1);
+        self.list_state.select(Some(index.min(last)));
+        Ok(())
+    }
+
+
+    // Pick up the result of a finished background scan
+    fn poll_task(&mut self) -> Result<()> {
+        let Some(result) = self.task.as_ref().and_then(|task| task.try_finish()) else {
+            return Ok(());
+        };
+        let task = self.task.take().expect("This is a bug. try_finish only returns a result for a running task.");
+        match result {
+            Ok(TaskOutput::Search(needle, keys)) => {
+                if keys.is_empty() {
+                    self.status_message = Some(format!("No keys contain {}", needle));
+                } else {
+                    self.focused_pane = Pane::List;
+                    self.apply_key_filter(Some(KeyFilter { label: format!("/{}", needle), keys, search: Some(needle) }))?;
+                    self.status_message = Some("Enter opens a key in its place in the tree - Esc clears the search".to_string());
+                }
+            }
+            Ok(TaskOutput::ValueTypes(kinds)) => {
+                let mut state = ListState::default();
+                state.select(Some(0));
+                self.value_types = Some(ValueTypesPopup { kinds: kinds.into_iter().collect(), state });
+            }
+            Ok(TaskOutput::Baseline(tree_name, baseline)) => {
+                self.status_message = Some(format!("Baseline of {} keys taken for tree {} - B shows changes since", baseline.len(), tree_name));
+                self.baseline = Some((tree_name, Arc::new(baseline)));
+                self.baseline_changes = None;
+            }
+            Ok(TaskOutput::BaselineDiff(changes)) => {
+                if changes.is_empty() {
+                    self.status_message = Some("No changes since baseline".to_string());
+                    self.baseline_changes = None;
+                } else {
+                    let count = |change| changes.values().filter(|c| **c == change).count();
+                    let label = format!("+{} -{} ~{} since baseline", count(Change::Added), count(Change::Removed), count(Change::Changed));
+                    let keys = changes.keys().cloned().collect();
+                    self.baseline_changes = Some(changes);
+                    self.focused_pane = Pane::List;
+                    self.apply_key_filter(Some(KeyFilter { label, keys, search: None }))?;
+                }
+            }
+            Ok(TaskOutput::DiskIndex(index)) => {
+                self.app.disk_index = Some(index);
+                self.app.invalidate_value();
+                let kept = self.app.trim_current_path();
+                self.app.refresh_total_keys();
+                self.finish_rebuild(kept)?;
+            }
+            Ok(TaskOutput::SharedValues(groups)) if groups.is_empty() => {
+                self.status_message = Some("No two keys share a value".to_string());
+            }
+            Ok(TaskOutput::SharedValues(groups)) => {
+                let mut state = ListState::default();
+                state.select(Some(0));
+                self.shared_values = Some(SharedValuesPopup { groups, state });
+            }
+            Ok(TaskOutput::Fingerprint(scope, fingerprint)) => {
+                self.info_popup = Some(InfoPopup {
+                    title: format!("Fingerprint of {}", scope),
+                    lines: vec![
+                        format!("sha256  {}", fingerprint.hash),
+                        format!("over {} keys, {} of keys and values", fingerprint.keys, format::size(fingerprint.bytes, self.ui_state.exact_sizes)),
+                        String::new(),
+                        "Equal fingerprints mean identical contents - compare with the same".to_string(),
+                        "action on another tree or database.".to_string(),
+                    ],
+                });
+            }
+            Ok(TaskOutput::Created(count)) => {
+                self.app.reload_tree()?;
+                self.update_list()?;
+                let tree = self.app.current_tree.as_ref().map(|tree| String::from_utf8_lossy(&tree.name()).to_string());
+                self.status_message = Some(format!("Created {} keys in {}{}", count, tree.unwrap_or_default(), self.verified()));
+            }
+            Ok(TaskOutput::Exported(file_name, count)) => {
+                self.status_message = Some(format!("Wrote {} keys and values to {}", count, file_name));
+            }
+            Ok(TaskOutput::KeyIndex(index)) => {
+                let kept = self.app.install_key_index(index);
+                self.finish_rebuild(kept)?;
+            }
+            Err(e) => {
+                self.rebuild_started = None;
+                self.status_message = Some(format!("{}: {}", task.label, e));
+            }
+        }
+        Ok(())
+    }
+
+
+    // Build the on-disk key index the selected tree is waiting for, in the background
+    fn start_disk_index(&mut self) {
+        if !self.app.disk_index_needed || self.task.is_some() {
+            return;
+        }
+        self.app.disk_index_needed = false;
+        let (Some(tree), Some(delimiter)) = (self.app.current_tree.clone(), self.app.delimiter.clone()) else { return };
+        let max_depth = self.app.max_depth;
+        self.task = Some(Task::spawn("Indexing keys on disk", Some(tree.len()), move |progress| {
+            Ok(TaskOutput::DiskIndex(DiskIndex::build(&tree, &delimiter, max_depth, progress)?))
+        }));
+    }
+
+
+    // R: re-read the whole tree into the key hierarchy, after another writer changed it.
+    // Runs in the background, so Esc cancels it and leaves the old index in place.
+    fn rebuild_key_index(&mut self) {
+        let (Some(tree), Some(delimiter)) = (self.app.current_tree.clone(), self.app.delimiter.clone()) else {
+            self.status_message = Some("Nothing to rebuild - without a delimiter keys are read straight from the tree".to_string());
+            return;
+        };
+        if self.task.is_some() {
+            return;
+        }
+        self.rebuild_started = Some((Instant::now(), self.app.current_path.clone()));
+        if self.app.use_disk_index {
+            self.app.disk_index_needed = true;
+            self.start_disk_index();
+            return;
+        }
+        // level by level, each level is read when it is opened, so re-reading this one is enough
+        if !self.app.flat_paths {
+            let kept = match self.app.reload_tree() {
+                Ok(()) => self.app.trim_current_path(),
+                Err(e) => {
+                    self.rebuild_started = None;
+                    self.status_message = Some(format!("Rebuild failed: {}", e));
+                    return;
+                }
+            };
+            self.app.refresh_total_keys();
+            if let Err(e) = self.finish_rebuild(kept) {
+                self.status_message = Some(format!("Rebuild failed: {}", e));
+            }
+            return;
+        }
+        let max_depth = self.app.max_depth;
+        self.task = Some(Task::spawn("Rebuilding the key index", Some(tree.len()), move |progress| {
+            Ok(TaskOutput::KeyIndex(index_keys(&tree, &delimiter, max_depth, Some(progress))?))
+        }));
+    }
+
+    // Show the rebuilt index, and say how long R took and whether the view had to move up
+    fn finish_rebuild(&mut self, kept: bool) -> Result<()> {
+        self.list_offset = 0;
+        self.update_list()?;
+        self.list_state.select(Some(0));
+        let Some((started, old_path)) = self.rebuild_started.take() else { return Ok(()) };
+        let delimiter = self.app.delimiter.clone().unwrap_or_default();
+        let mut message = format!("Rebuilt the key index in {:.2}s", started.elapsed().as_secs_f64());
+        if !kept {
+            let now_at = match self.app.current_path.is_empty() {
+                true => "the top of the tree".to_string(),
+                false => self.app.current_path.join(&delimiter),
+            };
+            message += &format!(" - {} is gone, showing {}", old_path.join(&delimiter), now_at);
+        }
+        self.status_message = Some(message);
+        Ok(())
+    }
+
+
+    fn diff_against_baseline(&mut self) {
+        let Some(tree) = self.app.current_tree.clone() else { return };
+        let tree_name = String::from_utf8_lossy(&tree.name()).to_string();
+        match &self.baseline {
+            Some((baseline_tree, baseline)) if *baseline_tree == tree_name => {
+                let baseline = baseline.clone();
+                self.task = Some(Task::spawn("Comparing with baseline", Some(baseline.len()), move |progress| {
+                    Ok(TaskOutput::BaselineDiff(diff_baseline(&tree, &baseline, progress)?))
+                }));
+            }
+            Some((baseline_tree, _)) => {
+                self.status_message = Some(format!("The baseline was taken on tree {} - press b to take one here", baseline_tree));
+            }
+            None => self.status_message = Some("No baseline yet - press b to take one".to_string()),
+        }
+    }
+
+
+    fn handle_value_types_input(&mut self, key: KeyCode) -> Result<()> {
+        let Some(popup) = &mut self.value_types else { return Ok(()) };
+        let selected = popup.state.selected().unwrap_or(0);
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => popup.state.select(Some(selected.saturating_sub(1))),
+            KeyCode::Down | KeyCode::Char('j') => popup.state.select(Some((selected + 1).min(popup.kinds.len()))),
+            KeyCode::Enter => {
+                // row 0 is "all keys", the rest follow popup.kinds
+                let filter = selected.checked_sub(1).map(|index| {
+                    let (kind, keys) = popup.kinds.swap_remove(index);
+                    KeyFilter { label: kind.name().to_string(), keys, search: None }
+                });
+                self.value_types = None;
+                self.focused_pane = Pane::List;
+                self.apply_key_filter(filter)?;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.value_types = None,
+            _ => {}
+        }
+        Ok(())
+    }
+
+
+    fn handle_shared_values_input(&mut self, key: KeyCode) -> Result<()> {
+        let Some(popup) = &mut self.shared_values else { return Ok(()) };
+        let selected = popup.state.selected().unwrap_or(0);
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => popup.state.select(Some(selected.saturating_sub(1))),
+            KeyCode::Down | KeyCode::Char('j') => popup.state.select(Some((selected + 1).min(popup.groups.len() - 1))),
+            KeyCode::Enter => {
+                let group = popup.groups.swap_remove(selected);
+                let label = format!("{} keys sharing a value", group.keys.len());
+                self.shared_values = None;
+                self.focused_pane = Pane::List;
+                self.apply_key_filter(Some(KeyFilter { label, keys: group.keys, search: None }))?;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.shared_values = None,
+            _ => {}
+        }
+        Ok(())
+    }
+
+
+    // The selected row's full key, or None with a note in the status bar when the list is empty
+    fn selected_key(&mut self) -> Option<String> {
+        let full_key = self.app.full_key(self.list_state.selected().unwrap_or(0));
+        if full_key.is_none() {
+            self.status_message = Some("No key selected - there are no keys here".to_string());
+        }
+        full_key
+    }
+
+
+    // Whether writes are allowed, saying why not when they aren't, so nothing is started in
+    // read-only mode that couldn't be saved
+    fn writable(&mut self) -> bool {
+        if let Err(e) = self.app.writable() {
+            self.status_message = Some(e.to_string());
+            return false;
+        }
+        true
+    }
+
+
+    fn start_editing(&mut self) {
+        if !self.writable() {
+            return;
+        }
+        let Some(full_key) = self.selected_key() else { return };
+        let editor = match self.app.value_of(&full_key) {
+            Ok(Some(value)) => {
+                let encoding = self.app.encoding_of(&full_key);
+                Editor::new(full_key, value, encoding)
+            }
+            Ok(None) => {
+                self.status_message = Some(format!("{} has no value to edit", full_key));
+                return;
+            }
+            Err(e) => Err(e),
+        };
+        match editor {
+            Ok(editor) => {
+                if let Some(encoding) = editor.encoding {
+                    self.status_message = Some(format!("Editing as {} text - saving encodes it back to {}", encoding.name(), encoding.name()));
+                }
+                self.editor = Some(editor);
+            }
+            Err(e) => self.status_message = Some(format!("{:#}", e)),
+        }
+    }
+
+
+    // Added to the status after a write, so --verify-writes' read back is visible
+    fn verified(&self) -> &'static str {
+        if self.app.verify_writes { ", read back and verified" } else { "" }
+    }
+
+    // Switch every size shown between exact bytes and KiB / MiB, and remember the choice
+    fn toggle_exact_sizes(&mut self) {
+        self.ui_state.exact_sizes = !self.ui_state.exact_sizes;
+        let shown = if self.ui_state.exact_sizes { "Showing sizes in exact bytes" } else { "Showing sizes in KiB / MiB" };
+        self.status_message = Some(match self.ui_state.save() {
+            Ok(()) => shown.to_string(),
+            Err(e) => format!("{}, for this session only - {:#}", shown, e),
+        });
+    }
+
+    // Step the selected value through the text encodings of encoding::CYCLE, then back to
+    // its tree's
+    fn cycle_encoding(&mut self) {
+        let Some(full_key) = self.selected_key() else { return };
+        let next = match self.app.value_encodings.get(&full_key) {
+            Some(current) => encoding::CYCLE.iter().position(|encoding| encoding == current).and_then(|index| encoding::CYCLE.get(index + 1)),
+            None => encoding::CYCLE.first(),
+        };
+        match next {
+            Some(&encoding) => {
+                self.app.value_encodings.insert(full_key, encoding);
+                self.status_message = Some(format!("Showing the value as {} - U tries the next encoding", encoding.name()));
+            }
+            None => {
+                self.app.value_encodings.remove(&full_key);
+                let encoding = self.app.encoding.map_or("UTF-8", |encoding| encoding.name());
+                self.status_message = Some(format!("Back to the tree's encoding ({})", encoding));
+            }
+        }
+        self.scroll_state = 0;
+    }
+
+
+    fn start_map_editing(&mut self) {
+        if !self.writable() {
+            return;
+        }
+        let Some(full_key) = self.selected_key() else { return };
+        let map_editor = match self.app.value_of(&full_key) {
+            Ok(Some(value)) => MapEditor::new(full_key, value),
+            Ok(None) => {
+                self.status_message = Some(format!("{} has no value to edit", full_key));
+                return;
+            }
+            Err(e) => Err(e),
+        };
+        match map_editor {
+            Ok(map_editor) => self.map_editor = Some(map_editor),
+            Err(e) => self.status_message = Some(format!("{:#}", e)),
+        }
+    }
+
+
+    fn handle_map_editor_input(&mut self, key: KeyEvent, running: &AtomicBool) -> Result<()> {
+        let Some(map_editor) = &mut self.map_editor else { return Ok(()) };
+        if let Some(edit) = &mut map_editor.edit {
+            match key.code {
+                KeyCode::Char(c) => edit.buffer.push(c),
+                KeyCode::Backspace => {
+                    edit.buffer.pop();
+                }
+                KeyCode::Enter => {
+                    if let Err(e) = map_editor.finish_edit() {
+                        self.status_message = Some(format!("{:#}", e));
+                    }
+                }
+                KeyCode::Esc => map_editor.edit = None,
+                _ => {}
+            }
+            return Ok(());
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('s') if ctrl => self.save_map_editor(),
+            KeyCode::Up | KeyCode::Char('k') => map_editor.selected = map_editor.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                map_editor.selected = (map_editor.selected + 1).min(map_editor.fields.len().saturating_sub(1));
+            }
+            KeyCode::Enter => map_editor.start_edit(Target::Value),
+            KeyCode::Char('r') => map_editor.start_edit(Target::Name),
+            KeyCode::Char('n') => map_editor.start_edit(Target::NewField),
+            KeyCode::Char('d') => map_editor.remove_selected(),
+            KeyCode::Esc => self.leave(Leave::CancelEdit, running),
+            _ => {}
+        }
+        Ok(())
+    }
+
+
+    fn save_map_editor(&mut self) {
+        let Some(map_editor) = &self.map_editor else { return };
+        let value = match map_editor.serialize(&self.config.pretty) {
+            Ok(value) => value,
+            Err(e) => {
+                self.status_message = Some(format!("Not saved: {:#}", e));
+                return;
+            }
+        };
+        match self.app.save_value(&map_editor.full_key, &map_editor.original, &value) {
+            Ok(true) => {
+                self.status_message = Some(format!("Saved {}{}", map_editor.full_key, self.verified()));
+                self.map_editor = None;
+            }
+            Ok(false) => {
+                self.status_message = Some("Not saved: the value changed since you started editing - esc and f again to reload it".to_string());
+            }
+            Err(e) => self.status_message = Some(format!("Save failed: {}", e)),
+        }
+    }
+
+
+    fn handle_editor_input(&mut self, key: KeyEvent, running: &AtomicBool) -> Result<()> {
+        let Some(editor) = &mut self.editor else { return Ok(()) };
+        if editor.confirm_reload {
+            editor.confirm_reload = false;
+            if key.code == KeyCode::Char('y') {
+                self.reload_editor();
+            }
+            return Ok(());
+        }
+        if editor.confirm_save {
+            editor.confirm_save = false;
+            if key.code == KeyCode::Char('y') {
+                self.save_editor();
+            }
+            return Ok(());
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('s') if ctrl && self.config.review_edits && editor.is_dirty() => editor.confirm_save = true,
+            KeyCode::Char('s') if ctrl => self.save_editor(),
+            KeyCode::Char('d') if ctrl => editor.show_diff = !editor.show_diff,
+            KeyCode::Char('r') if ctrl => {
+                if editor.is_dirty() {
+                    editor.confirm_reload = true;
+                } else {
+                    self.reload_editor();
+                }
+            }
+            KeyCode::Char(c) if !ctrl => editor.insert(c),
+            KeyCode::Enter => editor.insert('\n'),
+            KeyCode::Tab => editor.insert('\t'),
+            KeyCode::Backspace => editor.backspace(),
+            KeyCode::Delete => editor.delete(),
+            KeyCode::Left => editor.move_left(),
+            KeyCode::Right => editor.move_right(),
+            KeyCode::Up => editor.move_lines(-1),
+            KeyCode::Down => editor.move_lines(1),
+            KeyCode::PageUp => editor.move_lines(-(self.page_height.max(2) as isize - 1)),
+            KeyCode::PageDown => editor.move_lines(self.page_height.max(2) as isize - 1),
+            KeyCode::Home if ctrl => editor.move_to(true),
+            KeyCode::End if ctrl => editor.move_to(false),
+            KeyCode::Home => editor.move_to_line_start(),
+            KeyCode::End => editor.move_to_line_end(),
+            KeyCode::Esc => self.leave(Leave::CancelEdit, running),
+            _ => {}
+        }
+        Ok(())
+    }
+
+
+    // The key with edits that haven't been saved, in either editor
+    fn unsaved_edits(&self) -> Option<&str> {
+        match (&self.editor, &self.map_editor) {
+            (Some(editor), _) if editor.is_dirty() => Some(&editor.full_key),
+            (_, Some(map_editor)) if map_editor.is_dirty() => Some(&map_editor.full_key),
+            _ => None,
+        }
+    }
+
+    // Values with edits not yet written to the tree, for the header. Only one editor is open
+    // at a time, so this is 0 or 1 until something can hold several pending writes.
+    fn unsaved_count(&self) -> usize {
+        self.unsaved_edits().is_some() as usize
+    }
+
+
+    // Every way of leaving an edit (cancelling it, quitting) goes through here, so unsaved
+    // edits are saved or discarded on purpose rather than lost
+    fn leave(&mut self, leave: Leave, running: &AtomicBool) {
+        if self.unsaved_edits().is_some() {
+            self.pending_leave = Some(leave);
+        } else {
+            self.finish_leave(leave, running);
+        }
+    }
+
+    fn finish_leave(&mut self, leave: Leave, running: &AtomicBool) {
+        let editing = self.editor.take().is_some() | self.map_editor.take().is_some();
+        match leave {
+            Leave::CancelEdit if editing => self.status_message = Some("Edit cancelled".to_string()),
+            Leave::CancelEdit => {}
+            Leave::Quit => running.store(false, Ordering::SeqCst),
+        }
+    }
+
+    fn save_edits(&mut self) {
+        if self.editor.is_some() {
+            self.save_editor();
+        } else {
+            self.save_map_editor();
+        }
+    }
+
+
+    fn save_editor(&mut self) {
+        let Some(editor) = &self.editor else { return };
+        let value = match editor.encoded() {
+            Ok(value) => value,
+            Err(e) => {
+                self.status_message = Some(format!("Not saved: {:#}", e));
+                return;
+            }
+        };
+        match self.app.save_value(&editor.full_key, &editor.original, &value) {
+            Ok(true) => {
+                self.status_message = Some(match editor.encoding {
+                    Some(encoding) => format!("Saved {}, encoded as {}{}", editor.full_key, encoding.name(), self.verified()),
+                    None => format!("Saved {}{}", editor.full_key, self.verified()),
+                });
+                self.editor = None;
+            }
+            Ok(false) => {
+                self.status_message = Some("Not saved: the value changed since you started editing - ctrl-r reloads it".to_string());
+            }
+            Err(e) => self.status_message = Some(format!("Save failed: {}", e)),
+        }
+    }
+
+
+    // Replace the edit buffer with the value stored now
+    fn reload_editor(&mut self) {
+        let Some(editor) = &mut self.editor else { return };
+        self.status_message = Some(match self.app.value_of(&editor.full_key) {
+            Ok(Some(current)) => match editor.reload(current) {
+                Ok(()) => format!("Reloaded {}", editor.full_key),
+                Err(e) => format!("{:#}", e),
+            },
+            Ok(None) => format!("{} no longer exists", editor.full_key),
+            Err(e) => format!("Reload failed: {}", e),
+        });
+    }
+
+
+    fn handle_prompt_input(&mut self, key: KeyCode) -> Result<()> {
+        let Some(prompt) = &mut self.prompt else { return Ok(()) };
+        match key {
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Esc if matches!(prompt.kind, PromptKind::FilterTrees) => {
+                self.prompt = None;
+                self.filter_trees(None)?;
+            }
+            KeyCode::Esc if matches!(prompt.kind, PromptKind::Search) => {
+                self.prompt = None;
+                self.search_level(None)?;
+            }
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                let Some(prompt) = self.prompt.take() else { return Ok(()) };
+                match prompt.kind {
+                    PromptKind::Search => {
+                        self.search_level(None)?;
+                        self.search_keys(prompt.input);
+                    }
+                    PromptKind::FindInValue if prompt.input.is_empty() => self.value_search = None,
+                    PromptKind::FindInValue => {
+                        self.value_search = Some(ValueSearch { needle: prompt.input, current: 0, rows: vec![], jump: true });
+                    }
+                    PromptKind::ClearTree(tree) if prompt.input == tree => self.clear_tree(&tree)?,
+                    PromptKind::ClearTree(_) => self.status_message = Some("Name didn't match, nothing cleared".to_string()),
+                    PromptKind::FilterTrees => {}
+                    PromptKind::Compare => self.open_compare(prompt.input.trim()),
+                    PromptKind::TemplateKey if prompt.input.is_empty() => {}
+                    PromptKind::TemplateKey => {
+                        self.prompt = Some(Prompt { kind: PromptKind::TemplateValue(prompt.input), input: String::new() });
+                    }
+                    PromptKind::TemplateValue(key) => {
+                        self.prompt = Some(Prompt { kind: PromptKind::TemplateItems(key, prompt.input), input: String::new() });
+                    }
+                    PromptKind::TemplateItems(key, value) => match Template::new(key, value, &prompt.input) {
+                        Ok(template) => self.prompt = Some(Prompt { kind: PromptKind::ConfirmTemplate(template), input: String::new() }),
+                        Err(e) => self.status_message = Some(format!("{:#}", e)),
+                    },
+                    PromptKind::ConfirmTemplate(template) if prompt.input.trim() == "y" => self.write_template(template),
+                    PromptKind::ConfirmTemplate(_) => self.status_message = Some("Nothing written".to_string()),
+                    PromptKind::NewTree if prompt.input.is_empty() => {}
+                    PromptKind::NewTree => self.create_tree(&prompt.input)?,
+                    PromptKind::DropTree(raw_name, name, _) if prompt.input.trim() == "y" => self.drop_tree(&raw_name, &name)?,
+                    PromptKind::DropTree(..) => self.status_message = Some("Nothing deleted".to_string()),
+                    PromptKind::GoToKey => self.go_to_key(&prompt.input)?,
+                    PromptKind::DiffFile => self.diff_with_file(prompt.input.trim()),
+                    PromptKind::CopyRange => match RangeCopy::from_input(prompt.input.trim()) {
+                        Some(form) => self.copy_range(form)?,
+                        None => self.status_message = Some("Nothing copied - c, a or n picks how".to_string()),
+                    },
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        // the tree filter follows the input as it is typed
+        if let Some(Prompt { kind: PromptKind::FilterTrees, input }) = &self.prompt {
+            let filter = (!input.is_empty()).then(|| input.clone());
+            self.filter_trees(filter)?;
+        }
+        // and the key list follows a search, within the current level
+        if let Some(Prompt { kind: PromptKind::Search, input }) = &self.prompt {
+            let needle = input.clone();
+            self.search_level(Some(needle))?;
+        }
+        Ok(())
+    }
+
+
+    // Open the read-only comparison view on this database or the one at `path`
+    fn open_compare(&mut self, path: &str) {
+        let db = if path.is_empty() {
+            self.app.db.clone().map(|db| (db, "this database".to_string()))
+        } else {
+            let path = PathBuf::from(path);
+            // sled would create a database that isn't there
+            if !path.exists() {
+                self.status_message = Some(format!("No database at {}", path.display()));
+                return;
+            }
+            // sled locks a database, so this one can't be opened a second time
+            if path.canonicalize().is_ok_and(|path| path == self.db_path) {
+                self.app.db.clone().map(|db| (db, "this database".to_string()))
+            } else {
+                match db::open(&path) {
+                    Ok(db) => Some((db, path.display().to_string())),
+                    Err(e) => {
+                        self.status_message = Some(format!("{:#}", e));
+                        return;
+                    }
+                }
+            }
+        };
+        let Some((db, source)) = db else { return };
+        match ComparePane::open(db, source, &self.config, self.list_height as usize) {
+            Ok(compare) => {
+                self.compare = Some(compare);
+                self.compare_focused = true;
+            }
+            Err(e) => self.status_message = Some(format!("Could not open the comparison view: {}", e)),
+        }
+    }
+
+
+    // h: hash the selected tree's contents, or from the tree list the whole database's
+    fn fingerprint(&mut self) {
+        if self.task.is_some() {
+            return;
+        }
+        match (&self.view_mode, &self.app.current_tree, &self.app.db) {
+            (ViewMode::Keys, Some(tree), _) => {
+                let tree = tree.clone();
+                let scope = format!("tree {}", String::from_utf8_lossy(&tree.name()));
+                self.task = Some(Task::spawn("Fingerprinting the tree", Some(tree.len()), move |progress| {
+                    Ok(TaskOutput::Fingerprint(scope, fingerprint_tree(&tree, progress)?))
+                }));
+            }
+            (ViewMode::Trees, _, Some(db)) => {
+                let db = db.clone();
+                let scope = format!("database {}", self.db_path.display());
+                self.task = Some(Task::spawn("Fingerprinting the database", None, move |progress| {
+                    Ok(TaskOutput::Fingerprint(scope, fingerprint_database(&db, progress)?))
+                }));
+            }
+            _ => {}
+        }
+    }
+
+
+    // W: write what the list shows - the search or filter's keys, or else the branch - with
+    // the values to a file in the --dump format, to keep what a search found
+    fn export_matches(&mut self) {
+        let Some(tree) = self.app.current_tree.clone() else { return };
+        if self.task.is_some() {
+            return;
+        }
+        let keys = self.app.key_filter.as_ref().map(|filter| filter.keys.clone());
+        let prefix = match &self.app.delimiter {
+            Some(delimiter) if !self.app.current_path.is_empty() && !self.app.flat_paths => {
+                format!("{}{}", self.app.current_path.join(delimiter), delimiter)
+            }
+            _ => String::new(),
+        };
+        if keys.is_none() && prefix.is_empty() {
+            self.status_message = Some("Nothing to narrow the export to - search with / or open a branch first".to_string());
+            return;
+        }
+        let file_name = format!("{}-matches.ndjson", file_stem(&tree.name()));
+        let total = keys.as_ref().map(Vec::len);
+        self.task = Some(Task::spawn("Exporting matching keys", total, move |progress| {
+            let written = std::fs::File::create(&file_name)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| write_dump(&tree, keys.as_deref(), &prefix, &mut std::io::BufWriter::new(file), progress));
+            // a cancelled or failed export leaves no partial file behind
+            if written.is_err() {
+                let _ = std::fs::remove_file(&file_name);
+            }
+            Ok(TaskOutput::Exported(file_name, written?))
+        }));
+    }
+
+
+    // E: every key and value of the tree, the selected one in the tree list, streamed to
+    // <tree>.ndjson in the --dump format
+    fn export_tree(&mut self) -> Result<()> {
+        if self.task.is_some() {
+            return Ok(());
+        }
+        let tree = match self.view_mode {
+            ViewMode::Trees => {
+                let selected = self.list_offset + self.list_state.selected().unwrap_or(0);
+                match (&self.app.db, self.app.sled_trees.get(selected)) {
+                    (Some(db), Some((name, _))) => db.open_tree(name)?,
+                    _ => return Ok(()),
+                }
+            }
+            ViewMode::Keys => {
+                let Some(tree) = self.app.current_tree.clone() else { return Ok(()) };
+                tree
+            }
+        };
+        let file_name = format!("{}.ndjson", file_stem(&tree.name()));
+        let total = Some(tree.len());
+        self.task = Some(Task::spawn("Exporting the tree", total, move |progress| {
+            let written = std::fs::File::create(&file_name)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| write_dump(&tree, None, "", &mut std::io::BufWriter::new(file), progress));
+            if written.is_err() {
+                let _ = std::fs::remove_file(&file_name);
+            }
+            Ok(TaskOutput::Exported(file_name, written?))
+        }));
+        Ok(())
+    }
+
+
+    // <: compare the selected value with a file, line by line when both are text, shown in
+    // the value pane until Esc or the selection moves
+    fn diff_with_file(&mut self, path: &str) {
+        if path.is_empty() {
+            return;
+        }
+        let Some(full_key) = self.selected_key() else { return };
+        let stored = match self.app.value_of(&full_key) {
+            Ok(Some(value)) => value,
+            Ok(None) => {
+                self.status_message = Some(format!("{} has no value to compare", full_key));
+                return;
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Could not read {}: {}", full_key, e));
+                return;
+            }
+        };
+        let file = match std::fs::read(path) {
+            Ok(file) => file,
+            Err(e) => {
+                self.status_message = Some(format!("Could not read {}: {}", path, e));
+                return;
+            }
+        };
+        // both sides in the value's encoding, so a file saved the same way compares as text
+        let encoding = self.app.encoding_of(&full_key);
+        let text = |bytes: &[u8]| match encoding {
+            Some(encoding) => Some(encoding::decode(bytes, encoding)),
+            None => String::from_utf8(bytes.to_vec()).ok(),
+        };
+        let (lines, summary) = match (text(&stored), text(&file)) {
+            (Some(old), Some(new)) => {
+                let diff = diff_lines(&old, &new);
+                let added = diff.iter().filter(|line| matches!(line, DiffLine::Added(_))).count();
+                let removed = diff.iter().filter(|line| matches!(line, DiffLine::Removed(_))).count();
+                let summary = match (added, removed, stored == file) {
+                    (_, _, true) => "identical".to_string(),
+                    (0, 0, false) => "the same text, in different bytes".to_string(),
+                    _ => format!("+{} -{} lines", added, removed),
+                };
+                (Some(diff), summary)
+            }
+            // binary on either side: only where the bytes part
+            _ => {
+                let summary = match stored.iter().zip(&file).position(|(old, new)| old != new) {
+                    None if stored.len() == file.len() => "identical".to_string(),
+                    None => format!("binary, the same up to byte {}", stored.len().min(file.len())),
+                    Some(offset) => format!("binary, first differs at byte {}", offset),
+                };
+                (None, summary)
+            }
+        };
+        self.file_diff = Some(FileDiff { full_key, path: path.to_string(), lines, summary, sizes: (stored.len(), file.len()) });
+    }
+
+
+    // G: start the key template at the branch the list is in
+    fn start_template(&mut self) {
+        if !self.writable() {
+            return;
+        }
+        let input = match &self.app.delimiter {
+            Some(delimiter) if !self.app.current_path.is_empty() => format!("{}{}", self.app.current_path.join(delimiter), delimiter),
+            _ => String::new(),
+        };
+        self.prompt = Some(Prompt { kind: PromptKind::TemplateKey, input });
+    }
+
+    fn write_template(&mut self, template: Template) {
+        if !self.writable() {
+            return;
+        }
+        let Some(tree) = self.app.current_tree.clone() else { return };
+        let total = template.items.len();
+        let verify = self.app.verify_writes;
+        self.task = Some(Task::spawn("Creating keys", Some(total), move |progress| {
+            Ok(TaskOutput::Created(template.write(&tree, verify, progress)?))
+        }));
+    }
+
+
+    fn search_level(&mut self, needle: Option<String>) -> Result<()> {
+        self.app.set_key_search(needle);
+        self.list_offset = 0;
+        self.update_list()?;
+        self.list_state.select(Some(0));
+        Ok(())
+    }
+
+
+    fn filter_trees(&mut self, filter: Option<String>) -> Result<()> {
+        self.app.tree_filter = filter;
+        self.app.refresh_trees()?;
+        self.list_offset = 0;
+        self.list_state.select(Some(0));
+        Ok(())
+    }
+
+
+    fn clear_tree(&mut self, tree: &str) -> Result<()> {
+        self.baseline_changes = None;
+        self.status_message = Some(match self.app.clear_tree() {
+            Ok(count) => format!("Removed {} keys from {}{}", count, tree, self.verified()),
+            Err(e) => format!("Clear failed: {}", e),
+        });
+        self.list_offset = 0;
+        self.update_list()?;
+        self.list_state.select(Some(0));
+        Ok(())
+    }
+
+
+    fn create_tree(&mut self, name: &str) -> Result<()> {
+        match self.app.create_tree(name) {
+            Ok(()) => {
+                self.status_message = Some(format!("Created tree {}{}", name, self.verified()));
+                // select it, if the filters and --max-trees list it
+                let index = self.app.sled_trees.iter().position(|(raw, _)| raw.as_slice() == name.as_bytes());
+                self.clamp_tree_selection(index)?;
+            }
+            Err(e) => self.status_message = Some(format!("Could not create tree {}: {}", name, e)),
+        }
+        Ok(())
+    }
+
+
+    // d in the tree list: ask before dropping the selected tree, with its key count
+    fn confirm_drop_tree(&mut self) {
+        let index = self.list_offset + self.list_state.selected().unwrap_or(0);
+        let (Some(db), Some((raw_name, name))) = (&self.app.db, self.app.sled_trees.get(index)) else { return };
+        if raw_name.as_slice() == DEFAULT_TREE {
+            self.status_message = Some("sled's default tree can't be deleted".to_string());
+            return;
+        }
+        match db.open_tree(raw_name) {
+            Ok(tree) => {
+                let kind = PromptKind::DropTree(raw_name.clone(), name.clone(), tree.len());
+                self.prompt = Some(Prompt { kind, input: String::new() });
+            }
+            Err(e) => self.status_message = Some(format!("Could not open tree {}: {}", name, e)),
+        }
+    }
+
+
+    fn drop_tree(&mut self, raw_name: &[u8], name: &str) -> Result<()> {
+        let index = self.list_offset + self.list_state.selected().unwrap_or(0);
+        self.status_message = Some(match self.app.drop_tree(raw_name) {
+            Ok(()) => format!("Deleted tree {}{}", name, self.verified()),
+            Err(e) => format!("Could not delete tree {}: {}", name, e),
+        });
+        self.clamp_tree_selection(Some(index))
+    }
+
+
+    // Keep the tree list's window and selection inside the list after it changed length,
+    // selecting `index` (or the nearest tree to it) when given
+    fn clamp_tree_selection(&mut self, index: Option<usize>) -> Result<()> {
+        let count = self.app.sled_trees.len();
+        let index = index.unwrap_or(self.list_offset + self.list_state.selected().unwrap_or(0));
+        self.list_offset = self.list_offset.min(count.saturating_sub(self.list_height as usize));
+        self.move_selection_to(index.min(count.saturating_sub(1)))
+    }
+
+
+    fn search_keys(&mut self, needle: String) {
+        let Some(tree) = self.app.current_tree.clone() else { return };
+        if needle.is_empty() {
+            return;
+        }
+        let total = self.app.delimiter.is_none().then(|| tree.len());
+        self.task = Some(Task::spawn("Searching keys", total, move |progress| {
+            let matches = search_keys(&tree, &needle, progress)?;
+            Ok(TaskOutput::Search(needle, matches))
+        }));
+    }
+
+
+    // Pasted text goes where typing would: into the editor or prompt being typed in, and
+    // otherwise it's taken as a key to go to
+    fn handle_paste(&mut self, text: &str) -> Result<()> {
+        let text = text.replace("\r\n", "\n");
+        if let Some(editor) = &mut self.editor {
+            editor.insert_str(&text);
+        } else if let Some(edit) = self.map_editor.as_mut().and_then(|map_editor| map_editor.edit.as_mut()) {
+            edit.buffer.push_str(text.trim_end_matches('\n'));
+        } else if let Some(prompt) = &mut self.prompt {
+            prompt.input.push_str(text.trim_end_matches('\n'));
+        } else if self.show_help || self.info_popup.is_some() || self.value_types.is_some() || self.shared_values.is_some()
+            || self.task.is_some() || self.compare_focused {
+            // nothing to paste into
+        } else if matches!(self.view_mode, ViewMode::Keys) {
+            self.go_to_key(&text)?;
+        } else {
+            self.status_message = Some("Open a tree to go to a pasted key in it".to_string());
+        }
+        Ok(())
+    }
 
+    // Go to a key typed at g or pasted: in delimiter mode into the branch holding it, with
+    // it selected. A trailing delimiter names the branch itself.
+    fn go_to_key(&mut self, key: &str) -> Result<()> {
+        let key = key.trim();
+        let key = match &self.app.delimiter {
+            Some(delimiter) if key.len() > delimiter.len() => key.strip_suffix(delimiter.as_str()).unwrap_or(key),
+            _ => key,
+        };
+        if key.is_empty() {
+            return Ok(());
+        }
+        match self.app.reveal_key(key)? {
+            Some(position) => {
+                self.focused_pane = Pane::List;
+                self.list_offset = 0;
+                self.update_list()?;
+                self.move_selection_to(position)?;
+            }
+            None => {
+                let tree = self.app.current_tree.as_ref().map(|tree| String::from_utf8_lossy(&tree.name()).to_string());
+                self.status_message = Some(format!("No key {} in {}", key, tree.unwrap_or_default()));
+            }
+        }
+        Ok(())
+    }
+
+
+    // Leave the filtered list for the selected key's own place in the tree, selecting it
+    fn reveal_selected_key(&mut self) -> Result<()> {
+        let Some(full_key) = self.app.full_key(self.list_state.selected().unwrap_or(0)) else {
+            return Ok(());
+        };
+        match self.app.reveal_key(&full_key)? {
+            Some(position) => {
+                self.list_offset = 0;
+                self.update_list()?;
+                self.move_selection_to(position)?;
+            }
+            None => self.status_message = Some(format!("{} no longer exists", full_key)),
+        }
+        Ok(())
+    }
+
+
+    // Skip the tree list at startup: open the --tree tree, or the one the startup_tree
+    // setting picks. Stays on the tree list when there's no such tree.
+    fn open_startup_tree(&mut self, name: Option<Vec<u8>>) -> Result<()> {
+        let Some(db) = &self.app.db else { return Ok(()) };
+        let trees = &self.app.sled_trees;
+        let index = match (&name, self.config.startup_tree) {
+            (Some(name), _) => trees.iter().position(|(raw, _)| raw == name),
+            (None, StartupTree::List) => return Ok(()),
+            (None, StartupTree::FirstNamed) => trees.iter().position(|(raw, _)| raw.as_slice() != DEFAULT_TREE),
+            (None, StartupTree::FirstNonEmpty) => {
+                let mut found = None;
+                for (index, (raw, _)) in trees.iter().enumerate() {
+                    if !db.open_tree(raw)?.is_empty() {
+                        found = Some(index);
+                        break;
+                    }
+                }
+                found
+            }
+        };
+        match (index, name) {
+            (Some(index), _) => self.open_tree(index)?,
+            (None, Some(name)) => {
+                self.status_message = Some(format!("No tree {} in the tree list", String::from_utf8_lossy(&name)));
+            }
+            (None, None) => {}
+        }
+        Ok(())
+    }
+
+
+    // Open a tree from the tree list at its top level
+    fn open_tree(&mut self, index: usize) -> Result<()> {
+        self.view_mode = ViewMode::Keys;
+        self.app.select_tree(index)?;
+        self.list_offset = 0;
+        self.update_list()?;
+        self.list_state.select(Some(0));
+        if let Some(warning) = self.unsplittable_warning() {
+            self.status_message = Some(warning);
+        }
+        Ok(())
+    }
+
+
+    fn unsplittable_warning(&self) -> Option<String> {
+        let (count, example) = self.app.unsplittable_keys.as_ref()?;
+        let delimiter = self.app.delimiter.as_deref()?;
+        Some(format!(
+            "{} keys don't split cleanly on {}, e.g. {:?} - D picks another delimiter, max_depth stops splitting deeper",
+            count, delimiter, example
+        ))
+    }
+
+
+    // Override the tree's delimiter with the next candidate, then none at all
+    fn cycle_delimiter(&mut self) -> Result<()> {
+        let next = match &self.app.delimiter {
+            None => Some(DELIMITERS[0]),
+            Some(current) => DELIMITERS.iter()
+                .position(|delimiter| delimiter == current)
+                .and_then(|index| DELIMITERS.get(index + 1))
+                .copied(),
+        };
+        self.app.set_delimiter(next.map(String::from))?;
+        self.app.delimiter_detected = false;
+        self.baseline_changes = None;
+        self.focused_pane = Pane::List;
+        self.list_offset = 0;
+        self.update_list()?;
+        self.list_state.select(Some(0));
+        self.status_message = Some(match next {
+            Some(delimiter) => self.unsplittable_warning().unwrap_or_else(|| format!("Splitting keys on {}", delimiter)),
+            None => "No delimiter - listing whole keys".to_string(),
+        });
+        Ok(())
+    }
+
+
+    // Bookmark the selected key, or the selected tree in the tree list
+    fn set_mark(&mut self, letter: char) {
+        let selected = self.list_state.selected().unwrap_or(0);
+        let mark = match self.view_mode {
+            ViewMode::Trees => {
+                let Some((tree, tree_name)) = self.app.sled_trees.get(self.list_offset + selected) else { return };
+                Mark { tree: tree.clone(), tree_name: tree_name.clone(), full_key: None }
+            }
+            ViewMode::Keys => {
+                let Some(current_tree) = &self.app.current_tree else { return };
+                let tree = current_tree.name().to_vec();
+                let tree_name = String::from_utf8_lossy(&tree).into_owned();
+                Mark { tree, tree_name, full_key: self.app.full_key(selected) }
+            }
+        };
+        self.status_message = Some(format!("Marked {} as '{}", mark_location(&mark), letter));
+        self.marks.insert(letter, mark);
+    }
+
+
+    // Go back to a bookmarked tree and select its key, opening the branch it lives in
+    fn jump_to_mark(&mut self, letter: char) -> Result<()> {
+        let Some(mark) = self.marks.get(&letter).cloned() else {
+            self.status_message = Some(format!("No mark '{} - m{} sets it", letter, letter));
+            return Ok(());
+        };
+        let Some(index) = self.app.sled_trees.iter().position(|(tree, _)| *tree == mark.tree) else {
+            let exists = self.app.db.as_ref().is_some_and(|db| db.tree_names().iter().any(|name| *name == mark.tree));
+            self.status_message = Some(if exists && self.app.matching_trees > self.app.sled_trees.len() {
+                format!("Tree {} isn't loaded yet - + in the tree list loads more", mark.tree_name)
+            } else {
+                format!("Tree {} is no longer listed", mark.tree_name)
+            });
+            return Ok(());
+        };
+        self.focused_pane = Pane::List;
+        self.open_tree(index)?;
+        if let Some(full_key) = &mark.full_key {
+            match self.app.reveal_key(full_key)? {
+                Some(position) => {
+                    self.list_offset = 0;
+                    self.update_list()?;
+                    self.move_selection_to(position)?;
+                }
+                None => self.status_message = Some(format!("{} no longer exists", full_key)),
+            }
+        }
+        Ok(())
+    }
+
+
+    fn show_marks(&mut self) {
+        let lines = if self.marks.is_empty() {
+            vec!["No marks yet - m<letter> bookmarks the selected key".to_string()]
+        } else {
+            self.marks.iter().map(|(letter, mark)| format!("'{}  {}", letter, mark_location(mark))).collect()
+        };
+        self.info_popup = Some(InfoPopup { title: "Marks - '<letter> jumps".to_string(), lines });
+    }
+
+
+    // Replace (or clear, with None) the filter on the key list and go back to its top
+    fn apply_key_filter(&mut self, filter: Option<KeyFilter>) -> Result<()> {
+        self.app.set_key_filter(filter);
+        self.list_offset = 0;
+        self.update_list()?;
+        self.list_state.select(Some(0));
+        Ok(())
+    }
+
+
+    // Leave the current tree and go back to tree selection, assume at least Default tree available
+    fn go_to_tree_list(&mut self) {
+        self.view_mode = ViewMode::Trees;
+        self.list_offset = 0;
+        self.app.total_keys = 0;
+        self.app.current_tree = None;
+        self.app.current_path.clear();
+        self.app.key_filter = None;
+        self.baseline_changes = None;
+        self.list_state.select(Some(0));
+    }
+
+
+    // Write the current tree's key hierarchy to a file in the working directory
+    fn export_key_tree(&mut self, format: KeyTreeFormat) {
+        let tree_name = match &self.app.current_tree {
+            Some(tree) => file_stem(&tree.name()),
+            None => return,
+        };
+        let extension = match format {
+            KeyTreeFormat::Text => "txt",
+            KeyTreeFormat::Json => "json",
+        };
+        let file_name = format!("{}-keys.{}", tree_name, extension);
+        // written to memory first, so a tree that can't be exported leaves no empty file behind
+        let mut outline = vec![];
+        let result = self.app.write_key_tree(&mut outline, format)
+            .and_then(|count| {
+                std::fs::write(&file_name, &outline)?;
+                Ok(count)
+            });
+        self.status_message = Some(match result {
+            Ok(count) => format!("Wrote {} keys to {}", count, file_name),
+            Err(e) => format!("Export failed: {}", e),
+        });
+    }
+
+
+    // Re-read the trees or the current tree's keys, keeping the selection and scroll
+    // position where they still fit
+    fn refresh_view(&mut self) -> Result<()> {
+        match self.view_mode {
+            ViewMode::Trees => {
+                self.app.refresh_trees()?;
+                let last = self.app.sled_trees.len().saturating_sub(1);
+                self.list_offset = self.list_offset.min(self.app.sled_trees.len().saturating_sub(self.list_height as usize));
+                let selected = self.list_state.selected().unwrap_or(0).min(last - self.list_offset);
+                self.list_state.select(Some(selected));
+            }
+            ViewMode::Keys => {
+                let selected = self.app.full_key(self.list_state.selected().unwrap_or(0));
+                self.app.reload_tree()?;
+                // the branch being viewed may have gone with its keys
+                if !self.app.trim_current_path() {
+                    self.app.refresh_total_keys();
+                    self.list_offset = 0;
+                }
+                self.list_offset = self.list_offset.min(self.app.total_keys.saturating_sub(self.list_height as usize));
+                self.update_list()?;
+                let last = self.app.current_key_range.keys.len().saturating_sub(1);
+                self.list_state.select(Some(self.list_state.selected().unwrap_or(0).min(last)));
+                // keys added or removed above the selected one shift it, so find it again.
+                // A search or filter lists fixed keys, and --disk-index rebuilds in the background.
+                let unfiltered = self.app.key_filter.is_none() && self.app.key_search.is_none() && !self.app.use_disk_index;
+                let moved = selected.filter(|full_key| unfiltered && self.app.full_key(self.list_state.selected().unwrap_or(0)).as_ref() != Some(full_key));
+                if let Some(full_key) = moved {
+                    if let Some(position) = self.app.reveal_key(&full_key)? {
+                        self.move_selection_to(position)?;
+                    }
+                }
+            }
+        }
+        self.last_refresh = Instant::now();
+        Ok(())
+    }
+
+
+    // s: list keys largest first or smallest first, keeping the same key selected
+    fn toggle_descending(&mut self) -> Result<()> {
+        let selected = self.list_offset + self.list_state.selected().unwrap_or(0);
+        let full_key = self.app.full_key(self.list_state.selected().unwrap_or(0));
+        self.app.set_descending(!self.app.descending);
+        // turning the list round mirrors every position, apart from the flat path view,
+        // where only siblings swap round and the key has to be looked up
+        let flat_paths = self.app.flat_paths && self.app.delimiter.is_some();
+        let position = match full_key {
+            Some(full_key) if flat_paths && self.app.key_filter.is_none() && self.app.key_search.is_none() => {
+                self.app.reveal_key(&full_key)?.unwrap_or(0)
+            }
+            _ if flat_paths && self.app.key_search.is_some() => 0,
+            _ => self.app.total_keys.saturating_sub(1 + selected),
+        };
+        self.list_offset = 0;
+        self.update_list()?;
+        self.move_selection_to(position)?;
+        self.status_message = Some(match self.app.descending {
+            true => "Keys listed largest first".to_string(),
+            false => "Keys listed smallest first".to_string(),
+        });
+        Ok(())
+    }
+
+
+    // r: re-read the view, saying what it now holds and whether the branch in view went
+    fn reload(&mut self) -> Result<()> {
+        let path = self.app.current_path.clone();
+        self.refresh_view()?;
+        self.status_message = Some(match self.view_mode {
+            ViewMode::Trees => format!("Reloaded the tree list: {} trees", self.app.sled_trees.len()),
+            ViewMode::Keys if self.app.current_path != path => {
+                format!("Reloaded - /{} no longer has any keys, moved up to /{}", path.join("/"), self.app.current_path.join("/"))
+            }
+            ViewMode::Keys => format!("Reloaded: {} keys here", self.app.total_keys),
+        });
+        Ok(())
+    }
+
+
+    // Refresh on the configured interval, but not under a popup, prompt or running task
+    fn auto_refresh(&mut self) -> Result<()> {
+        let Some(secs) = self.config.auto_refresh_secs else { return Ok(()) };
+        let busy = self.show_help
+            || self.info_popup.is_some()
+            || self.value_types.is_some()
+            || self.shared_values.is_some()
+            || self.task.is_some()
+            || self.prompt.is_some()
+            || self.editor.is_some()
+            || self.map_editor.is_some()
+            || self.delete_armed.is_some()
+            || self.pending_count.is_some()
+            || self.pending_mark.is_some();
+        if busy || self.last_refresh.elapsed() < Duration::from_secs(secs.max(1)) {
+            return Ok(());
+        }
+        self.refresh_view()
+    }
+
+
+    // The selection is a branch without a value of its own, and the value pane isn't
+    // showing something else (a pin, a concatenation) in its place
+    fn on_bare_branch(&self) -> bool {
+        if !matches!(self.view_mode, ViewMode::Keys) || self.pinned.is_some() || self.concat {
+            return false;
+        }
+        let index = self.list_state.selected().unwrap_or(0);
+        let is_branch = self.app.delimiter.is_some()
+            && self.app.current_key_range.keys.get(index).is_some_and(|entry| entry.has_children);
+        is_branch && self.app.full_key(index).is_some_and(|key| matches!(self.app.value_of(&key), Ok(None)))
+    }
+
+
+    // Go one level down into the branch at a row of the list
+    fn enter_branch(&mut self, index: usize) -> Result<()> {
+        self.app.select_key(index)?;
+        self.list_offset = 0;
+        self.update_list()?;
+        self.list_state.select(Some(0));
+        self.scroll_state = 0;
+        self.horizontal_scroll = 0;
+        self.value_search = None;
+        Ok(())
+    }
+
+
+    // Read the key window again when the list changed size (a resize, or * switching to
+    // columns), keeping the same key selected
+    fn follow_list_height(&mut self) -> Result<()> {
+        if !matches!(self.view_mode, ViewMode::Keys) || self.list_height == self.window_height {
+            return Ok(());
+        }
+        let height = (self.list_height as usize).max(1);
+        let absolute = self.list_offset + self.list_state.selected().unwrap_or(0);
+        if absolute >= self.list_offset + height {
+            self.list_offset = absolute + 1 - height;
+        }
+        self.update_list()?;
+        let last = self.app.current_key_range.keys.len().saturating_sub(1);
+        self.list_state.select(Some((absolute - self.list_offset).min(last)));
+        Ok(())
+    }
+
+
+    fn update_list(&mut self) -> Result<()> {
+        // Get just enough items to fill the visible area
+        self.app.set_key_range(self.list_offset, self.list_height as usize)?;
+        self.window_height = self.list_height;
+
+        // A concurrent writer can leave total_keys larger than what iteration actually returns,
+        // which would show empty rows and let the window scroll past the end
+        let expected = self.app.total_keys.saturating_sub(self.list_offset).min(self.list_height as usize);
+        if self.app.current_key_range.keys.len() < expected {
+            self.app.refresh_total_keys();
+            let max_offset = self.app.total_keys.saturating_sub(self.list_height as usize);
+            if self.list_offset > max_offset {
+                self.list_offset = max_offset;
+                self.app.set_key_range(self.list_offset, self.list_height as usize)?;
+            }
+            let last = self.app.current_key_range.keys.len().saturating_sub(1);
+            let selected = self.list_state.selected().unwrap_or(0).min(last);
+            self.list_state.select(Some(selected));
+            self.status_message = Some("Data changed, refreshed".to_string());
+        }
+        Ok(())
+    }
+
+
+}    
 
+
+impl Drop for TuiApp {
+    fn drop(&mut self) {
+        if self.terminal.is_terminal() {
+            let _ = crossterm::execute!(std::io::stdout(), DisableBracketedPaste, DisableMouseCapture);
+            ratatui::restore();
+        }
+    }
+}
+
+
+// The read-only comparison view: its own tree or key list over the selected value
+fn draw_compare(frame: &mut Frame, area: Rect, compare: &mut ComparePane, focused: bool, pretty: bool, config: &Config) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(area);
+    match compare.view_mode {
+        ViewMode::Trees => draw_tree_list(frame, chunks[0], &compare.app, &mut compare.list_state, compare.list_offset, &config.highlight),
+        ViewMode::Keys => draw_key_list(
+            frame,
+            chunks[0],
+            &compare.app.current_key_range.keys,
+            &mut compare.list_state,
+            compare.app.total_keys,
+            None,
+            compare.app.current_tree.as_ref(),
+            None,
+            &[],
+            &[],
+            None,
+            1,
+            &config.highlight,
+        ),
+    }
+    let location = match (&compare.app.current_tree, compare.app.current_path.is_empty()) {
+        (Some(tree), true) => String::from_utf8_lossy(&tree.name()).to_string(),
+        (Some(tree), false) => format!("{} / {}", String::from_utf8_lossy(&tree.name()), compare.app.current_path.join("/")),
+        (None, _) => "trees".to_string(),
+    };
+    let (label, text) = match compare.selected_value() {
+        Some(value) => {
+            let rendered = compare.app.render_value(&value, pretty, &config.pretty);
+            (rendered.label.unwrap_or_default(), rendered.text)
+        }
+        None => ("", String::new()),
+    };
+    let value_widget = Paragraph::new(text)
+        .block(Block::default()
+            .title(format!("{}: {} {}", compare.source, location, label))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(if focused { Color::Blue } else { Color::White })))
+        .wrap(ratatui::widgets::Wrap { trim: false })
+        .scroll((compare.scroll, 0));
+    frame.render_widget(value_widget, chunks[1]);
+}
+
+
+// Draws the window of trees starting at list_offset; list_state's selection is relative to it
 fn draw_tree_list(
     frame: &mut Frame,
     area: Rect,
-    trees: &Vec<String>,
+    app: &App,
     list_state: &mut ListState,
-    total_keys: usize,
+    list_offset: usize,
+    highlight: &Highlight,
 ) {
+    let trees = &app.sled_trees;
     if !trees.is_empty() {
+        // names that collide after lossy decoding get a #n suffix to tell them apart.
+        // Unless --no-sort-trees, the list is sorted by display name so duplicates are adjacent.
+        let height = area.height.saturating_sub(2) as usize;
         let items: Vec<ListItem> = trees
             .iter()
-            .map(|entry| {
-                ListItem::new(entry.clone())
+            .enumerate()
+            .skip(list_offset)
+            .take(height)
+            .map(|(i, (_, name))| {
+                let (before, after) = if app.sort_trees {
+                    (trees[..i].iter().rev().take_while(|other| other.1 == *name).count(),
+                     trees[i + 1..].iter().take_while(|other| other.1 == *name).count())
+                } else {
+                    (trees[..i].iter().filter(|other| other.1 == *name).count(),
+                     trees[i + 1..].iter().filter(|other| other.1 == *name).count())
+                };
+                if before + after > 0 {
+                    ListItem::new(format!("{} #{}", name, before + 1))
+                } else {
+                    ListItem::new(name.clone())
+                }
             })
             .collect();
 
+        let filter = match &app.tree_filter {
+            Some(filter) => format!(" matching \"{}\"", filter),
+            None if app.is_empty_database() => " - empty database".to_string(),
+            None => String::new(),
+        };
+        let selected = list_offset + list_state.selected().unwrap_or(0) + 1;
+        let unloaded = if app.matching_trees > trees.len() {
+            format!(" (of {} - + loads more)", app.matching_trees)
+        } else {
+            String::new()
+        };
         let trees_list = List::new(items)
             .block(Block::default()
-                .title(format!(" {} Keys ", total_keys))
+                .title(format!(" {} / {} Trees{}{} ", selected, trees.len(), unloaded, filter))
                 .borders(Borders::ALL))
-            .highlight_style(Style::default().reversed());
+            .highlight_style(highlight.style())
+            .highlight_symbol(highlight.symbol.as_str());
         
         frame.render_stateful_widget(trees_list, area, list_state);
+    } else if let Some(filter) = &app.tree_filter {
+        frame.render_widget(
+            Paragraph::new(format!("No trees match \"{}\" - esc clears the filter", filter)),
+            area
+        );
     } else {
         frame.render_widget(
             Paragraph::new("No SledDB trees found!"),
@@ -476,31 +3662,87 @@ fn draw_tree_list(
 }
 
 
+#[allow(clippy::too_many_arguments)]
 fn draw_key_list(
     frame: &mut Frame,
     area: Rect,
-    keys: &Vec<KeyEntry>,
+    keys: &[KeyEntry],
     list_state: &mut ListState,
     total_keys: usize,
+    position: Option<usize>, // of the selection, when shown
     current_tree: Option<&sled::Tree>,
+    filter_label: Option<&str>,
+    changes: &[Option<Change>], // baseline differences, by row
+    notes: &[Option<String>],    // shown after each row: how long ago it was modified, its owner
+    range: Option<Range<usize>>, // rows inside a V range
+    columns: usize,              // more than one lays the rows out newspaper style
+    highlight: &Highlight,
 ) {
     if !keys.is_empty() {
         let items: Vec<ListItem> = keys
             .iter()
-            .map(|entry| {
-                if entry.has_children {
-                    ListItem::new(format!("{} +", entry.key))
-                } else {
-                    ListItem::new(entry.key.clone())
+            .enumerate()
+            .map(|(row, entry)| {
+                // branches show how many entries sit directly under them
+                let label = match (entry.has_children, entry.child_count) {
+                    (false, _) => entry.key.clone(),
+                    (true, 0) => format!("{} +", entry.key),
+                    (true, count) if count > MAX_CHILD_COUNT => format!("{} ({}+)", entry.key, MAX_CHILD_COUNT),
+                    (true, count) => format!("{} ({})", entry.key, count),
+                };
+                let item = match notes.get(row).and_then(Option::as_deref) {
+                    Some(notes) => ListItem::new(format!("{}  {}", label, notes)),
+                    None => ListItem::new(label),
+                };
+                let item = match range.as_ref().is_some_and(|range| range.contains(&row)) {
+                    true => item.bg(Color::DarkGray),
+                    false => item,
+                };
+                match changes.get(row).copied().flatten() {
+                    Some(Change::Added) => item.fg(Color::Green),
+                    Some(Change::Removed) => item.fg(Color::Red),
+                    Some(Change::Changed) => item.fg(Color::Yellow),
+                    None => item,
                 }
             })
             .collect();
 
+        let count = match position {
+            Some(position) => format!("{} / {}", position, total_keys),
+            None => total_keys.to_string(),
+        };
+        let title = match filter_label {
+            Some(label) => format!(" {} Keys [{}] ", count, label),
+            None => format!(" {} Keys ", count),
+        };
+        let block = Block::default().title(title).borders(Borders::ALL);
+        if columns > 1 {
+            // down the first column, then the next: one List per column, the selection in
+            // whichever column holds it
+            let rows = area.height.saturating_sub(2).max(1) as usize;
+            let inner = block.inner(area);
+            frame.render_widget(block, area);
+            let column_areas = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Ratio(1, columns as u32); columns])
+                .split(inner);
+            let selected = list_state.selected().unwrap_or(0);
+            let mut items = items.into_iter();
+            for (column, column_area) in column_areas.iter().enumerate() {
+                let column_items: Vec<ListItem> = items.by_ref().take(rows).collect();
+                let mut state = ListState::default();
+                state.select((selected / rows == column).then_some(selected % rows));
+                let column_list = List::new(column_items)
+                    .highlight_style(highlight.style())
+                    .highlight_symbol(highlight.symbol.as_str());
+                frame.render_stateful_widget(column_list, *column_area, &mut state);
+            }
+            return;
+        }
         let keys_list = List::new(items)
-            .block(Block::default()
-                .title(format!(" {} Keys ", total_keys))
-                .borders(Borders::ALL))
-            .highlight_style(Style::default().reversed());
+            .block(block)
+            .highlight_style(highlight.style())
+            .highlight_symbol(highlight.symbol.as_str());
         
         frame.render_stateful_widget(keys_list, area, list_state);
     } else {
@@ -511,7 +3753,15 @@ fn draw_key_list(
         };
 
         frame.render_widget(
-            Paragraph::new(format!("No Keys found in tree {}", tree_name)),
+            Paragraph::new(match filter_label {
+                Some(label) => format!("No keys match {}", label),
+                None => format!("Tree {} has no keys", tree_name),
+            })
+                .wrap(ratatui::widgets::Wrap { trim: false })
+                .block(Block::default().title(match filter_label {
+                    Some(label) => format!(" 0 Keys [{}] ", label),
+                    None => " 0 Keys ".to_string(),
+                }).borders(Borders::ALL)),
             area
         );
     }
@@ -519,42 +3769,481 @@ fn draw_key_list(
 
 
 
+// Rows a text takes in the value pane when wrapped. Counted by ratatui's own wrapping, which
+// works in graphemes and display width, so the count matches what is drawn and wide or
+// multi-byte characters near the edge can't throw scrolling out.
 fn calculate_wrapped_lines(text: &str, width: u16) -> usize {
-    let width = width as usize;
-    let mut total_lines = 0;
+    Paragraph::new(text).wrap(ratatui::widgets::Wrap { trim: false }).line_count(width).max(1)
+}
 
-    for line in text.split('\n') {
-        if line.is_empty() {
-            total_lines += 1;
-            continue;
+
+// Pane rows of the matches of `needle` in the value's lines, ignoring ASCII case. With a
+// width, lines wrap as calculate_wrapped_lines counts them, and a match deep in a long line
+// is placed on the row its start wraps to.
+fn match_rows(lines: &[&str], needle: &str, width: Option<u16>) -> Vec<usize> {
+    let needle = needle.to_ascii_lowercase();
+    let mut rows = vec![];
+    let mut row = 0;
+    for line in lines {
+        for (start, _) in line.to_ascii_lowercase().match_indices(&needle) {
+            rows.push(row + width.map_or(0, |width| calculate_wrapped_lines(&line[..start], width) - 1));
         }
+        row += width.map_or(1, |width| calculate_wrapped_lines(line, width));
+    }
+    rows
+}
 
-        let mut remaining = line;
-        while !remaining.is_empty() {
-            total_lines += 1;
-            
-            // Find the last space within the width limit
-            let mut split_at = width;
-            if remaining.len() > width {
-                // Look for a space to break at
-                if let Some(last_space) = remaining[..width].rfind(' ') {
-                    split_at = last_space + 1;
-                }
-            } else {
+
+// Mark every match of `needle` in the pane's text, ignoring ASCII case, with the
+// `current`th match in a colour of its own. The text's own colours are kept around them.
+fn mark_matches(text: Text<'static>, needle: &str, current: usize) -> Text<'static> {
+    let needle = needle.to_ascii_lowercase();
+    let found = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let focused = Style::default().fg(Color::Black).bg(Color::LightCyan);
+    let mut index = 0;
+    let lines = text.lines.into_iter().map(|line| {
+        let whole: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+        let matches: Vec<(usize, usize, Style)> = whole.to_ascii_lowercase().match_indices(&needle)
+            .map(|(start, _)| {
+                index += 1;
+                (start, start + needle.len(), if index - 1 == current { focused } else { found })
+            })
+            .collect();
+        if matches.is_empty() {
+            return line;
+        }
+        let mut spans = vec![];
+        let mut offset = 0;
+        for span in line.spans {
+            let end = offset + span.content.len();
+            let mut cuts: Vec<usize> = matches.iter()
+                .flat_map(|(start, end, _)| [*start, *end])
+                .filter(|cut| *cut > offset && *cut < end)
+                .collect();
+            cuts.push(end);
+            let mut from = offset;
+            for to in cuts {
+                let style = match matches.iter().find(|(start, end, _)| (*start..*end).contains(&from)) {
+                    Some((_, _, style)) => span.style.patch(*style),
+                    None => span.style,
+                };
+                spans.push(Span::styled(span.content[from - offset..to - offset].to_string(), style));
+                from = to;
+            }
+            offset = end;
+        }
+        Line { spans, ..line }
+    });
+    Text::from(lines.collect::<Vec<_>>())
+}
+
+
+// Word-wrap text to `width` display columns, indenting continuation lines past the line's
+// own indentation so wrapped paragraphs stand apart from real line starts
+fn hanging_indent_wrap(text: &str, width: u16) -> String {
+    let width = width as usize;
+    if width == 0 {
+        return text.to_string();
+    }
+    let mut wrapped = Vec::new();
+    for line in text.split('\n') {
+        let leading = line.chars().take_while(|c| *c == ' ').count();
+        // always leaving room for a wide character
+        let indent = " ".repeat((leading + HANGING_INDENT).min(width / 2).min(width.saturating_sub(2)));
+        let chars: Vec<(char, usize)> = line.chars().map(|c| (c, c.width().unwrap_or(0))).collect();
+        let text = |chars: &[(char, usize)]| chars.iter().map(|(c, _)| c).collect::<String>();
+        let mut rest = chars.as_slice();
+        let mut prefix = "";
+        loop {
+            let room = width - prefix.len();
+            let mut used = 0;
+            let fits = rest.iter().take_while(|(_, width)| { used += width; used <= room }).count();
+            if fits == rest.len() {
+                wrapped.push(format!("{}{}", prefix, text(rest)));
                 break;
             }
+            // break after the last space that fits, or mid-word when there is none. A wide
+            // character in a narrower pane still takes a row of its own.
+            let fits = fits.max(1);
+            let split_at = rest[..fits].iter().rposition(|(c, _)| *c == ' ').map_or(fits, |space| space + 1);
+            wrapped.push(format!("{}{}", prefix, text(&rest[..split_at])));
+            rest = &rest[split_at..];
+            prefix = &indent;
+        }
+    }
+    wrapped.join("\n")
+}
 
-            remaining = &remaining[split_at.min(remaining.len())..];
-            
-            // Handle the case where a very long word is wrapped
-            if remaining.len() > width && split_at == width {
-                // No space found, force wrap at width
-                remaining = &remaining[width..];
+
+fn help_lines(config: &Config) -> Vec<String> {
+    let backspace = if config.backspace_leaves_tree {
+        "parent key, or tree list at the root"
+    } else {
+        "parent key (stays at the tree root)"
+    };
+    let mut lines = vec![
+        "↓↑ / j k       move selection or scroll".to_string(),
+        "<count>        repeat the next movement, e.g. 15j".to_string(),
+        "mouse          wheel scrolls the pane under it, click selects a row".to_string(),
+        "enter          select tree / show subkeys".to_string(),
+        format!("backspace      {}", backspace),
+        "esc / t        back to the tree list".to_string(),
+        "tab            switch between key and value panes".to_string(),
+        "pgup pgdn      page up / down".to_string(),
+        "home end       top / bottom of value".to_string(),
+        "home           (key list) back to the tree's top level".to_string(),
+        "/              filter this level as you type, enter searches all keys".to_string(),
+        "/              (tree list) filter trees by name; esc clears".to_string(),
+        "/, n / N       (value pane) find in the value, next / previous match; esc clears".to_string(),
+        "D              split keys on the next delimiter (/ \\ : :: , . - _, none)".to_string(),
+        "v              toggle the flat path view of a delimited tree".to_string(),
+        "w              toggle value wrapping".to_string(),
+        "i              toggle hanging indent of wrapped lines".to_string(),
+        "l              (value pane) toggle line numbers".to_string(),
+        "e              (value pane) edit the value; ctrl-s saves, ctrl-d diffs, ctrl-r reloads".to_string(),
+        "f              (value pane) edit a flat JSON/TOML/YAML map field by field".to_string(),
+        "|              toggle the table view of CSV/TSV values".to_string(),
+        "x              (value pane) toggle a hex dump of the value's bytes".to_string(),
+        "c              (value pane) show the value as text / escaped UTF-8 / base64 / hex".to_string(),
+        "p              toggle pretty-printing of json/toml/yaml/ron/ndjson".to_string(),
+        "z / Z          fold the ndjson record at the top of the value / all records".to_string(),
+        "o / O          export key hierarchy as text / JSON".to_string(),
+        "d d, d y       delete the selected key (d asks, d or y confirms)".to_string(),
+        "C              remove every key from the tree (asks for its name)".to_string(),
+        "G              create many keys from a template, e.g. item/{n} for 1..=100".to_string(),
+        "y              copy the value (value pane) or the full key (list)".to_string(),
+        "S              copy the value as a quoted shell argument".to_string(),
+        "Y              copy the value pretty-printed".to_string(),
+        "!              view the value in $PAGER".to_string(),
+        "P              pin the value pane to the selected key / unpin".to_string(),
+        "X              copy a --dump command extracting the keys in view".to_string(),
+        "V ... V        copy the values of a range of keys, joined / as JSON array / NDJSON".to_string(),
+        "b / B          take a baseline / show keys changed since it".to_string(),
+        "F              toggle the live events feed for the tree".to_string(),
+        "space          peek into the tree or branch under the cursor".to_string(),
+        "a              show branches as their values concatenated".to_string(),
+        "A              list full keys instead of path segments".to_string(),
+        "H              (tree list) show / hide the config's hidden trees".to_string(),
+        "+              (tree list) load more trees, with --max-trees".to_string(),
+        "n              (tree list) create a tree".to_string(),
+        "d              (tree list) delete the selected tree, after asking".to_string(),
+        "=              open a read-only comparison view / switch to and from it".to_string(),
+        "U              show the value in the next text encoding".to_string(),
+        "L              path bar: boxed / one line / hidden".to_string(),
+        "I              taller info bar, for long messages and key hints".to_string(),
+        "r              re-read the tree list or the current tree".to_string(),
+        "s              list keys largest first / smallest first".to_string(),
+        "*              compact mode: keys in columns, no value pane (tab shows it)".to_string(),
+        "<              compare the value with a file".to_string(),
+        "W              write the searched / filtered keys or the branch, with values, to a file".to_string(),
+        "E              write the whole tree (selected in the tree list) to <tree>.ndjson".to_string(),
+        "#              sizes in exact bytes or KiB / MiB (remembered)".to_string(),
+        "R              rebuild the key index after outside changes".to_string(),
+        "T              scan value types and filter keys by type".to_string(),
+        "u              find keys that share a value".to_string(),
+        "h              fingerprint the tree (from the tree list, the database)".to_string(),
+        "m<letter>      bookmark the selected key or tree".to_string(),
+        "'<letter>      jump back to a bookmark".to_string(),
+        "M              list bookmarks".to_string(),
+        "g              go to a key by name; pasting a key goes to it too".to_string(),
+        "?              this help".to_string(),
+        "q              quit".to_string(),
+        String::new(),
+        "backspace_leaves_tree in ~/.config/sledit/config.toml".to_string(),
+        "controls whether backspace at the root leaves the tree".to_string(),
+    ];
+    if !config.keymap.custom.is_empty() {
+        lines.push(String::new());
+        lines.push("from ~/.config/sledit/keys.toml:".to_string());
+        lines.extend(config.keymap.custom.iter().map(|(key, action)| format!("{:<15}{}", key, action)));
+    }
+    lines
+}
+
+
+// Lines of unchanged text kept around each change in the diff view
+const DIFF_CONTEXT: usize = 2;
+
+// A diff as coloured +/- lines, the unchanged ones only around changes
+fn diff_text(diff: &[DiffLine]) -> Vec<Line<'_>> {
+    diff_hunks(diff).into_iter()
+        .map(|line| match line {
+            Some(DiffLine::Added(text)) => Line::from(format!("+ {}", text)).fg(Color::Green),
+            Some(DiffLine::Removed(text)) => Line::from(format!("- {}", text)).fg(Color::Red),
+            Some(DiffLine::Same(text)) => Line::from(format!("  {}", text)).fg(Color::DarkGray),
+            None => Line::from("  ...").fg(Color::DarkGray),
+        })
+        .collect()
+}
+
+
+// The changed lines of a diff with a little unchanged context; None stands for a run of
+// unchanged lines left out
+fn diff_hunks(diff: &[DiffLine]) -> Vec<Option<&DiffLine>> {
+    let changed: Vec<usize> = diff.iter().enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Same(_)))
+        .map(|(index, _)| index)
+        .collect();
+    let near_change = |index: usize| changed.iter().any(|&change| change.abs_diff(index) <= DIFF_CONTEXT);
+    let mut hunks = Vec::new();
+    let mut skipped = false;
+    for (index, line) in diff.iter().enumerate() {
+        if near_change(index) {
+            if skipped {
+                hunks.push(None);
+                skipped = false;
+            }
+            hunks.push(Some(line));
+        } else {
+            skipped = true;
+        }
+    }
+    if skipped {
+        hunks.push(None);
+    }
+    hunks
+}
+
+
+// A multi-record value's text with the bodies of folded records replaced by a one-line
+// note, and the record each resulting line belongs to (a record's separator line included)
+fn fold_records(text: &str, records: &[Range<usize>], folded: &BTreeSet<usize>) -> (String, Vec<usize>) {
+    let mut lines = vec![];
+    let mut owners = vec![];
+    for (index, line) in text.split('\n').enumerate() {
+        // a record's separator is the line just before its body
+        let owner = records.iter().rposition(|record| record.start <= index + 1).unwrap_or(0);
+        if folded.contains(&owner) && records[owner].contains(&index) {
+            if index == records[owner].start {
+                let count = records[owner].len();
+                lines.push(format!("  ... {} line{} folded", count, if count == 1 { "" } else { "s" }));
+                owners.push(owner);
             }
+            continue;
         }
+        lines.push(line.to_string());
+        owners.push(owner);
+    }
+    (lines.join("\n"), owners)
+}
+
+
+fn mark_location(mark: &Mark) -> String {
+    match &mark.full_key {
+        Some(full_key) => format!("{}: {}", mark.tree_name, full_key),
+        None => mark.tree_name.clone(),
     }
+}
+
+
+// A tree name made safe to use in a file name
+fn file_stem(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).replace(|c: char| !c.is_alphanumeric() && c != '-' && c != '_', "_")
+}
 
-    total_lines
+
+// A rectangle of the given percentage size centered in `area`, for popups
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TempDb;
+    use std::path::Path;
+    use unicode_width::UnicodeWidthStr;
+
+    // A TuiApp drawing into an in-memory buffer of the given size, with a stub clipboard
+    fn open_tui(path: &Path, width: u16, height: u16, tree: Option<&str>) -> TuiApp {
+        let screen = || Ok(Screen::Test(ratatui::Terminal::new(ratatui::backend::TestBackend::new(width, height))?));
+        let start_tree = tree.map(|tree| tree.as_bytes().to_vec());
+        let mut tui = TuiApp::open(screen, path.to_path_buf(), Config::default(), false, false, None, true, start_tree, false, false)
+            .expect("test database opens");
+        tui.clipboard = Clipboard::stub();
+        tui.draw().expect("first frame draws");
+        tui
+    }
+
+    // A key press followed by a redraw, as the run loop does
+    fn press(tui: &mut TuiApp, code: KeyCode) {
+        tui.handle_event(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)), Arc::new(AtomicBool::new(true)))
+            .expect("key is handled");
+        tui.draw().expect("frame draws");
+        tui.follow_list_height().expect("list follows the pane");
+    }
+
+    #[test]
+    fn empty_database_navigation() {
+        let db = TempDb::new("empty-navigation");
+        let mut tui = open_tui(&db.0, 80, 24, None);
+        let keys = [KeyCode::PageDown, KeyCode::End, KeyCode::Home, KeyCode::Enter, KeyCode::Backspace];
+        for code in keys {
+            press(&mut tui, code);
+        }
+        // and inside the empty default tree
+        tui.view_mode = ViewMode::Trees;
+        tui.list_offset = 0;
+        tui.list_state.select(Some(0));
+        press(&mut tui, KeyCode::Enter);
+        assert!(matches!(tui.view_mode, ViewMode::Keys));
+        assert_eq!(tui.app.total_keys, 0);
+        for code in keys {
+            press(&mut tui, code);
+        }
+    }
+
+    // The text last drawn, row by row
+    fn screen_text(tui: &TuiApp) -> String {
+        let Screen::Test(terminal) = &tui.terminal else { unreachable!("tests draw into a TestBackend") };
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn leaving_the_field_editor_with_changes_asks_first() {
+        let db = TempDb::with_keys("field-editor", "t", &[("cfg", r#"{"a":1,"b":2}"#)]);
+        let mut tui = open_tui(&db.0, 100, 24, Some("t"));
+        press(&mut tui, KeyCode::Tab);
+        press(&mut tui, KeyCode::Char('f'));
+        assert!(tui.map_editor.is_some());
+        press(&mut tui, KeyCode::Char('d')); // remove field a
+        assert_eq!(tui.unsaved_count(), 1);
+        assert!(screen_text(&tui).contains("| 1 unsaved change"), "the path bar counts the field editor's changes");
+        press(&mut tui, KeyCode::Esc);
+        assert!(tui.map_editor.is_some(), "the edit is kept until saved or discarded");
+        assert!(screen_text(&tui).contains("Unsaved edits to cfg: s save them, d discard them"));
+        press(&mut tui, KeyCode::Char('d'));
+        assert!(tui.map_editor.is_none());
+        assert_eq!(tui.unsaved_count(), 0);
+        assert!(!screen_text(&tui).contains("unsaved change"));
+        assert_eq!(tui.app.value_of("cfg").unwrap().as_deref(), Some(&br#"{"a":1,"b":2}"#[..]));
+    }
+
+    #[test]
+    fn csv_trees_draw_empty_and_header_only_values() {
+        let db = TempDb::with_keys("csv-table", "t", &[("a", ""), ("b", "name,size"), ("c", "name,size\nx,1")]);
+        let mut tui = open_tui(&db.0, 80, 24, Some("t"));
+        tui.app.format_hint = Some(format::FormatHint::Csv);
+        for code in [KeyCode::Home, KeyCode::Down, KeyCode::Down] {
+            press(&mut tui, code);
+            assert_eq!(tui.max_scroll, 0);
+        }
+        assert!(screen_text(&tui).contains("[table 1x2]"));
+    }
+
+    #[test]
+    fn crlf_values_draw_as_plain_lines() {
+        let value = (1..=30).map(|line| format!("line {}", line)).collect::<Vec<_>>().join("\r\n");
+        let db = TempDb::with_keys("crlf", "t", &[("doc", value.as_str())]);
+        let tui = open_tui(&db.0, 80, 16, Some("t"));
+        assert!(tui.page_height > 0 && tui.page_height < 30);
+        assert_eq!(tui.max_scroll, 30 - tui.page_height);
+        let text = screen_text(&tui);
+        assert!(text.contains("30 lines"), "{}", text);
+        assert!(text.contains("CRLF"), "{}", text);
+        assert!(text.contains("line 1 "), "{}", text);
+        assert!(!text.contains('\r'));
+    }
+
+    #[test]
+    fn startup_message_tells_new_databases_from_unversioned_ones() {
+        let message = |db: &TempDb| {
+            db.wait_for_lock();
+            open_tui(&db.0, 80, 24, None).status_message.clone().unwrap_or_default()
+        };
+        let db = TempDb::new("startup-new");
+        assert!(message(&db).starts_with("New database"));
+        assert!(message(&db).starts_with("sled format 0.34"));
+
+        let db = TempDb::new("startup-unversioned");
+        std::fs::create_dir_all(&db.0).unwrap();
+        std::fs::write(db.0.join("notes.txt"), "not a database yet").unwrap();
+        let text = message(&db);
+        assert!(text.starts_with("sled format unknown"), "{}", text);
+    }
+
+    #[test]
+    fn page_down_and_end_stop_at_the_last_of_two_keys() {
+        let db = TempDb::with_keys("two-keys", "t", &[("a", "1"), ("b", "2")]);
+        let mut tui = open_tui(&db.0, 80, 40, Some("t"));
+        assert_eq!(tui.app.total_keys, 2);
+        for code in [KeyCode::PageDown, KeyCode::PageDown, KeyCode::Home, KeyCode::End, KeyCode::End] {
+            press(&mut tui, code);
+            let expected = if code == KeyCode::Home { 0 } else { 1 };
+            assert_eq!(tui.list_state.selected(), Some(expected));
+            assert_eq!(tui.list_offset, 0);
+            assert_eq!(tui.app.current_key_range.keys.len(), 2);
+        }
+    }
+
+    #[test]
+    fn y_copies_the_full_key_and_the_value() {
+        let db = TempDb::with_keys("copy", "t", &[("app/name", "sledit"), ("app/version", "1")]);
+        let mut tui = open_tui(&db.0, 80, 24, Some("t"));
+        press(&mut tui, KeyCode::Enter); // into app
+        press(&mut tui, KeyCode::Char('y'));
+        press(&mut tui, KeyCode::Tab);
+        press(&mut tui, KeyCode::Char('y'));
+        assert_eq!(tui.clipboard.copied, Some(vec!["app/name".to_string(), "sledit".to_string()]));
+    }
+
+    // Rows the value pane's paragraph fills when drawn `width` columns wide
+    fn drawn_rows(text: &str, width: u16) -> usize {
+        let area = ratatui::layout::Rect::new(0, 0, width, 20);
+        let mut buffer = ratatui::buffer::Buffer::empty(area);
+        ratatui::widgets::Widget::render(Paragraph::new(text).wrap(ratatui::widgets::Wrap { trim: false }), area, &mut buffer);
+        (0..area.height)
+            .filter(|&y| (0..width).any(|x| buffer[(x, y)].symbol() != " "))
+            .map(|y| y as usize + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    #[test]
+    fn wrapped_lines_count_graphemes_by_display_width() {
+        let accented = "h\u{e9}llo"; // precomposed é
+        let combining = "e\u{301}e\u{301}e\u{301}"; // three é of two code points each
+        let cjk = "\u{65e5}\u{672c}\u{8a9e}"; // three characters two columns wide
+        let rows = |text: &str| [1, 2, 3, 5].map(|width| calculate_wrapped_lines(text, width));
+        assert_eq!(rows(accented), [5, 3, 2, 1]);
+        assert_eq!(rows(combining), [3, 2, 1, 1]);
+        // a wide character never splits across rows; one column is too narrow to draw any
+        assert_eq!(rows(cjk), [1, 3, 2, 1]);
+        for text in [accented, combining, cjk] {
+            for width in 1..=7 {
+                assert_eq!(calculate_wrapped_lines(text, width), drawn_rows(text, width), "{:?} at width {}", text, width);
+            }
+        }
+        // the hanging indent wraps in display columns too, keeping every character
+        let line = format!("{} {}{}", cjk, cjk, combining);
+        for width in [1, 2, 5, 7] {
+            let wrapped = hanging_indent_wrap(&line, width);
+            for row in wrapped.split('\n') {
+                assert!(row.width() <= (width as usize).max(2), "{:?} is wider than {}", row, width);
+            }
+            let kept: String = wrapped.split('\n').map(|row| row.trim_start()).collect();
+            assert_eq!(kept.replace(' ', ""), line.replace(' ', ""), "at width {}", width);
+        }
+        assert_eq!(hanging_indent_wrap(&line, 7), "\u{65e5}\u{672c}\u{8a9e} \n   \u{65e5}\u{672c}\n   \u{8a9e}e\u{301}e\u{301}\n   e\u{301}");
+    }
+}